@@ -7,12 +7,31 @@ use std::time::Duration;
 
 use cubist_sdk::core::*;
 use crate::cubist_gen::*;
+use clap::Parser;
 use ethers::types::U256;
+use template_support::bridge::{exit_if_not_bridged, when_bridged, BridgeArgs, Pair};
+use template_support::profile::ProfileArgs;
+use template_support::relay::Wait;
 
-#[tokio::main]
-async fn main() -> eyre::Result<()> {
+#[derive(Parser)]
+struct Args {
+    #[clap(flatten)]
+    bridge: BridgeArgs,
+    #[clap(flatten)]
+    profile: ProfileArgs,
+}
+
+fn main() -> eyre::Result<()> {
     color_eyre::install()?;
     tracing_subscriber::fmt::init();
+    let args = Args::parse();
+    // selecting the profile sets an environment variable, so it must happen
+    // before the runtime starts its worker threads
+    args.profile.select(env!("CARGO_MANIFEST_DIR"))?;
+    tokio::runtime::Runtime::new()?.block_on(run(args))
+}
+
+async fn run(args: Args) -> eyre::Result<()> {
     let cubist = cubist().await?;    
     println!("Deploy consumers");
     let r1 = R1::deploy(()).await?;
@@ -22,40 +41,64 @@ async fn main() -> eyre::Result<()> {
     println!("Deploy producers");
     let s1 = S1::deploy(ch.addr(S1::target())).await?;
     let s2 = S2::deploy(ch.addr(S2::target())).await?;
-    assert_eq!(true, cubist.when_bridged(None).await);
+    let (ch_target, s1_target, s2_target) = (Channel::target(), S1::target(), S2::target());
+    let (ch_client, s1_client, s2_client) = (ch.client(), s1.client(), s2.client());
+    let shims = (
+        r1.addr(ch_target),
+        r2.addr(ch_target),
+        ch.addr(s1_target),
+        ch.addr(s2_target),
+    );
+    let (ch_client_ref, s1_client_ref, s2_client_ref) = (&ch_client, &s1_client, &s2_client);
+    let (ch_ref, r1_ref, r2_ref) = (&ch, &r1, &r2);
+    let pairs = || async move {
+        vec![
+            Pair::new("R1", R1::target(), ch_target, shims.0, ch_client_ref)
+                .await
+                .bridged(r1_ref.is_bridged().await),
+            Pair::new("R2", R2::target(), ch_target, shims.1, ch_client_ref)
+                .await
+                .bridged(r2_ref.is_bridged().await),
+            Pair::new("Channel", ch_target, s1_target, shims.2, s1_client_ref)
+                .await
+                .bridged(ch_ref.is_bridged().await),
+            Pair::new("Channel", ch_target, s2_target, shims.3, s2_client_ref)
+                .await
+                .bridged(ch_ref.is_bridged().await),
+        ]
+    };
+    exit_if_not_bridged(
+        when_bridged(cubist.when_bridged(None), args.bridge.timeout(), pairs).await,
+    )?;
     println!("Bridged");
 
     println!("send(1)");
     let num1 = U256::from(1);
     s1.send(num1).send().await?.await?;
 
-    for i in 0..15 {
-        let msg = format!("({i}) ch: {:?}, r1: {:?}, r2: {:?}",
-                          ch.retrieve().call().await?,
-                          r1.retrieve().call().await?,
-                          r2.retrieve().call().await?);
-        println!("{}", msg);
-        if msg.ends_with("1, r1: 1, r2: 1") {
-            break;
-        }
-        tokio::time::sleep(Duration::from_secs(1)).await;
-    }
+    let relayed = Wait::new("ch, r1, r2 to be 1")
+        .for_state(|| async move {
+            let vals = (ch_ref.retrieve().call().await?,
+                        r1_ref.retrieve().call().await?,
+                        r2_ref.retrieve().call().await?);
+            println!("ch: {:?}, r1: {:?}, r2: {:?}", vals.0, vals.1, vals.2);
+            Ok::<_, eyre::Report>(vals)
+        }, |vals| *vals == (num1, num1, num1)).await?;
+    println!("Relayed after {:?}", relayed.latency);
 
     println!("send(2)");
     let num2 = U256::from(2);
     s2.send(num2).send().await?.await?;
 
-    for i in 0..15 {
-        let msg = format!("({i}) ch: {:?}, r1: {:?}, r2: {:?}",
-                 ch.retrieve().call().await?,
-                 r1.retrieve().call().await?,
-                          r2.retrieve().call().await?);
-        println!("{msg}");
-        if msg.ends_with("2, r1: 2, r2: 2") {
-            break;
-        }
-        tokio::time::sleep(Duration::from_secs(1)).await;
-    }
+    let relayed = Wait::new("ch, r1, r2 to be 2")
+        .for_state(|| async move {
+            let vals = (ch_ref.retrieve().call().await?,
+                        r1_ref.retrieve().call().await?,
+                        r2_ref.retrieve().call().await?);
+            println!("ch: {:?}, r1: {:?}, r2: {:?}", vals.0, vals.1, vals.2);
+            Ok::<_, eyre::Report>(vals)
+        }, |vals| *vals == (num2, num2, num2)).await?;
+    println!("Relayed after {:?}", relayed.latency);
 
     println!("Done");
     
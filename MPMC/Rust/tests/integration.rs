@@ -0,0 +1,45 @@
+#![allow(non_snake_case)]
+
+#[path = "../src/cubist_gen.rs"]
+mod cubist_gen;
+
+use crate::cubist_gen::*;
+use ethers::types::U256;
+use template_support::{relay::Wait, testing::TestEnv};
+
+#[tokio::test]
+async fn deploys_and_updates_values_cross_chain() -> eyre::Result<()> {
+    let _env = TestEnv::start(env!("CARGO_MANIFEST_DIR"))?;
+    let cubist = cubist().await?;
+
+    let r1 = R1::deploy(()).await?;
+    let r2 = R2::deploy(()).await?;
+    let ch = Channel::deploy((r1.addr(Channel::target()), r2.addr(Channel::target()))).await?;
+    let s1 = S1::deploy(ch.addr(S1::target())).await?;
+    let s2 = S2::deploy(ch.addr(S2::target())).await?;
+
+    // wait for the bridge to be established before calling any cross-chain contract methods
+    assert!(cubist.when_bridged(None).await);
+
+    let (ch, r1, r2) = (&ch, &r1, &r2);
+    for (call, num) in [
+        (s1.send(1.into()), U256::from(1)),
+        (s2.send(2.into()), U256::from(2)),
+    ] {
+        call.send().await?.await?;
+        Wait::new(format!("'Channel', 'R1' and 'R2' to store {num}"))
+            .for_state(
+                || async move {
+                    Ok::<_, eyre::Report>((
+                        ch.retrieve().call().await?,
+                        r1.retrieve().call().await?,
+                        r2.retrieve().call().await?,
+                    ))
+                },
+                |vals| *vals == (num, num, num),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
@@ -1,37 +1,58 @@
 mod cubist_gen;
 
-use std::{fs, fmt::Display};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use crate::cubist_gen::*;
 use clap::{Args, Parser, Subcommand};
-use color_eyre::owo_colors::OwoColorize;
-use ethers::types::U256;
-use eyre::{Context, Result};
+use ethers::{
+    abi::ParamType,
+    providers::Middleware,
+    types::{Address, U256},
+};
+use eyre::{bail, ensure, Context, Result};
+use serde::Deserialize;
+use template_support::{
+    address::{AddressBook, Resolver, ADDRESS_BOOK_FILE},
+    amount::{de_amount, de_opt_amount, parse_amount},
+    batch::{print_results, BatchArgs, Lane, Operation},
+    bridge::{exit_if_not_bridged, when_bridged, Pair},
+    deploy::{DeployFlags, Plan, Status, Step},
+    doctor::Doctor,
+    gas::GasArgs,
+    manifest::{ContractEntry, Manifest, ManifestCommand},
+    output::{print_records, OutputFormat, Record},
+    profile::ProfileArgs,
+    receipt::{send_call, Receipt},
+    s_action, s_contract, s_header, s_value,
+    signers::{ChainSigner, Signers, SIGNERS_FILE},
+    table::{Column, Table},
+};
 
 const SENDER: &str = "StorageSender";
 const RECEIVER: &str = "StorageReceiver";
 
-macro_rules! s_action {
-    ($x: expr) => {
-        $x.bold().green()
-    };
-}
-
-macro_rules! s_value {
-    ($x: expr) => {
-        $x.yellow()
-    };
-}
-
-macro_rules! s_contract {
-    ($x: expr) => {
-        $x.blue()
-    };
-}
-
 #[derive(Debug, Parser)]
 #[clap(about = "Multi-chain Storage dApp", long_about = None)]
 struct Cli {
+    /// Output format of commands that list contracts ('list'), of the receipts of sent
+    /// transactions ('store-*', 'inc', 'dec') and of the report of 'batch'.
+    #[clap(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+    /// Account that signs transactions ('store-*', 'inc', 'dec', and 'batch' operations
+    /// without a 'sender'): an account index on the called contract's chain, 'chain:index',
+    /// a hex address or an alias from 'address-book.json'.  Must be one of the local accounts
+    /// on that chain, or the keystore or private key selected for that chain in
+    /// 'signers.json'.  Defaults to the project's default sender.
+    #[clap(long, global = true)]
+    from: Option<String>,
+    #[clap(flatten)]
+    profile: ProfileArgs,
+    #[clap(flatten)]
+    gas: GasArgs,
     #[clap(subcommand)]
     command: Command,
 }
@@ -39,8 +60,13 @@ struct Cli {
 #[derive(Debug, Subcommand)]
 enum Command {
     /// Deploy both 'StorageSender' and 'StorageReceiver',
-    /// configuring the sender to forward values to the receiver
+    /// configuring the sender to forward values to the receiver.
+    /// Refuses to replace an existing deployment unless '--force' is given.
     Deploy(DeployArgs),
+    /// Export the deployment to a portable manifest, or import one to reuse
+    /// an existing deployment without redeploying.
+    #[clap(subcommand)]
+    Manifest(ManifestCommand),
     /// List the current deployments.  Only one instance of 'StorageSender'
     /// and 'StorageReceiver' contracts may exist at a time.
     List,
@@ -57,62 +83,116 @@ enum Command {
     /// Decrement the value of 'StorageSender' by one; the relayer will automatically
     /// forward the new value to its 'StorageReceiver' contract.
     Dec,
+    /// Run the operations listed in a YAML or JSON file.  Each has a 'command' ('store',
+    /// 'store-sender', 'store-receiver', 'inc' or 'dec'), an 'amount' (the value to store,
+    /// or the step of 'inc' and 'dec', 1 by default) and optionally a 'sender' (like
+    /// '--from'); unknown commands and arguments are rejected before anything is sent.
+    /// Prints the transaction or the error of each operation, and fails if any operation
+    /// failed.
+    Batch(BatchArgs),
+    /// Diagnose the local environment: RPC endpoints, mnemonics, account funding,
+    /// deployed contracts and the relayer.
+    Doctor,
 }
 
 #[derive(Debug, Args)]
 struct DeployArgs {
     /// The value to which to initialize the 'StorageSender' contract.
-    #[clap(short = 's', long = "sender-value", default_value = "0")]
-    sender_value: u64,
+    #[clap(short = 's', long = "sender-value", default_value = "0", value_parser = parse_amount)]
+    sender_value: U256,
     /// The value to which to initialize the 'StorageReceiver' contract.
-    #[clap(short = 'r', long = "receiver-value", default_value = "0")]
-    receiver_value: u64,
+    #[clap(short = 'r', long = "receiver-value", default_value = "0", value_parser = parse_amount)]
+    receiver_value: U256,
+    #[clap(flatten)]
+    flags: DeployFlags,
 }
 
 #[derive(Debug, Args)]
 struct StoreArgs {
-    /// The value to store.
-    #[clap(index = 1)]
-    val: u64,
+    /// The value to store.  Accepts plain integers, hex ('0x...'),
+    /// scientific notation ('1e18') and unit suffixes ('1.5ether').
+    #[clap(index = 1, value_parser = parse_amount)]
+    val: U256,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     color_eyre::install()?;
     tracing_subscriber::fmt::init();
     let args = Cli::parse();
+    // selecting the profile sets an environment variable, so it must happen
+    // before the runtime starts its worker threads
+    args.profile.select(env!("CARGO_MANIFEST_DIR"))?;
+    tokio::runtime::Runtime::new()?.block_on(run(args))
+}
 
-    match args.command {
+async fn run(args: Cli) -> Result<()> {
+    let result = match args.command {
         Command::Deploy(args) => deploy(&args).await,
-        Command::List => list().await,
-        Command::Store(args) => store_sender(&args).await,
-        Command::StoreSender(args) => store_sender(&args).await,
-        Command::StoreReceiver(args) => store_receiver(&args).await,
-        Command::Inc => inc().await,
-        Command::Dec => dec().await,
-    }
+        Command::Manifest(cmd) => manifest(&cmd).await,
+        Command::List => list(args.output).await,
+        Command::Store(store_args) => {
+            store_sender(&store_args, args.from.as_deref(), &args.gas, args.output).await
+        }
+        Command::StoreSender(store_args) => {
+            store_sender(&store_args, args.from.as_deref(), &args.gas, args.output).await
+        }
+        Command::StoreReceiver(store_args) => {
+            store_receiver(&store_args, args.from.as_deref(), &args.gas, args.output).await
+        }
+        Command::Inc => inc(args.from.as_deref(), &args.gas, args.output).await,
+        Command::Dec => dec(args.from.as_deref(), &args.gas, args.output).await,
+        Command::Batch(batch_args) => {
+            batch(&batch_args, args.from.as_deref(), &args.gas, args.output).await
+        }
+        Command::Doctor => doctor().await,
+    };
+    exit_if_not_bridged(result)
+}
+
+/// The deployment steps, and which of them an earlier deployment completed.
+async fn deploy_plan(args: &DeployArgs) -> Plan {
+    let receiver = StorageReceiver::deployed().await.ok();
+    let sender = StorageSender::deployed().await.ok();
+    let rec_shim = receiver.as_ref().map(|c| c.addr(StorageSender::target()));
+
+    let sender_status = match (&sender, &receiver) {
+        (None, _) => Status::Pending,
+        (Some(s), Some(_)) => Status::Done(s.address()),
+        // its receiver is gone
+        (Some(s), None) => Status::Stale(s.address()),
+    };
+    Plan::new(vec![
+        Step::new(
+            StorageReceiver::target(),
+            RECEIVER,
+            receiver.map_or(Status::Pending, |c| Status::Done(c.address())),
+        )
+        .arg(args.receiver_value),
+        Step::new(StorageSender::target(), SENDER, sender_status)
+            .arg(args.sender_value)
+            .arg(rec_shim.map_or_else(|| format!("<{RECEIVER} shim>"), |a| format!("{a:?}"))),
+    ])
 }
 
 async fn deploy(args: &DeployArgs) -> Result<()> {
     let cubist = cubist().await?;
 
-    let deploy_dir = cubist.config().deploy_dir();
-    if deploy_dir.is_dir() {
-        println!(
-            "{} deployment dir: {}",
-            s_action!("Deleting"),
-            s_value!(deploy_dir.display()),
-        );
-        fs::remove_dir_all(deploy_dir).context("Deleting previous deployment dir")?;
+    let mut plan = deploy_plan(args).await;
+    if !plan.prepare(&args.flags, &cubist.config().deploy_dir())? {
+        return Ok(());
     }
 
-    println!(
-        "{} {}({})",
-        s_action!("Deploying"),
-        s_contract!(RECEIVER),
-        s_value!(args.receiver_value),
-    );
-    let receiver = StorageReceiver::deploy(U256::from(args.receiver_value)).await?;
+    let receiver = if plan.step(RECEIVER)?.is_pending() {
+        println!(
+            "{} {}({})",
+            s_action!("Deploying"),
+            s_contract!(RECEIVER),
+            s_value!(args.receiver_value),
+        );
+        StorageReceiver::deploy(args.receiver_value).await?
+    } else {
+        StorageReceiver::deployed().await?
+    };
 
     println!(
         "{} {}({})",
@@ -121,131 +201,496 @@ async fn deploy(args: &DeployArgs) -> Result<()> {
         s_value!(args.sender_value),
     );
     let rec_shim_addr = receiver.addr(StorageSender::target());
-    StorageSender::deploy((U256::from(args.sender_value), rec_shim_addr)).await?;
+    let sender = StorageSender::deploy((args.sender_value, rec_shim_addr)).await?;
 
     // wait for the bridge to be up
-    assert!(cubist.when_bridged(None).await);
+    let (sender_client, receiver) = (sender.client(), &receiver);
+    let pairs = || async move {
+        let (sender_target, receiver_target) = (StorageSender::target(), StorageReceiver::target());
+        vec![Pair::new(
+            RECEIVER,
+            receiver_target,
+            sender_target,
+            rec_shim_addr,
+            &sender_client,
+        )
+        .await
+        .bridged(receiver.is_bridged().await)]
+    };
+    let waited = when_bridged(
+        cubist.when_bridged(None),
+        args.flags.bridge.timeout(),
+        pairs,
+    )
+    .await?;
 
-    println!("{}", s_action!("Done"));
+    println!("{} (bridged after {waited:.1?})", s_action!("Done"));
     Ok(())
 }
 
-const DASHES: &str = "──────────────────────────────────────────────────────────────";
-const SEP_TOP: (&str, &str, &str) = ("┌─", "─┬─", "─┐");
-const SEP_MID: (&str, &str, &str) = ("├─", "─┼─", "─┤");
-const SEP_DEF: (&str, &str, &str) = ("│ ", " │ ", " │");
-const SEP_BOT: (&str, &str, &str) = ("└─", "─┴─", "─┘");
-
-fn print_row<T1: Display, T2: Display, T3: Display, T4: Display>(
-    contract: T1,
-    value: T2,
-    target: T3,
-    address: T4,
-    sep: Option<(&str, &str, &str)>,
-) {
-    let sep = sep.unwrap_or(SEP_DEF);
-    println!(
-        "{}{contract:>15.15}{}{value:>5.5}{}{target:8.8}{}{address:>11.11}{}",
-        sep.0, sep.1, sep.1, sep.1, sep.2
-    );
-}
+async fn manifest(cmd: &ManifestCommand) -> Result<()> {
+    let cubist = cubist().await?;
+    let deploy_dir = cubist.config().deploy_dir();
+    let (sender_target, receiver_target) = (StorageSender::target(), StorageReceiver::target());
+    let sender_provider = cubist.project(sender_target).unwrap().provider();
+    let receiver_provider = cubist.project(receiver_target).unwrap().provider();
 
-async fn list() -> Result<()> {
-    let receiver_tuple = match StorageReceiver::deployed().await {
-        Ok(rec) => {
-            let val = rec.retrieve().await?;
-            (
+    match cmd {
+        ManifestCommand::Export { file } => {
+            let receiver = StorageReceiver::deployed()
+                .await
+                .context("Contracts not deployed; call 'deploy' first")?;
+            let sender = StorageSender::deployed()
+                .await
+                .context("Contracts not deployed; call 'deploy' first")?;
+
+            let mut rec_entry = ContractEntry::describe(
                 RECEIVER,
-                StorageReceiver::target(),
-                Some(rec.address()),
-                Some(val),
+                receiver_target,
+                &receiver_provider,
+                receiver.address(),
+                &[ParamType::Uint(256)],
             )
-        }
-        _ => (RECEIVER, StorageReceiver::target(), None, None),
-    };
-    let sender_tuple = match StorageSender::deployed().await {
-        Ok(sender) => {
-            let val = sender.retrieve().await?;
-            (
+            .await?;
+            rec_entry
+                .shims
+                .insert(sender_target.to_string(), receiver.addr(sender_target));
+
+            let sender_entry = ContractEntry::describe(
                 SENDER,
-                StorageSender::target(),
-                Some(sender.address()),
-                Some(val),
+                sender_target,
+                &sender_provider,
+                sender.address(),
+                &[ParamType::Uint(256), ParamType::Address],
             )
+            .await?;
+
+            Manifest::new(vec![rec_entry, sender_entry], &deploy_dir)?.save(file.as_deref())
         }
-        _ => (SENDER, StorageSender::target(), None, None),
+        ManifestCommand::Import { file, force } => {
+            let manifest = Manifest::load(file)?;
+            let receiver = manifest.contract(RECEIVER)?;
+            receiver.verify(&receiver_provider).await?;
+            receiver
+                .verify_shim(sender_target, &sender_provider)
+                .await?;
+            manifest.contract(SENDER)?.verify(&sender_provider).await?;
+
+            if !force {
+                if let Ok(c) = StorageReceiver::deployed().await {
+                    bail!(
+                        "Already deployed ('{RECEIVER}' at {:?}); use '--force' to replace it",
+                        c.address()
+                    );
+                }
+            }
+            manifest.restore_deploy_dir(&deploy_dir)
+        }
+    }
+}
+
+const COLUMNS: [Column; 4] = [
+    Column::right(15),
+    // wide enough for any U256 (78 digits)
+    Column::right(78),
+    Column::left(8),
+    Column::right(11),
+];
+
+async fn list(output: OutputFormat) -> Result<()> {
+    let mut receiver_record = Record {
+        target: StorageReceiver::target().to_string(),
+        label: Some(RECEIVER.to_owned()),
+        ..Default::default()
     };
-    print_row(DASHES, DASHES, DASHES, DASHES, Some(SEP_TOP));
-    print_row(
-        "contract".bold(),
-        "value".bold(),
-        "target".bold(),
-        "address".bold(),
-        None,
-    );
-    print_row(DASHES, DASHES, DASHES, DASHES, Some(SEP_MID));
-    for t in [sender_tuple, receiver_tuple] {
-        print_row(
-            s_contract!(&t.0),
-            s_value!(t.3.map(|x| x.to_string()).unwrap_or_default()),
-            s_action!(t.1.to_string()),
-            s_value!(t.2.map(|x| x.to_string()).unwrap_or_default()),
-            None,
-        );
+    if let Ok(rec) = StorageReceiver::deployed().await {
+        receiver_record.value = Some(rec.retrieve().await?);
+        receiver_record.address = Some(rec.address());
+    }
+    let mut sender_record = Record {
+        target: StorageSender::target().to_string(),
+        label: Some(SENDER.to_owned()),
+        ..Default::default()
+    };
+    if let Ok(sender) = StorageSender::deployed().await {
+        sender_record.value = Some(sender.retrieve().await?);
+        sender_record.address = Some(sender.address());
+    }
+    let records = [sender_record, receiver_record];
+
+    if output != OutputFormat::Table {
+        return print_records(output, &records);
+    }
+
+    let mut table = Table::new(
+        &COLUMNS,
+        &[
+            &s_header!("contract"),
+            &s_header!("value"),
+            &s_header!("target"),
+            &s_header!("address"),
+        ],
+    )?;
+    for r in &records {
+        table.row(&[
+            &s_contract!(r.label.clone().unwrap_or_default()),
+            &s_value!(r.value.map(|x| x.to_string()).unwrap_or_default()),
+            &s_action!(r.target),
+            &s_value!(r.address.map(|x| x.to_string()).unwrap_or_default()),
+        ])?;
     }
-    print_row(DASHES, DASHES, DASHES, DASHES, Some(SEP_BOT));
+    table.print();
     println!();
     Ok(())
 }
 
-async fn store_sender(args: &StoreArgs) -> Result<()> {
+fn signers_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(SIGNERS_FILE)
+}
+
+/// Resolves '--from': the local accounts on either chain, or an alias from the
+/// address book.
+async fn resolver() -> Result<Resolver> {
+    let cubist = cubist().await?;
+    let mut resolver = Resolver::new(None);
+    for target in [StorageSender::target(), StorageReceiver::target()] {
+        let proj = cubist.project(target).unwrap();
+        resolver.add_accounts(target.to_string(), proj.accounts().await?);
+    }
+    let book = Path::new(env!("CARGO_MANIFEST_DIR")).join(ADDRESS_BOOK_FILE);
+    resolver.set_address_book(AddressBook::load(book)?);
+    Ok(resolver)
+}
+
+async fn store_sender(
+    args: &StoreArgs,
+    from: Option<&str>,
+    gas: &GasArgs,
+    output: OutputFormat,
+) -> Result<()> {
     let sender = StorageSender::deployed()
         .await
         .context("Contracts not deployed; call 'cargo run -- deploy' first")?;
-    println!(
-        "\n{} {}.store({})\n",
+    let target = StorageSender::target();
+    let signer = Signers::load(signers_path())?.signer(
+        &target.to_string(),
+        from,
+        &resolver().await?,
+        sender.project().sender().await?,
+    )?;
+    send_store_sender(args.val, &signer, gas)
+        .await?
+        .print(output)
+}
+
+async fn store_receiver(
+    args: &StoreArgs,
+    from: Option<&str>,
+    gas: &GasArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let receiver = StorageReceiver::deployed()
+        .await
+        .context("Contracts not deployed; call 'deploy' first")?;
+    let target = StorageReceiver::target();
+    let signer = Signers::load(signers_path())?.signer(
+        &target.to_string(),
+        from,
+        &resolver().await?,
+        receiver.project().sender().await?,
+    )?;
+    send_store_receiver(args.val, &signer, gas)
+        .await?
+        .print(output)
+}
+
+async fn inc(from: Option<&str>, gas: &GasArgs, output: OutputFormat) -> Result<()> {
+    let sender = StorageSender::deployed().await?;
+    let target = StorageSender::target();
+    let signer = Signers::load(signers_path())?.signer(
+        &target.to_string(),
+        from,
+        &resolver().await?,
+        sender.project().sender().await?,
+    )?;
+    send_inc(1.into(), &signer, gas).await?.print(output)
+}
+
+async fn dec(from: Option<&str>, gas: &GasArgs, output: OutputFormat) -> Result<()> {
+    let sender = StorageSender::deployed().await?;
+    let target = StorageSender::target();
+    let signer = Signers::load(signers_path())?.signer(
+        &target.to_string(),
+        from,
+        &resolver().await?,
+        sender.project().sender().await?,
+    )?;
+    send_dec(1.into(), &signer, gas).await?.print(output)
+}
+
+/// Store `val` to 'StorageSender', signed by `signer`.
+async fn send_store_sender(val: U256, signer: &ChainSigner, gas: &GasArgs) -> Result<Receipt> {
+    let sender = StorageSender::deployed()
+        .await
+        .context("Contracts not deployed; call 'cargo run -- deploy' first")?;
+    eprintln!(
+        "\n{} {}.store({}) from {:?}\n",
         s_action!("Calling"),
         s_contract!(SENDER),
-        s_value!(args.val)
+        s_value!(val),
+        s_value!(signer.address())
     );
-    sender.store(U256::from(args.val)).send().await?.await?;
-    Ok(())
+    let target = StorageSender::target();
+    let policy = gas.policy(env!("CARGO_MANIFEST_DIR"), &target.to_string())?;
+    let mut call = sender.store(val).from(signer.address());
+    let (receipt, _) = send_call(
+        &sender.client(),
+        target,
+        SENDER,
+        sender.abi(),
+        &mut call,
+        signer,
+        &policy,
+    )
+    .await?;
+    Ok(receipt)
 }
 
-async fn store_receiver(args: &StoreArgs) -> Result<()> {
+/// Store `val` to 'StorageReceiver', signed by `signer`.
+async fn send_store_receiver(val: U256, signer: &ChainSigner, gas: &GasArgs) -> Result<Receipt> {
     let receiver = StorageReceiver::deployed()
         .await
         .context("Contracts not deployed; call 'deploy' first")?;
-    println!(
-        "\n{} {}.store({})\n",
+    eprintln!(
+        "\n{} {}.store({}) from {:?}\n",
         s_action!("Calling"),
         s_contract!(RECEIVER),
-        s_value!(args.val)
+        s_value!(val),
+        s_value!(signer.address())
     );
-    receiver.store(U256::from(args.val)).send().await?.await?;
-    Ok(())
+    let target = StorageReceiver::target();
+    let policy = gas.policy(env!("CARGO_MANIFEST_DIR"), &target.to_string())?;
+    let mut call = receiver.store(val).from(signer.address());
+    let (receipt, _) = send_call(
+        &receiver.client(),
+        target,
+        RECEIVER,
+        receiver.abi(),
+        &mut call,
+        signer,
+        &policy,
+    )
+    .await?;
+    Ok(receipt)
 }
 
-async fn inc() -> Result<()> {
+/// Increment the value of 'StorageSender' by `by`, signed by `signer`.
+async fn send_inc(by: U256, signer: &ChainSigner, gas: &GasArgs) -> Result<Receipt> {
     let sender = StorageSender::deployed().await?;
-    println!(
-        "\n{} {}.inc({})\n",
+    eprintln!(
+        "\n{} {}.inc({}) from {:?}\n",
         s_action!("Calling"),
         s_contract!(SENDER),
-        s_value!(1)
+        s_value!(by),
+        s_value!(signer.address())
     );
-    sender.inc(1.into()).send().await?.await?;
-    Ok(())
+    let target = StorageSender::target();
+    let policy = gas.policy(env!("CARGO_MANIFEST_DIR"), &target.to_string())?;
+    let mut call = sender.inc(by).from(signer.address());
+    let (receipt, _) = send_call(
+        &sender.client(),
+        target,
+        SENDER,
+        sender.abi(),
+        &mut call,
+        signer,
+        &policy,
+    )
+    .await?;
+    Ok(receipt)
 }
 
-async fn dec() -> Result<()> {
+/// Decrement the value of 'StorageSender' by `by`, signed by `signer`.
+async fn send_dec(by: U256, signer: &ChainSigner, gas: &GasArgs) -> Result<Receipt> {
     let sender = StorageSender::deployed().await?;
-    println!(
-        "{} {}.dec({}) ... ",
+    eprintln!(
+        "\n{} {}.dec({}) from {:?}\n",
         s_action!("Calling"),
         s_contract!(SENDER),
-        s_value!(1)
+        s_value!(by),
+        s_value!(signer.address())
     );
-    sender.dec(1.into()).send().await?.await?;
-    Ok(())
+    let target = StorageSender::target();
+    let policy = gas.policy(env!("CARGO_MANIFEST_DIR"), &target.to_string())?;
+    let mut call = sender.dec(by).from(signer.address());
+    let (receipt, _) = send_call(
+        &sender.client(),
+        target,
+        SENDER,
+        sender.abi(),
+        &mut call,
+        signer,
+        &policy,
+    )
+    .await?;
+    Ok(receipt)
+}
+
+/// An entry of a batch file: a command with its 'amount' (the value to store, or
+/// the step of 'inc' and 'dec'), signed by `sender` (like '--from').
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+enum BatchOp {
+    #[serde(alias = "store")]
+    StoreSender {
+        #[serde(deserialize_with = "de_amount")]
+        amount: U256,
+        sender: Option<String>,
+    },
+    StoreReceiver {
+        #[serde(deserialize_with = "de_amount")]
+        amount: U256,
+        sender: Option<String>,
+    },
+    Inc {
+        #[serde(default, deserialize_with = "de_opt_amount")]
+        amount: Option<U256>,
+        sender: Option<String>,
+    },
+    Dec {
+        #[serde(default, deserialize_with = "de_opt_amount")]
+        amount: Option<U256>,
+        sender: Option<String>,
+    },
+}
+
+impl Operation for BatchOp {
+    fn command(&self) -> &'static str {
+        match self {
+            BatchOp::StoreSender { .. } => "store-sender",
+            BatchOp::StoreReceiver { .. } => "store-receiver",
+            BatchOp::Inc { .. } => "inc",
+            BatchOp::Dec { .. } => "dec",
+        }
+    }
+}
+
+/// The lane and signer of a batch operation.  Signers are unlocked once per
+/// chain and account, and kept in `unlocked`.
+async fn batch_op(
+    op: &BatchOp,
+    from: Option<&str>,
+    signers: &Signers,
+    resolver: &Resolver,
+    unlocked: &mut HashMap<(String, Option<String>), ChainSigner>,
+) -> Result<(Lane, ChainSigner)> {
+    let (chain, sender) = match op {
+        BatchOp::StoreSender { sender, .. }
+        | BatchOp::Inc { sender, .. }
+        | BatchOp::Dec { sender, .. } => (StorageSender::target(), sender),
+        BatchOp::StoreReceiver { sender, .. } => (StorageReceiver::target(), sender),
+    };
+
+    let from = sender.as_deref().or(from);
+    let key = (chain.to_string(), from.map(str::to_owned));
+    let signer = match unlocked.get(&key) {
+        Some(signer) => signer.clone(),
+        None => {
+            let default = cubist().await?.project(chain).unwrap().sender().await?;
+            let signer = signers.signer(&chain.to_string(), from, resolver, default)?;
+            unlocked.insert(key, signer.clone());
+            signer
+        }
+    };
+    Ok(((chain.to_string(), signer.address()), signer))
+}
+
+async fn batch(
+    args: &BatchArgs,
+    from: Option<&str>,
+    gas: &GasArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let ops: Vec<BatchOp> = args.load()?;
+    // each operation is sent with its account's pending nonce
+    let policy = gas.policy(
+        env!("CARGO_MANIFEST_DIR"),
+        &StorageSender::target().to_string(),
+    )?;
+    ensure!(
+        policy.nonce.is_none(),
+        "'--nonce' cannot be used with 'batch'"
+    );
+
+    let (signers, resolver) = (Signers::load(signers_path())?, resolver().await?);
+    let mut unlocked = HashMap::new();
+    let mut jobs = vec![];
+    for op in &ops {
+        jobs.push(batch_op(op, from, &signers, &resolver, &mut unlocked).await);
+    }
+
+    let results = args
+        .run(&ops, jobs, |op, signer| async move {
+            // 'inc' and 'dec' step by one unless given an amount
+            let step = |amount: &Option<U256>| amount.unwrap_or_else(|| 1.into());
+            match op {
+                BatchOp::StoreSender { amount, .. } => {
+                    send_store_sender(*amount, &signer, gas).await
+                }
+                BatchOp::StoreReceiver { amount, .. } => {
+                    send_store_receiver(*amount, &signer, gas).await
+                }
+                BatchOp::Inc { amount, .. } => send_inc(step(amount), &signer, gas).await,
+                BatchOp::Dec { amount, .. } => send_dec(step(amount), &signer, gas).await,
+            }
+        })
+        .await;
+    print_results(&results, output)
+}
+
+async fn doctor() -> Result<()> {
+    let mut doctor = Doctor::new(env!("CARGO_MANIFEST_DIR"))?;
+    doctor.check_networks().await;
+
+    println!("{}", s_header!("deployment"));
+    let cubist = match cubist().await {
+        Ok(cubist) => cubist,
+        Err(e) => {
+            doctor.fail("sdk", format!("Cannot load the Cubist project: {e:#}"));
+            return doctor.finish();
+        }
+    };
+    let (sender_target, receiver_target) = (StorageSender::target(), StorageReceiver::target());
+    let sender_provider = cubist.project(sender_target).unwrap().provider();
+    let receiver_provider = cubist.project(receiver_target).unwrap().provider();
+    let mut deployed = false;
+    if let Ok(rec) = StorageReceiver::deployed().await {
+        deployed = true;
+        doctor
+            .check_code(receiver_target, RECEIVER, &receiver_provider, rec.address())
+            .await;
+        let shim = format!("shim:{RECEIVER}");
+        doctor
+            .check_code(
+                sender_target,
+                &shim,
+                &sender_provider,
+                rec.addr(sender_target),
+            )
+            .await;
+    }
+    if let Ok(sender) = StorageSender::deployed().await {
+        deployed = true;
+        doctor
+            .check_code(sender_target, SENDER, &sender_provider, sender.address())
+            .await;
+    }
+    if deployed {
+        doctor
+            .check_bridged(cubist.when_bridged(None), Duration::from_secs(10))
+            .await;
+    } else {
+        doctor.warn("sdk", "Contracts not deployed; call 'deploy' first");
+    }
+    doctor.finish()
 }
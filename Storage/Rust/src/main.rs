@@ -1,12 +1,33 @@
 mod cubist_gen;
 
+use std::time::{Duration, Instant};
+
 use crate::cubist_gen::*;
+use clap::Parser;
 use ethers::types::U256;
+use template_support::bridge::{exit_if_not_bridged, when_bridged, BridgeArgs, Pair};
+use template_support::profile::ProfileArgs;
+use template_support::relay::Wait;
+
+#[derive(Parser)]
+struct Args {
+    #[clap(flatten)]
+    bridge: BridgeArgs,
+    #[clap(flatten)]
+    profile: ProfileArgs,
+}
 
-#[tokio::main]
-async fn main() -> eyre::Result<()> {
+fn main() -> eyre::Result<()> {
     color_eyre::install()?;
     tracing_subscriber::fmt::init();
+    let args = Args::parse();
+    // selecting the profile sets an environment variable, so it must happen
+    // before the runtime starts its worker threads
+    args.profile.select(env!("CARGO_MANIFEST_DIR"))?;
+    tokio::runtime::Runtime::new()?.block_on(run(args))
+}
+
+async fn run(args: Args) -> eyre::Result<()> {
     let cubist = cubist().await?;
 
     println!("Deploying");
@@ -14,7 +35,22 @@ async fn main() -> eyre::Result<()> {
     let sender_one =
         StorageSender::deploy((U256::from(2), receiver_one.addr(StorageSender::target()))).await?;
 
-    assert!(cubist.when_bridged(None).await);
+    let (sender_target, receiver_target) = (StorageSender::target(), StorageReceiver::target());
+    let (shim_one, sender_one_client) = (receiver_one.addr(sender_target), sender_one.client());
+    let (sender_one_client_ref, receiver_one_ref) = (&sender_one_client, &receiver_one);
+    let pairs = || async move {
+        let pair = Pair::new(
+            "StorageReceiver",
+            receiver_target,
+            sender_target,
+            shim_one,
+            sender_one_client_ref,
+        );
+        vec![pair.await.bridged(receiver_one_ref.is_bridged().await)]
+    };
+    exit_if_not_bridged(
+        when_bridged(cubist.when_bridged(None), args.bridge.timeout(), pairs).await,
+    )?;
     println!("Cubist relayer in place");
 
     println!("Deploying again using a different Cubist instance");
@@ -25,7 +61,21 @@ async fn main() -> eyre::Result<()> {
         .deploy((U256::from(20), receiver_two.addr(StorageSender::target())))
         .await?;
 
-    assert!(cubist.when_bridged(None).await);
+    let (shim_two, sender_two_client) = (receiver_two.addr(sender_target), sender_two.client());
+    let (sender_two_client_ref, receiver_two_ref) = (&sender_two_client, &receiver_two);
+    let pairs = || async move {
+        let pair = Pair::new(
+            "StorageReceiver",
+            receiver_target,
+            sender_target,
+            shim_two,
+            sender_two_client_ref,
+        );
+        vec![pair.await.bridged(receiver_two_ref.is_bridged().await)]
+    };
+    exit_if_not_bridged(
+        when_bridged(cubist.when_bridged(None), args.bridge.timeout(), pairs).await,
+    )?;
     println!("Second Cubist relayer in place");
 
     assert_eq!(U256::from(10), receiver_two.retrieve().call().await?);
@@ -39,6 +89,7 @@ async fn main() -> eyre::Result<()> {
 
     println!("Storing {sender_three:?}, {sender_thirty:?}");
 
+    let stored_at = Instant::now();
     let call = sender_one.store(sender_three);
     call.send().await?.await?;
     let call = sender_two.store(sender_thirty);
@@ -48,21 +99,25 @@ async fn main() -> eyre::Result<()> {
     assert_eq!(sender_thirty, sender_two.retrieve().call().await?);
 
     // wait up to 10s and make sure that both 'store' calls on Polygon were propagated to Ethereum
-    for delay in std::iter::repeat(std::time::Duration::from_millis(200)).take(50) {
-        let receiver_val_one = receiver_one.retrieve().call().await?;
-        let receiver_val_two = receiver_two.retrieve().call().await?;
-        if receiver_val_one == sender_three && receiver_val_two == sender_thirty {
-            break;
-        }
-        tokio::time::sleep(delay).await;
-    }
-
-    // one last chance
-    let retrieved_one = receiver_one.retrieve().call().await?;
-    let retrieved_two = receiver_two.retrieve().call().await?;
-    println!("Retrieved {retrieved_one:?}, {retrieved_two:?}");
-    assert_eq!(sender_three, retrieved_one);
-    assert_eq!(sender_thirty, retrieved_two);
+    let (receiver_one_ref, receiver_two_ref) = (&receiver_one, &receiver_two);
+    let retrieved = Wait::new(format!(
+        "receivers to store {sender_three:?}, {sender_thirty:?}"
+    ))
+    .since(stored_at)
+    .timeout(Duration::from_secs(10))
+    .for_state(
+        || async move {
+            let retrieved_one = receiver_one_ref.retrieve().call().await?;
+            let retrieved_two = receiver_two_ref.retrieve().call().await?;
+            Ok::<_, eyre::Report>((retrieved_one, retrieved_two))
+        },
+        |vals| *vals == (sender_three, sender_thirty),
+    )
+    .await?;
+    println!(
+        "Retrieved {:?} after {:?}",
+        retrieved.value, retrieved.latency
+    );
 
     Ok(())
 }
@@ -0,0 +1,79 @@
+#[path = "../src/cubist_gen.rs"]
+mod cubist_gen;
+
+use crate::cubist_gen::*;
+use ethers::types::U256;
+use std::process::Command;
+use template_support::{relay::Wait, testing::TestEnv};
+
+/// Run the template's 'cli' with `args` (in the test's Cubist environment),
+/// returning its stdout.
+fn cli(args: &[&str]) -> eyre::Result<String> {
+    let out = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .args(args)
+        .output()?;
+    eyre::ensure!(
+        out.status.success(),
+        "'cli {}' failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    Ok(String::from_utf8(out.stdout)?)
+}
+
+#[tokio::test]
+async fn deploys_and_updates_values_cross_chain() -> eyre::Result<()> {
+    let _env = TestEnv::start(env!("CARGO_MANIFEST_DIR"))?;
+    let cubist = cubist().await?;
+
+    let val = U256::from(55);
+    let receiver = StorageReceiver::deploy(val).await?;
+    let sender = StorageSender::deploy((val, receiver.addr(StorageSender::target()))).await?;
+
+    // wait for the bridge to be established before calling any cross-chain contract methods
+    assert!(cubist.when_bridged(None).await);
+
+    // check values
+    assert_eq!(val, sender.retrieve().call().await?);
+    assert_eq!(val, receiver.retrieve().call().await?);
+
+    let receiver = &receiver;
+    for (call, expected) in [
+        (sender.inc(33.into()), val + 33),
+        (sender.dec(34.into()), val - 1),
+        (sender.store(val), val),
+    ] {
+        call.send().await?.await?;
+        assert_eq!(expected, sender.retrieve().call().await?);
+        Wait::new(format!("'StorageReceiver' to store {expected}"))
+            .for_state(
+                || async move { receiver.retrieve().call().await },
+                |val| *val == expected,
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_reuses_the_receiver_and_its_shim() -> eyre::Result<()> {
+    let _env = TestEnv::start(env!("CARGO_MANIFEST_DIR"))?;
+
+    // a deployment interrupted right after deploying 'StorageReceiver'
+    let receiver = StorageReceiver::deploy(U256::from(7)).await?;
+    let receiver_addr = format!("{:?}", receiver.address());
+    let shim = format!("{:?}", receiver.addr(StorageSender::target()));
+
+    // a new process reloads the receiver and passes its shim to 'StorageSender'
+    let plan = cli(&["deploy", "--plan"])?;
+    assert!(plan.contains(&receiver_addr), "{plan}");
+    assert!(plan.contains(&shim), "{plan}");
+
+    cli(&["deploy", "--resume"])?;
+    let plan = cli(&["deploy", "--plan"])?;
+    assert!(plan.contains("Already deployed"), "{plan}");
+    assert!(plan.contains(&receiver_addr), "{plan}");
+
+    Ok(())
+}
@@ -12,9 +12,21 @@ import "@openzeppelin/contracts/access/Ownable.sol";
  * It receives payment in native tokens and issues ERC20 tokens
  * from ERC20Bridged in response. When commanded by ERC20Bridged,
  * it releases native tokens to a specified recipient.
+ *
+ * The native tokens it holds are either collateral, backing the
+ * ERC20 tokens issued (or being issued), or fees, which the owner
+ * can collect. Collecting fees never touches the collateral.
  */
 contract TokenSender is Ownable {
     ERC20Bridged private _bridge_receiver;
+    // native tokens backing issued ERC20 tokens, released by bridgeReceive
+    uint256 private _collateral;
+    // native tokens kept as fees and not collected yet
+    uint256 private _fees;
+
+    event BridgeSent(address indexed from, address indexed to, uint256 paid, uint256 sent);
+    event BridgeReceived(address indexed to, uint256 amount);
+    event FeesCollected(address indexed to, uint256 amount);
 
     constructor(ERC20Bridged receiver) Ownable() {
         // set up the "receiver" side of the bridge
@@ -27,12 +39,39 @@ contract TokenSender is Ownable {
 
         uint256 kept = msg.value / 1000; // keep 0.1% as a fee
         uint256 to_send = msg.value - kept;
+        _collateral += to_send;
+        _fees += kept;
 
+        emit BridgeSent(msg.sender, to, msg.value, to_send);
         _bridge_receiver.bridgeMint(to, to_send);
     }
 
     function bridgeReceive(address to, uint256 amount) public onlyOwner {
+        // only tokens that were issued can be redeemed
+        require(amount <= _collateral, "Bridge error: amount exceeds collateral");
+        _collateral -= amount;
+
+        emit BridgeReceived(to, amount);
         // transfer the requested amount
         payable(to).transfer(amount);
     }
+
+    function collateral() public view returns (uint256) {
+        return _collateral;
+    }
+
+    function accruedFees() public view returns (uint256) {
+        return _fees;
+    }
+
+    function collectFees(address payable to) public onlyOwner {
+        uint256 amount = _fees;
+        require(amount > 0, "Bridge error: no fees to collect");
+        // whatever else happens, the collateral stays behind
+        require(address(this).balance >= _collateral + amount, "Bridge error: fees exceed surplus");
+        _fees = 0;
+
+        emit FeesCollected(to, amount);
+        to.transfer(amount);
+    }
 }
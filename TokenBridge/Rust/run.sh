@@ -27,8 +27,7 @@ run-cli deploy
 run-cli balances
 
 # buy FBB (worth 1000000000000 WEI) and award the proceeds to account-0 on Polygon
-run-cli buy 1000000000000 0
-sleep 0.5 # give the relayer some time to propagate values
+run-cli buy 1000000000000 0 --wait # wait for the relayer to mint FBB on Polygon
 
 # print balances; expect:
 #   - 999000000000 FBB in account-0 on Polygon
@@ -36,8 +35,7 @@ sleep 0.5 # give the relayer some time to propagate values
 run-cli balances
 
 # sell 99000000000 FBB and award the proceeds to account-1 on Ethereum
-run-cli sell 99000000000 1
-sleep 0.5 # give the relayer some time to propagate values
+run-cli sell 99000000000 1 --wait # wait for the relayer to release WEI on Ethereum
 
 # print balances; expect:
 #   - 99000000000 less FBB (i.e., 900000000000) in account-0 on Polygon
@@ -1,39 +1,78 @@
 mod cubist_gen;
+mod economics;
 
-use std::{fmt::Display, fs, str::FromStr};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
 use crate::cubist_gen::*;
+use crate::economics::Quote;
 use clap::{Args, Parser, Subcommand};
-use color_eyre::owo_colors::OwoColorize;
-use cubist_sdk::core::TargetProject;
-use ethers::types::{Address, U256};
+use ethers::{
+    abi::{self, Abi, Detokenize, ParamType, Token},
+    contract::builders::ContractCall,
+    types::{Address, BlockNumber, Log, H256, U256},
+    utils::id,
+};
 use ethers_providers::Middleware;
-use eyre::{bail, eyre, Context, Result};
+use eyre::{bail, ensure, eyre, Context, Result};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use template_support::{
+    address::{AddressBook, Resolver, ADDRESS_BOOK_FILE},
+    amount::{de_amount, parse_amount},
+    batch::{print_results, BatchArgs, Lane, Operation},
+    bench::{BenchReport, Sample, SampleStatus},
+    bridge::{exit_if_not_bridged, when_bridged, Pair},
+    deploy::{DeployFlags, Plan, Status, Step},
+    doctor::{Doctor, Report},
+    gas::GasArgs,
+    history::{
+        log_actions, pair_transfers, reverted_calls, RevertedCall, ScanArgs, Transfer,
+        TransferStatus,
+    },
+    manifest::{ContractEntry, Manifest, ManifestCommand},
+    output::{print_records, ser_amount, OutputFormat, Record},
+    profile::ProfileArgs,
+    receipt::{send_call, Receipt},
+    relay::Wait,
+    s_action, s_contract, s_error, s_header, s_value,
+    signers::{ChainSigner, Signers, SIGNERS_FILE},
+    style::Tracked,
+    table::{Column, Table},
+    watch::{clear_screen, delta, new_blocks},
+};
 
 const TOKEN_SENDER: &str = "TokenSender";
 const ERC20_BRIDGED: &str = "ERC20Bridged";
 
-macro_rules! s_action {
-    ($x: expr) => {
-        $x.bold().green()
-    };
-}
-
-macro_rules! s_value {
-    ($x: expr) => {
-        $x.yellow()
-    };
-}
-
-macro_rules! s_contract {
-    ($x: expr) => {
-        $x.blue()
-    };
-}
-
 #[derive(Debug, Parser)]
 #[clap(about = "Multi-chain Token Bridge dApp", long_about = None)]
 struct Cli {
+    /// Output format of commands that list accounts or transfers ('balances',
+    /// 'watch', 'history', 'fees', 'token info', 'token allowance'), of the receipts of sent
+    /// transactions ('buy', 'sell', 'collect-fees', 'token transfer', ...) and of the
+    /// report of 'batch'.
+    #[clap(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+    /// Account that signs transactions ('buy', 'sell', 'collect-fees', 'token transfer',
+    /// 'token approve', 'token transfer-from', and 'batch' operations without a 'sender'):
+    /// an account index on the called
+    /// contract's chain, 'chain:index', a hex address or an alias (see 'alias').  Must be
+    /// one of the local accounts on that chain, or the keystore or private key selected for
+    /// that chain in 'signers.json'.  Defaults to the project's default sender.
+    #[clap(long, global = true)]
+    from: Option<String>,
+    #[clap(flatten)]
+    profile: ProfileArgs,
+    #[clap(flatten)]
+    gas: GasArgs,
     #[clap(subcommand)]
     command: Command,
 }
@@ -51,72 +90,348 @@ enum Command {
     /// It defines an ERC20 token (sym 'FBB') that is minted in response to payments
     /// on the "sending" side; a user can burn these tokens via 'bridge_send'
     /// to release native tokens from the sending side.
-    Deploy,
-    /// List balances of accounts and contracts on both chains.
+    ///
+    /// Refuses to replace an existing deployment unless '--force' is given.
+    Deploy(DeployFlags),
+    /// Export the deployment to a portable manifest, or import one to reuse
+    /// an existing deployment without redeploying.
+    #[clap(subcommand)]
+    Manifest(ManifestCommand),
+    /// List balances of accounts and contracts on both chains.  The account that signs
+    /// transactions on each chain (see '--from') is named 'sender'.
     Balances,
+    /// Like 'balances', but refresh whenever a new block is mined on either chain.
+    /// Changed balances are highlighted along with the change since the last refresh.
+    Watch(WatchArgs),
     /// Mint some FBB tokens.  This is done by calling 'TokenSender' and specifying a WEI amount and
     /// an address to receive minted FBB.  Conversion rate is 0.999, which means that 'TokenSender'
     /// will send a request to 'ERC20Bridged' to mint FBB tokens (in the amount of 99.9% of the
-    /// received WEI amount) and award them to the specified recipient.
+    /// received WEI amount) and award them to the specified recipient.  Refuses payments under
+    /// the minimum (see 'quote') or over the sender's balance.
     Buy(BuyArgs),
     /// Burn some FBB tokens.  This is done by calling 'ERC20Bridged' and specifying an FBB amount
     /// and an address to receive WEI.  Conversion rate is 1.0, which means that 'ERC20Bridged' will
     /// first burn the specified amount of FBB and then send a request to 'TokenSender' to award
-    /// the same amount of WEI to the specified recipient.
+    /// the same amount of WEI to the specified recipient.  Refuses amounts over the seller's FBB
+    /// balance or over what 'TokenSender' holds.
     Sell(SellArgs),
+    /// Run the operations listed in a YAML or JSON file.  Each has a 'command' ('buy' or
+    /// 'sell'), an 'amount' and optionally a 'receiver' and a 'sender' (like '--from'),
+    /// which default as for 'buy' and 'sell'.  Prints the transaction or the error of each
+    /// operation, and fails if any operation failed.
+    Batch(BatchArgs),
+    /// Measure how the relayer copes with load: send many payments to 'TokenSender'
+    /// concurrently, from several local accounts at a target rate, and time how long each
+    /// takes to be minted on 'ERC20Bridged' chain.  Prints the latency percentiles, the
+    /// throughput and the failures, and saves them with every transfer as JSON.
+    Bench(BenchArgs),
+    /// Show what a payment to 'TokenSender' buys: the fee it keeps, the FBB it mints
+    /// and the minimum payment it accepts.
+    Quote {
+        /// Payment in WEI.  Accepts the same formats as 'buy'.
+        #[clap(value_parser = parse_amount)]
+        payment_wei: U256,
+    },
+    /// List cross-chain transfers in both directions: payments to 'TokenSender' paired
+    /// with FBB mints, and FBB burns paired with native token releases.  Shows whether
+    /// each transfer is pending, delivered, delivered with a mismatched amount, reverted
+    /// on the source chain or failed on the destination chain, and how long delivery
+    /// took.  Payments and releases are found in events that only a 'TokenSender'
+    /// deployed with the 'BridgeSent' and 'BridgeReceived' events emits.
+    History(HistoryArgs),
+    /// Check that the bridge is solvent: 'TokenSender' must hold enough native tokens
+    /// to back all FBB in circulation, plus transfers still in flight.  Also flags FBB
+    /// minted without a matching payment, releases without a matching burn and
+    /// deliveries that reverted.
+    /// Exits with a non-zero status if any check fails.
+    Audit,
+    /// Show the fees 'TokenSender' has kept and not collected yet, along with the
+    /// collateral backing FBB in circulation and in flight.
+    Fees,
+    /// Send the fees 'TokenSender' has kept to an address.  Only the owner of 'TokenSender'
+    /// (the account that deployed it) can collect fees, and only the fees: the collateral
+    /// backing FBB stays behind.
+    CollectFees(CollectFeesArgs),
+    /// Inspect and move FBB on 'ERC20Bridged' chain with the standard ERC20 operations,
+    /// e.g., to pass FBB between holders before selling it.
+    #[clap(subcommand)]
+    Token(TokenCommand),
+    /// Manage the project's address book.  Aliases can be used wherever
+    /// an address is expected.
+    #[clap(subcommand)]
+    Alias(AliasCommand),
+    /// Diagnose the local environment: RPC endpoints, mnemonics, account funding,
+    /// deployed contracts and the relayer.
+    Doctor,
+}
+
+/// ERC20 operations on FBB.  Addresses are either hex addresses (starting with '0x'),
+/// account indices on 'ERC20Bridged' chain, 'chain:index', contract names
+/// ('ERC20Bridged', 'shim:TokenSender') or aliases (see 'alias').  Amounts are in
+/// FBB base units and accept the same formats as 'sell'.
+#[derive(Debug, Subcommand)]
+enum TokenCommand {
+    /// Show the token's name, symbol, decimals and total supply.
+    Info,
+    /// Send FBB from the signer (see '--from') to an address.
+    Transfer {
+        /// Receiver of the FBB.
+        to: String,
+        #[clap(value_parser = parse_amount)]
+        amount_fbb: U256,
+    },
+    /// Allow an address to spend some of the signer's FBB (see 'transfer-from').
+    /// Replaces any earlier allowance.
+    Approve {
+        /// Address allowed to spend the FBB.
+        spender: String,
+        #[clap(value_parser = parse_amount)]
+        amount_fbb: U256,
+    },
+    /// Show how much FBB an address is still allowed to spend on behalf of another.
+    Allowance {
+        /// Holder of the FBB.
+        owner: String,
+        /// Address allowed to spend the FBB.
+        spender: String,
+    },
+    /// Send FBB on behalf of another holder, who must have approved the signer
+    /// (see '--from') for at least that amount.
+    TransferFrom {
+        /// Holder of the FBB.
+        owner: String,
+        /// Receiver of the FBB.
+        to: String,
+        #[clap(value_parser = parse_amount)]
+        amount_fbb: U256,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum AliasCommand {
+    /// List all aliases.
+    List,
+    /// Add an alias (or change its address).
+    Add {
+        /// Name of the alias.
+        name: String,
+        /// Either a hex address (starting with '0x'), 'chain:index' or a contract name.
+        address: String,
+    },
+    /// Remove an alias.
+    Remove {
+        /// Name of the alias.
+        name: String,
+    },
 }
 
 #[derive(Debug, Args)]
 struct BuyArgs {
     /// Payment in WEI. The amount of minted FBB will be equal to 99.9% of that.
-    #[clap(index = 1)]
-    payment_wei: u64,
-    /// Receiver of newly minted FBB.  Either a hex address (starting with '0x')
-    /// or account index on 'ERC20Bridged' chain.  Defaults to the address
-    /// of the first (index 0) account on 'ERC20Bridged' chain.
+    /// Accepts plain integers, hex ('0x...'), scientific notation ('1e12')
+    /// and unit suffixes ('1.5ether', '20gwei').
+    #[clap(index = 1, value_parser = parse_amount)]
+    payment_wei: U256,
+    /// Receiver of newly minted FBB.  Either a hex address (starting with '0x'),
+    /// an account index on 'ERC20Bridged' chain, 'chain:index', a contract name
+    /// ('ERC20Bridged', 'shim:TokenSender') or an alias (see 'alias').  Defaults to
+    /// the address of the first (index 0) account on 'ERC20Bridged' chain.
     #[clap(index = 2, default_value = "0")]
     fbb_receiver: String,
+    #[clap(flatten)]
+    delivery: DeliveryArgs,
 }
 
 #[derive(Debug, Args)]
 struct SellArgs {
-    /// The amount of FBB to sell/burn.
-    #[clap(index = 1)]
-    amount_fbb: u64,
-    /// Receiver of WEI.  Either a hex address (starting with '0x')
-    /// or account index on 'TokenSender' chain.  Defaults to the
-    /// address of the first (index 0) account on 'TokenSender' chain.
+    /// The amount of FBB to sell/burn, in FBB base units.  Accepts plain integers,
+    /// hex ('0x...'), scientific notation ('1e12') and unit suffixes ('3fbb').
+    #[clap(index = 1, value_parser = parse_amount)]
+    amount_fbb: U256,
+    /// Receiver of WEI.  Either a hex address (starting with '0x'), an account
+    /// index on 'TokenSender' chain, 'chain:index', a contract name ('TokenSender',
+    /// 'shim:ERC20Bridged') or an alias (see 'alias').  Defaults to the address
+    /// of the first (index 0) account on 'TokenSender' chain.
     #[clap(index = 2, default_value = "0")]
     wei_receiver: String,
+    #[clap(flatten)]
+    delivery: DeliveryArgs,
+}
+
+#[derive(Debug, Args)]
+struct CollectFeesArgs {
+    /// Receiver of the fees.  Either a hex address (starting with '0x'), an account
+    /// index on 'TokenSender' chain, 'chain:index' or an alias (see 'alias').
+    #[clap(long)]
+    to: String,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+#[derive(Debug, Args)]
+struct DeliveryArgs {
+    /// Wait until the relayer delivers the transfer to the other chain, then print
+    /// the delivering transaction and the end-to-end latency.
+    #[clap(long)]
+    wait: bool,
+    /// How long to wait for delivery (with '--wait'), in seconds.
+    #[clap(long, default_value = "30", requires = "wait")]
+    wait_timeout: u64,
+}
+
+impl DeliveryArgs {
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.wait_timeout)
+    }
+}
+
+#[derive(Debug, Args)]
+struct BenchArgs {
+    /// Number of payments to send.
+    #[clap(long, default_value = "100")]
+    count: usize,
+    /// Payments to send per second.
+    #[clap(long, default_value = "10")]
+    rate: f64,
+    /// Number of local accounts on 'TokenSender' chain to send from, in turn.
+    #[clap(long, default_value = "4")]
+    accounts: usize,
+    /// Payment in WEI, each minting FBB to a fresh address.  Accepts the same formats
+    /// as 'buy'.
+    #[clap(long, default_value = "1000gwei", value_parser = parse_amount)]
+    payment_wei: U256,
+    /// How long to wait for each payment to be mined, and for each mint, in seconds.
+    #[clap(long, default_value = "60")]
+    timeout: u64,
+    /// How often to poll 'ERC20Bridged' chain for mints, in milliseconds.
+    #[clap(long, default_value = "100")]
+    poll_ms: u64,
+    /// Label saved with the report, e.g., the relayer version.
+    #[clap(long)]
+    label: Option<String>,
+    /// File to save the report to.
+    #[clap(long, default_value = "bench.json")]
+    out: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct HistoryArgs {
+    /// Only list transfers sent or received by this address.  Either a hex address
+    /// (starting with '0x'), 'chain:index', a contract name or an alias (see 'alias').
+    #[clap(long)]
+    address: Option<String>,
+    #[clap(flatten)]
+    scan: ScanArgs,
+}
+
+#[derive(Debug, Args)]
+struct WatchArgs {
+    /// How often to poll both chains for new blocks, in milliseconds.
+    #[clap(long, default_value = "1000")]
+    interval_ms: u64,
+}
+
+fn main() -> Result<()> {
     color_eyre::install()?;
     tracing_subscriber::fmt::init();
     let args = Cli::parse();
+    // selecting the profile sets an environment variable, so it must happen
+    // before the runtime starts its worker threads
+    args.profile.select(env!("CARGO_MANIFEST_DIR"))?;
+    tokio::runtime::Runtime::new()?.block_on(run(args))
+}
 
-    match args.command {
-        Command::Deploy => deploy().await,
-        Command::Balances => balances().await,
-        Command::Buy(args) => buy(&args).await,
-        Command::Sell(args) => sell(&args).await,
-    }
+async fn run(args: Cli) -> Result<()> {
+    let result = match args.command {
+        Command::Deploy(args) => deploy(&args).await,
+        Command::Manifest(cmd) => manifest(&cmd).await,
+        Command::Balances => balances(args.output, args.from.as_deref()).await,
+        Command::Watch(watch_args) => watch(&watch_args, args.output, args.from.as_deref()).await,
+        Command::Buy(buy_args) => {
+            buy(&buy_args, args.from.as_deref(), &args.gas, args.output).await
+        }
+        Command::Sell(sell_args) => {
+            sell(&sell_args, args.from.as_deref(), &args.gas, args.output).await
+        }
+        Command::Batch(batch_args) => {
+            batch(&batch_args, args.from.as_deref(), &args.gas, args.output).await
+        }
+        Command::Bench(bench_args) => bench(&bench_args, args.output).await,
+        Command::Quote { payment_wei } => quote(payment_wei),
+        Command::History(history_args) => history(&history_args, args.output).await,
+        Command::Audit => audit(args.output).await,
+        Command::Fees => fees(args.output).await,
+        Command::CollectFees(fee_args) => {
+            collect_fees(&fee_args, args.from.as_deref(), &args.gas, args.output).await
+        }
+        Command::Token(cmd) => token(&cmd, args.from.as_deref(), &args.gas, args.output).await,
+        Command::Alias(cmd) => alias(&cmd).await,
+        Command::Doctor => doctor().await,
+    };
+    exit_if_not_bridged(result)
+}
+
+const E20B_SHIMS: &str = "ERC20Bridged shims";
+
+/// The deployment steps, and which of them an earlier deployment completed.
+async fn deploy_plan() -> Plan {
+    let toks = TokenSender::deployed().await.ok();
+    let e20b = ERC20Bridged::deployed().await.ok();
+    let toks_target = TokenSender::target();
+    let e20b_target = ERC20Bridged::target();
+
+    let shims = e20b.as_ref().map(|c| c.addr(toks_target));
+    let toks_shim = toks.as_ref().map(|c| c.addr(e20b_target));
+    // Each contract's constructor refers to the other's shims, which can only be
+    // recovered once both contracts are deployed.  A contract deployed without
+    // the other one (i.e., by an interrupted deployment) is therefore stale.
+    let status = |addr: Option<Address>, other_deployed: bool| match addr {
+        None => Status::Pending,
+        Some(addr) if other_deployed => Status::Done(addr),
+        Some(addr) => Status::Stale(addr),
+    };
+    let placeholder = |shim: Option<Address>, name: &str| {
+        shim.map_or_else(|| format!("<{name} shim>"), |a| format!("{a:?}"))
+    };
+
+    Plan::new(vec![
+        Step::new(
+            toks_target,
+            E20B_SHIMS,
+            shims.map_or(Status::Pending, Status::Done),
+        ),
+        Step::new(
+            toks_target,
+            TOKEN_SENDER,
+            status(toks.as_ref().map(|c| c.address()), e20b.is_some()),
+        )
+        .arg(placeholder(shims, ERC20_BRIDGED)),
+        Step::new(
+            e20b_target,
+            ERC20_BRIDGED,
+            status(e20b.as_ref().map(|c| c.address()), toks.is_some()),
+        )
+        .arg("'FooBarBaz'")
+        .arg("'FBB'")
+        .arg(placeholder(toks_shim, TOKEN_SENDER)),
+    ])
 }
 
-async fn deploy() -> Result<()> {
+async fn deploy(args: &DeployFlags) -> Result<()> {
     let cubist = cubist().await?;
 
-    let deploy_dir = cubist.config().deploy_dir();
-    if deploy_dir.is_dir() {
-        println!(
-            "{} deployment dir: {}",
-            s_action!("Deleting"),
-            s_value!(deploy_dir.display()),
-        );
-        fs::remove_dir_all(deploy_dir).context("Deleting previous deployment dir")?;
+    // Unlike 'StorageReceiver' in the Storage template, shims deployed on their
+    // own are not recorded in the deployment dir, so a new process cannot reload
+    // them: resuming a deployment interrupted after 'ERC20Bridged::deploy_shims'
+    // deploys them again (leaving the old ones unused), and a lone 'TokenSender'
+    // refers to lost shims, so it is stale (see 'deploy_plan').
+    let mut plan = deploy_plan().await;
+    if !plan.prepare(args, &cubist.config().deploy_dir())? {
+        return Ok(());
     }
 
+    // Break the circular dependency between ERC20Bridged and TokenSender by first
+    // deploying ERC20Bridged shims only, then passing that address to TokenSender,
+    // then finally deploying ERC20Bridged with TokenSender's address.
+    println!("{} {}", s_action!("Deploying"), s_contract!(E20B_SHIMS));
     let e20b = ERC20Bridged::deploy_shims().await?;
     let e20b_shim_addr = e20b.addr(TokenSender::target());
     println!(
@@ -139,47 +454,163 @@ async fn deploy() -> Result<()> {
         ERC20Bridged::deploy(("FooBarBaz".to_owned(), "FBB".to_owned(), toks_shim_addr)).await?;
 
     // wait for the bridge to be up
-    assert!(cubist.when_bridged(None).await);
+    let waited = when_bridged(
+        cubist.when_bridged(None),
+        args.bridge.timeout(),
+        bridge_pairs,
+    )
+    .await?;
 
-    println!("{}", s_action!("Done"));
+    println!("{} (bridged after {waited:.1?})", s_action!("Done"));
     Ok(())
 }
 
-async fn proj_accounts(proj: &TargetProject) -> Result<Vec<(Option<String>, Address)>> {
-    Ok(proj
-        .accounts()
-        .await?
-        .into_iter()
-        .map(|a| (None, a))
-        .collect())
+/// The contract/shim pairs the relayer connects, and whether each is bridged yet.
+async fn bridge_pairs() -> Vec<Pair> {
+    let (toks_target, e20b_target) = (TokenSender::target(), ERC20Bridged::target());
+    let (Ok(toks), Ok(e20b)) = (
+        TokenSender::deployed().await,
+        ERC20Bridged::deployed().await,
+    ) else {
+        return vec![];
+    };
+    vec![
+        Pair::new(
+            TOKEN_SENDER,
+            toks_target,
+            e20b_target,
+            toks.addr(e20b_target),
+            &e20b.client(),
+        )
+        .await
+        .bridged(toks.is_bridged().await),
+        Pair::new(
+            ERC20_BRIDGED,
+            e20b_target,
+            toks_target,
+            e20b.addr(toks_target),
+            &toks.client(),
+        )
+        .await
+        .bridged(e20b.is_bridged().await),
+    ]
 }
 
-async fn token_sender_accounts() -> Result<Vec<(Option<String>, Address)>> {
-    let target = TokenSender::target();
-    let proj = cubist().await?.project(target).unwrap();
+async fn manifest(cmd: &ManifestCommand) -> Result<()> {
+    let cubist = cubist().await?;
+    let deploy_dir = cubist.config().deploy_dir();
+    let (toks_target, e20b_target) = (TokenSender::target(), ERC20Bridged::target());
+    let toks_provider = cubist.project(toks_target).unwrap().provider();
+    let e20b_provider = cubist.project(e20b_target).unwrap().provider();
+
+    match cmd {
+        ManifestCommand::Export { file } => {
+            let toks = TokenSender::deployed()
+                .await
+                .context("Contracts not deployed; call 'deploy' first")?;
+            let e20b = ERC20Bridged::deployed()
+                .await
+                .context("Contracts not deployed; call 'deploy' first")?;
+
+            let mut toks_entry = ContractEntry::describe(
+                TOKEN_SENDER,
+                toks_target,
+                &toks_provider,
+                toks.address(),
+                &[ParamType::Address],
+            )
+            .await?;
+            toks_entry
+                .shims
+                .insert(e20b_target.to_string(), toks.addr(e20b_target));
+
+            let mut e20b_entry = ContractEntry::describe(
+                ERC20_BRIDGED,
+                e20b_target,
+                &e20b_provider,
+                e20b.address(),
+                &[ParamType::String, ParamType::String, ParamType::Address],
+            )
+            .await?;
+            e20b_entry
+                .shims
+                .insert(toks_target.to_string(), e20b.addr(toks_target));
 
-    let mut result = proj_accounts(&proj).await?;
+            Manifest::new(vec![toks_entry, e20b_entry], &deploy_dir)?.save(file.as_deref())
+        }
+        ManifestCommand::Import { file, force } => {
+            let manifest = Manifest::load(file)?;
+            let toks = manifest.contract(TOKEN_SENDER)?;
+            toks.verify(&toks_provider).await?;
+            toks.verify_shim(e20b_target, &e20b_provider).await?;
+            let e20b = manifest.contract(ERC20_BRIDGED)?;
+            e20b.verify(&e20b_provider).await?;
+            e20b.verify_shim(toks_target, &toks_provider).await?;
+
+            if !force {
+                if let Ok(c) = TokenSender::deployed().await {
+                    bail!(
+                        "Already deployed ('{TOKEN_SENDER}' at {:?}); use '--force' to replace it",
+                        c.address()
+                    );
+                }
+            }
+            manifest.restore_deploy_dir(&deploy_dir)
+        }
+    }
+}
+
+/// Contracts and shims deployed on the 'TokenSender' chain.
+async fn token_sender_contracts() -> Vec<(String, Address)> {
+    let target = TokenSender::target();
+    let mut result = vec![];
     if let Ok(c) = TokenSender::deployed().await {
-        result.push((Some(TOKEN_SENDER.to_owned()), c.address()));
+        result.push((TOKEN_SENDER.to_owned(), c.address()));
     }
     if let Ok(c) = ERC20Bridged::deployed().await {
-        result.push((Some(format!("(shim) {ERC20_BRIDGED}")), c.addr(target)));
+        result.push((format!("shim:{ERC20_BRIDGED}"), c.addr(target)));
     }
-    Ok(result)
+    result
 }
 
-async fn erc20_accounts() -> Result<Vec<(Option<String>, Address)>> {
+/// Contracts and shims deployed on the 'ERC20Bridged' chain.
+async fn erc20_contracts() -> Vec<(String, Address)> {
     let target = ERC20Bridged::target();
-    let proj = cubist().await?.project(target).unwrap();
-
-    let mut result = proj_accounts(&proj).await?;
+    let mut result = vec![];
     if let Ok(c) = ERC20Bridged::deployed().await {
-        result.push((Some(ERC20_BRIDGED.to_owned()), c.address()));
+        result.push((ERC20_BRIDGED.to_owned(), c.address()));
     }
     if let Ok(c) = TokenSender::deployed().await {
-        result.push((Some(format!("(shim) {TOKEN_SENDER}")), c.addr(proj.target)));
+        result.push((format!("shim:{TOKEN_SENDER}"), c.addr(target)));
     }
-    Ok(result)
+    result
+}
+
+fn address_book_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(ADDRESS_BOOK_FILE)
+}
+
+/// Resolves addresses given on the command line.  Bare account indices refer to
+/// `default_chain`; contract names refer to `contracts`.
+async fn resolver(
+    default_chain: Option<String>,
+    contracts: Vec<(String, Address)>,
+) -> Result<Resolver> {
+    let cubist = cubist().await?;
+    let mut resolver = Resolver::new(default_chain);
+    for target in [TokenSender::target(), ERC20Bridged::target()] {
+        let proj = cubist.project(target).unwrap();
+        resolver.add_accounts(target.to_string(), proj.accounts().await?);
+    }
+    for (name, addr) in contracts {
+        resolver.add_contract(name, addr);
+    }
+    resolver.set_address_book(AddressBook::load(address_book_path())?);
+    Ok(resolver)
+}
+
+fn signers_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(SIGNERS_FILE)
 }
 
 async fn get_fbb_balance(acc: Address) -> Result<Option<U256>> {
@@ -191,143 +622,1722 @@ async fn get_fbb_balance(acc: Address) -> Result<Option<U256>> {
     }
 }
 
-const DASHES: &str = "──────────────────────────────────────────────────────────────";
-const SEP_TOP: (&str, &str, &str) = ("┌─", "─┬─", "─┐");
-const SEP_MID: (&str, &str, &str) = ("├─", "─┼─", "─┤");
-const SEP_DEF: (&str, &str, &str) = ("│ ", " │ ", " │");
-const SEP_BOT: (&str, &str, &str) = ("└─", "─┴─", "─┘");
+/// Extra width of amount columns when watching, to make room for deltas.
+const DELTA_WIDTH: usize = 16;
 
-fn print_sender_row<T1: Display, T2: Display, T3: Display, T4: Display>(
-    ind: T1,
-    name: T2,
-    addr: T3,
-    wei: T4,
-    sep: Option<(&str, &str, &str)>,
-) {
-    let sep = sep.unwrap_or(SEP_DEF);
-    println!(
-        "{}{ind:>10.10}{}{name:>19.19}{}{addr:>42.42}{}{wei:>25.25}{}",
-        sep.0, sep.1, sep.1, sep.1, sep.2
-    );
+fn sender_columns(watch: bool) -> [Column; 4] {
+    let extra = if watch { DELTA_WIDTH } else { 0 };
+    [
+        Column::right(10),
+        Column::right(19),
+        Column::right(42),
+        Column::right(25 + extra),
+    ]
 }
 
-fn print_erc20_row<T1: Display, T2: Display, T3: Display, T4: Display, T5: Display>(
-    ind: T1,
-    name: T2,
-    addr: T3,
-    wei: T4,
-    fbb: T5,
-    sep: Option<(&str, &str, &str)>,
-) {
-    let sep = sep.unwrap_or(SEP_DEF);
-    println!(
-        "{}{ind:>10.10}{}{name:>19.19}{}{addr:>42.42}{}{wei:>25.25}{}{fbb:>20.20}{}",
-        sep.0, sep.1, sep.1, sep.1, sep.1, sep.2
-    );
+fn erc20_columns(watch: bool) -> [Column; 5] {
+    let extra = if watch { DELTA_WIDTH } else { 0 };
+    [
+        Column::right(10),
+        Column::right(19),
+        Column::right(42),
+        Column::right(25 + extra),
+        Column::right(20 + extra),
+    ]
+}
+
+/// Balances of all accounts and contracts on the 'TokenSender' and 'ERC20Bridged' chains.
+#[derive(Debug, PartialEq, Eq)]
+struct Balances {
+    sender: Vec<Record>,
+    erc20: Vec<Record>,
+    /// The accounts that sign transactions on each chain (see '--from'), if known.
+    sender_signer: Option<Address>,
+    erc20_signer: Option<Address>,
 }
 
-async fn balances() -> Result<()> {
+impl Balances {
+    fn records(&self) -> Vec<Record> {
+        [self.sender.as_slice(), self.erc20.as_slice()].concat()
+    }
+}
+
+/// Balances of all accounts and contracts, marking the accounts that `signers`
+/// and '--from' (`from`, resolved with `resolver`) select to sign transactions.
+async fn balance_records(
+    signers: &Signers,
+    resolver: &Resolver,
+    from: Option<&str>,
+) -> Result<Balances> {
     let cubist = cubist().await?;
 
+    let target = TokenSender::target();
+    let proj = cubist.project(target).unwrap();
+    // an account given with '--from' may only exist on one of the chains
+    let sender_signer = signers
+        .signer_address(&target.to_string(), from, resolver, proj.sender().await?)
+        .ok();
+    let accounts = signer_and_accounts(sender_signer, proj.accounts().await?);
+    let contracts = token_sender_contracts().await.into_iter();
+    let contracts = contracts.map(|(name, addr)| (None, Some(name), addr));
+    let mut sender = vec![];
+    for (index, label, addr) in accounts.into_iter().chain(contracts) {
+        sender.push(Record {
+            target: target.to_string(),
+            index,
+            label,
+            address: Some(addr),
+            wei: Some(proj.provider().get_balance(addr, None).await?),
+            ..Default::default()
+        });
+    }
+
+    let target = ERC20Bridged::target();
+    let proj = cubist.project(target).unwrap();
+    let erc20_signer = signers
+        .signer_address(&target.to_string(), from, resolver, proj.sender().await?)
+        .ok();
+    let accounts = signer_and_accounts(erc20_signer, proj.accounts().await?);
+    let contracts = erc20_contracts().await.into_iter();
+    let contracts = contracts.map(|(name, addr)| (None, Some(name), addr));
+    let mut erc20 = vec![];
+    for (index, label, addr) in accounts.into_iter().chain(contracts) {
+        erc20.push(Record {
+            target: target.to_string(),
+            index,
+            label,
+            address: Some(addr),
+            wei: Some(proj.provider().get_balance(addr, None).await?),
+            fbb: get_fbb_balance(addr).await?,
+            ..Default::default()
+        });
+    }
+
+    Ok(Balances {
+        sender,
+        erc20,
+        sender_signer,
+        erc20_signer,
+    })
+}
+
+/// The (index, label, address) of local `accounts`, preceded by `signer` if it is
+/// not one of them (e.g., a keystore).
+fn signer_and_accounts(
+    signer: Option<Address>,
+    accounts: Vec<Address>,
+) -> Vec<(Option<usize>, Option<String>, Address)> {
+    let external = signer.filter(|s| !accounts.contains(s));
+    let external = external.map(|addr| (None, None, addr));
+    let accounts = accounts.into_iter().enumerate();
+    let accounts = accounts.map(|(i, addr)| (Some(i), None, addr));
+    external.into_iter().chain(accounts).collect()
+}
+
+/// An amount, highlighted along with its delta if it differs from `prev`.
+fn amount_cell(cur: Option<U256>, prev: Option<U256>) -> Tracked<String> {
+    let cur_str = cur.unwrap_or_default().to_string();
+    match delta(prev, cur) {
+        Some(d) => Tracked::new(format!("{cur_str} ({d})"), true),
+        None => Tracked::new(cur_str, false),
+    }
+}
+
+/// The account index of `rec`; contracts have none.
+fn index_cell(rec: &Record) -> String {
+    rec.index.map(|i| i.to_string()).unwrap_or_default()
+}
+
+/// The name of `rec`: its label, or "sender" if it is the account in `signer`.
+fn name_cell(rec: &Record, signer: Option<Address>) -> Box<dyn std::fmt::Display + '_> {
+    match &rec.label {
+        Some(label) => Box::new(s_contract!(label)),
+        None if rec.address.is_some() && rec.address == signer => Box::new(s_action!("sender")),
+        None => Box::new(""),
+    }
+}
+
+/// The record in `prev` (if any) for the same address as `rec`.
+fn find_prev<'a>(prev: Option<&'a [Record]>, rec: &Record) -> Option<&'a Record> {
+    prev.and_then(|p| p.iter().find(|r| r.address == rec.address))
+}
+
+/// Print balances as tables.  If `prev` is given, highlight what changed since.
+fn print_balances(cur: &Balances, prev: Option<&Balances>) -> Result<()> {
+    let watch = prev.is_some();
+
+    println!();
+    let mut table = Table::new(
+        &sender_columns(watch),
+        &[
+            &s_action!(format!("({})", TokenSender::target())),
+            &s_header!("name"),
+            &s_header!("address"),
+            &s_header!("wei"),
+        ],
+    )?;
+    for r in &cur.sender {
+        let p = find_prev(prev.map(|p| p.sender.as_slice()), r);
+        table.row(&[
+            &index_cell(r),
+            &name_cell(r, cur.sender_signer),
+            &s_value!(format!("{:?}", r.address.unwrap_or_default())),
+            &amount_cell(r.wei, p.and_then(|p| p.wei)),
+        ])?;
+    }
+    table.print();
+
     println!();
-    let proj = cubist.project(TokenSender::target()).unwrap();
-    print_sender_row(DASHES, DASHES, DASHES, DASHES, Some(SEP_TOP));
-    print_sender_row(
-        format!("({})", TokenSender::target()).green().bold(),
-        "name".bold(),
-        "address".bold(),
-        "wei".bold(),
-        None,
+    let mut table = Table::new(
+        &erc20_columns(watch),
+        &[
+            &s_action!(format!("({})", ERC20Bridged::target())),
+            &s_header!("name"),
+            &s_header!("address"),
+            &s_header!("wei"),
+            &s_header!("fbb"),
+        ],
+    )?;
+    for r in &cur.erc20 {
+        let p = find_prev(prev.map(|p| p.erc20.as_slice()), r);
+        table.row(&[
+            &index_cell(r),
+            &name_cell(r, cur.erc20_signer),
+            &s_value!(format!("{:?}", r.address.unwrap_or_default())),
+            &amount_cell(r.wei, p.and_then(|p| p.wei)),
+            &amount_cell(r.fbb, p.and_then(|p| p.fbb)),
+        ])?;
+    }
+    table.print();
+    println!();
+    Ok(())
+}
+
+async fn balances(output: OutputFormat, from: Option<&str>) -> Result<()> {
+    let signers = Signers::load(signers_path())?;
+    let resolver = resolver(None, vec![]).await?;
+    let balances = balance_records(&signers, &resolver, from).await?;
+    if output != OutputFormat::Table {
+        return print_records(output, &balances.records());
+    }
+    print_balances(&balances, None)
+}
+
+async fn watch(args: &WatchArgs, output: OutputFormat, from: Option<&str>) -> Result<()> {
+    let cubist = cubist().await?;
+    let sender_proj = cubist.project(TokenSender::target()).unwrap();
+    let erc20_proj = cubist.project(ERC20Bridged::target()).unwrap();
+    let (sender_provider, erc20_provider) = (sender_proj.provider(), erc20_proj.provider());
+    let mut blocks = new_blocks(
+        [&sender_provider, &erc20_provider],
+        Duration::from_millis(args.interval_ms),
+    )
+    .await?;
+
+    let signers = Signers::load(signers_path())?;
+    let resolver = resolver(None, vec![]).await?;
+    let mut prev: Option<Balances> = None;
+    loop {
+        let cur = balance_records(&signers, &resolver, from).await?;
+        if prev.as_ref() != Some(&cur) {
+            if output == OutputFormat::Table {
+                clear_screen();
+                println!(
+                    "{} balances on {} and {} (Ctrl-C to stop)",
+                    s_action!("Watching"),
+                    s_value!(TokenSender::target()),
+                    s_value!(ERC20Bridged::target()),
+                );
+                // on the first refresh, there's nothing to compare against yet
+                print_balances(&cur, Some(prev.as_ref().unwrap_or(&cur)))?;
+            } else {
+                print_records(output, &cur.records())?;
+            }
+            prev = Some(cur);
+        }
+        if blocks.next().await.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+async fn buy(
+    args: &BuyArgs,
+    from: Option<&str>,
+    gas: &GasArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let tok = TokenSender::deployed()
+        .await
+        .context("Contracts not deployed; call 'deploy' first")?;
+    let resolver = resolver(
+        Some(ERC20Bridged::target().to_string()),
+        erc20_contracts().await,
+    )
+    .await?;
+    let receiver = resolver.resolve(&args.fbb_receiver)?;
+    let target = TokenSender::target();
+    let signer = Signers::load(signers_path())?.signer(
+        &target.to_string(),
+        from,
+        &resolver,
+        tok.project().sender().await?,
+    )?;
+
+    // remember where to start looking for the mint on the other chain
+    let dest = if args.delivery.wait {
+        let erc20 = ERC20Bridged::deployed().await?;
+        let from_block = erc20.client().get_block_number().await?;
+        Some((erc20, from_block))
+    } else {
+        None
+    };
+
+    let (receipt, sent_at) = send_payment(args.payment_wei, receiver, &signer, gas).await?;
+    receipt.print(output)?;
+    let sender = signer.address();
+    let wei = tok.client().get_balance(sender, None).await?;
+    print_signer(target, sender, wei, None);
+
+    if let Some((erc20, from_block)) = dest {
+        // minting emits a 'Transfer' from the zero address
+        let filter = erc20
+            .transfer_filter()
+            .from_block(from_block)
+            .topic1(Address::zero())
+            .topic2(receiver)
+            .filter;
+        let minted = Wait::new(format!(
+            "FBB to be minted to {receiver:?} on {}",
+            ERC20Bridged::target()
+        ))
+        .since(sent_at)
+        .timeout(args.delivery.timeout())
+        .for_event(&erc20.client(), &filter)
+        .await?;
+        print_delivered(
+            "Minted",
+            minted.value.transaction_hash.unwrap_or_default(),
+            minted.latency,
+        );
+    }
+    Ok(())
+}
+
+/// Pay `payment` to 'TokenSender' for FBB minted to `receiver`, signed by `signer`.
+/// Returns the receipt and when the transaction was sent.
+async fn send_payment(
+    payment: U256,
+    receiver: Address,
+    signer: &ChainSigner,
+    gas: &GasArgs,
+) -> Result<(Receipt, Instant)> {
+    let tok = TokenSender::deployed()
+        .await
+        .context("Contracts not deployed; call 'deploy' first")?;
+
+    // fail before signing anything, rather than letting the chain revert
+    Quote::new(payment).check()?;
+    let target = TokenSender::target();
+    let sender = signer.address();
+    let policy = gas.policy(env!("CARGO_MANIFEST_DIR"), &target.to_string())?;
+    let balance = tok.client().get_balance(sender, None).await?;
+    ensure!(
+        payment <= balance,
+        "Payment of {payment} wei exceeds the balance of {sender:?} ({balance} wei)"
+    );
+
+    let mut call = tok.bridge_send(receiver).from(sender);
+    call.tx.set_value(payment);
+    eprintln!(
+        "\n{} {}.bridge_send{{value: {}}}({:?}) from {:?}\n",
+        s_action!("Calling"),
+        s_contract!(TOKEN_SENDER),
+        s_value!(payment),
+        s_value!(receiver),
+        s_value!(sender)
+    );
+    send_call(
+        &tok.client(),
+        target,
+        TOKEN_SENDER,
+        tok.abi(),
+        &mut call,
+        signer,
+        &policy,
+    )
+    .await
+}
+
+async fn sell(
+    args: &SellArgs,
+    from: Option<&str>,
+    gas: &GasArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let erc20 = ERC20Bridged::deployed()
+        .await
+        .context("Contracts not deployed; call 'deploy' first")?;
+    let resolver = resolver(
+        Some(TokenSender::target().to_string()),
+        token_sender_contracts().await,
+    )
+    .await?;
+    let receiver = resolver.resolve(&args.wei_receiver)?;
+    let amount = args.amount_fbb;
+    let target = ERC20Bridged::target();
+    let signer = Signers::load(signers_path())?.signer(
+        &target.to_string(),
+        from,
+        &resolver,
+        erc20.project().sender().await?,
+    )?;
+    let seller = signer.address();
+    let tok = TokenSender::deployed().await?;
+
+    // remember where to start looking for the release on the other chain
+    let dest = if args.delivery.wait {
+        Some(tok.client().get_block_number().await?)
+    } else {
+        None
+    };
+
+    let (receipt, sent_at) = send_sale(amount, receiver, &signer, gas).await?;
+    receipt.print(output)?;
+    let wei = erc20.client().get_balance(seller, None).await?;
+    let fbb = erc20.balance_of(seller).call().await?;
+    print_signer(target, seller, wei, Some(fbb));
+
+    if let Some(from_block) = dest {
+        let filter = tok
+            .bridge_received_filter()
+            .from_block(from_block)
+            .topic1(receiver)
+            .filter;
+        let released = Wait::new(format!(
+            "{amount} wei to be released to {receiver:?} on {}",
+            TokenSender::target()
+        ))
+        .since(sent_at)
+        .timeout(args.delivery.timeout())
+        .for_event(&tok.client(), &filter)
+        .await?;
+        let hash = released.value.transaction_hash.unwrap_or_default();
+        print_delivered("Released", hash, released.latency);
+    }
+    Ok(())
+}
+
+/// Sell (burn) `amount` FBB for native tokens released to `receiver`, signed by
+/// `signer`.  Returns the receipt and when the transaction was sent.
+async fn send_sale(
+    amount: U256,
+    receiver: Address,
+    signer: &ChainSigner,
+    gas: &GasArgs,
+) -> Result<(Receipt, Instant)> {
+    let erc20 = ERC20Bridged::deployed()
+        .await
+        .context("Contracts not deployed; call 'deploy' first")?;
+
+    // fail before signing anything, rather than burning FBB that cannot be released
+    ensure!(!amount.is_zero(), "Cannot sell 0 FBB");
+    let target = ERC20Bridged::target();
+    let seller = signer.address();
+    let policy = gas.policy(env!("CARGO_MANIFEST_DIR"), &target.to_string())?;
+    let fbb = erc20.balance_of(seller).call().await?;
+    ensure!(
+        amount <= fbb,
+        "Selling {amount} FBB exceeds the FBB balance of {seller:?} ({fbb})"
+    );
+    let tok = TokenSender::deployed().await?;
+    // fees are not collateral, so 'TokenSender' refuses to release them
+    let collateral = tok.collateral().call().await?;
+    ensure!(
+        amount <= collateral,
+        "{TOKEN_SENDER} holds {collateral} wei of collateral, so it cannot release {amount} wei"
     );
-    print_sender_row(DASHES, DASHES, DASHES, DASHES, Some(SEP_MID));
-    for (i, acc) in token_sender_accounts().await?.into_iter().enumerate() {
-        print_sender_row(
-            i,
-            s_contract!(acc.0.unwrap_or_default()),
-            s_value!(format!("{:?}", acc.1)),
-            s_value!(proj.provider().get_balance(acc.1, None).await?.to_string()),
-            None,
+    eprintln!(
+        "\n{} {}.bridge_send({:?}, {}) from {:?}\n",
+        s_action!("Calling"),
+        s_contract!(ERC20_BRIDGED),
+        s_value!(receiver),
+        s_value!(amount),
+        s_value!(seller)
+    );
+
+    let mut call = erc20.bridge_send(receiver, amount).from(seller);
+    send_call(
+        &erc20.client(),
+        target,
+        ERC20_BRIDGED,
+        erc20.abi(),
+        &mut call,
+        signer,
+        &policy,
+    )
+    .await
+}
+
+/// An entry of a batch file: 'buy' or 'sell' `amount` for `receiver` (which
+/// defaults as for 'buy' and 'sell'), signed by `sender` (like '--from').
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+enum BatchOp {
+    Buy {
+        #[serde(deserialize_with = "de_amount")]
+        amount: U256,
+        receiver: Option<String>,
+        sender: Option<String>,
+    },
+    Sell {
+        #[serde(deserialize_with = "de_amount")]
+        amount: U256,
+        receiver: Option<String>,
+        sender: Option<String>,
+    },
+}
+
+impl Operation for BatchOp {
+    fn command(&self) -> &'static str {
+        match self {
+            BatchOp::Buy { .. } => "buy",
+            BatchOp::Sell { .. } => "sell",
+        }
+    }
+}
+
+/// The lane, signer and receiver of a batch operation.  Signers are unlocked
+/// once per chain and account, and kept in `unlocked`.
+async fn batch_op(
+    op: &BatchOp,
+    from: Option<&str>,
+    signers: &Signers,
+    unlocked: &mut HashMap<(String, Option<String>), ChainSigner>,
+) -> Result<(Lane, (ChainSigner, Address))> {
+    let (chain, receiver_chain, contracts, receiver, sender) = match op {
+        BatchOp::Buy {
+            receiver, sender, ..
+        } => (
+            TokenSender::target(),
+            ERC20Bridged::target(),
+            erc20_contracts().await,
+            receiver,
+            sender,
+        ),
+        BatchOp::Sell {
+            receiver, sender, ..
+        } => (
+            ERC20Bridged::target(),
+            TokenSender::target(),
+            token_sender_contracts().await,
+            receiver,
+            sender,
+        ),
+    };
+    // like 'buy' and 'sell', default to the first account on the receiving chain
+    let resolver = resolver(Some(receiver_chain.to_string()), contracts).await?;
+    let receiver = resolver.resolve(receiver.as_deref().unwrap_or("0"))?;
+
+    let from = sender.as_deref().or(from);
+    let key = (chain.to_string(), from.map(str::to_owned));
+    let signer = match unlocked.get(&key) {
+        Some(signer) => signer.clone(),
+        None => {
+            let default = cubist().await?.project(chain).unwrap().sender().await?;
+            let signer = signers.signer(&chain.to_string(), from, &resolver, default)?;
+            unlocked.insert(key, signer.clone());
+            signer
+        }
+    };
+    Ok(((chain.to_string(), signer.address()), (signer, receiver)))
+}
+
+async fn batch(
+    args: &BatchArgs,
+    from: Option<&str>,
+    gas: &GasArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let ops: Vec<BatchOp> = args.load()?;
+    // each operation is sent with its account's pending nonce
+    let policy = gas.policy(
+        env!("CARGO_MANIFEST_DIR"),
+        &TokenSender::target().to_string(),
+    )?;
+    ensure!(
+        policy.nonce.is_none(),
+        "'--nonce' cannot be used with 'batch'"
+    );
+
+    let signers = Signers::load(signers_path())?;
+    let mut unlocked = HashMap::new();
+    let mut jobs = vec![];
+    for op in &ops {
+        jobs.push(batch_op(op, from, &signers, &mut unlocked).await);
+    }
+
+    let results = args
+        .run(&ops, jobs, |op, (signer, receiver)| async move {
+            let (receipt, _) = match op {
+                BatchOp::Buy { amount, .. } => {
+                    send_payment(*amount, receiver, &signer, gas).await?
+                }
+                BatchOp::Sell { amount, .. } => send_sale(*amount, receiver, &signer, gas).await?,
+            };
+            Ok(receipt)
+        })
+        .await;
+    print_results(&results, output)
+}
+
+/// The settings a benchmark ran with, saved with its report.
+#[derive(Debug, Serialize)]
+struct BenchConfig {
+    token_sender_chain: String,
+    erc20_chain: String,
+    count: usize,
+    rate: f64,
+    accounts: usize,
+    #[serde(serialize_with = "ser_amount")]
+    payment_wei: U256,
+    timeout: u64,
+    poll_ms: u64,
+}
+
+impl BenchConfig {
+    fn new(args: &BenchArgs) -> Self {
+        Self {
+            token_sender_chain: TokenSender::target().to_string(),
+            erc20_chain: ERC20Bridged::target().to_string(),
+            count: args.count,
+            rate: args.rate,
+            accounts: args.accounts,
+            payment_wei: args.payment_wei,
+            timeout: args.timeout,
+            poll_ms: args.poll_ms,
+        }
+    }
+}
+
+/// How many payments of each account 'bench' keeps in flight at most.
+const BENCH_IN_FLIGHT: usize = 4;
+
+/// A payment sent by 'bench'.
+struct BenchPayment {
+    index: usize,
+    sender: Address,
+    /// The fresh address the FBB is minted to.
+    receiver: Address,
+    sent_at: Instant,
+    tx: Option<H256>,
+    error: Option<String>,
+}
+
+async fn bench(args: &BenchArgs, output: OutputFormat) -> Result<()> {
+    if output == OutputFormat::Csv {
+        bail!("'bench' only supports '--output table' and '--output json'");
+    }
+    let tok = TokenSender::deployed()
+        .await
+        .context("Contracts not deployed; call 'deploy' first")?;
+    let erc20 = ERC20Bridged::deployed().await?;
+    let (tok_client, erc20_client) = (tok.client(), erc20.client());
+    ensure!(args.count > 0, "'--count' must be positive");
+    ensure!(args.rate > 0.0, "'--rate' must be positive");
+    Quote::new(args.payment_wei).check()?;
+
+    let accounts = tok.project().accounts().await?;
+    ensure!(
+        (1..=accounts.len()).contains(&args.accounts),
+        "'--accounts' must be between 1 and {}, the number of local accounts on {}",
+        accounts.len(),
+        TokenSender::target()
+    );
+    let accounts = &accounts[..args.accounts];
+
+    // payments of the same account overlap, so their nonces are assigned up front:
+    // payment i is the (i / accounts)-th of account i % accounts
+    let k = accounts.len();
+    let mut nonces = vec![];
+    for (i, &acc) in accounts.iter().enumerate() {
+        let payments = (args.count + k - 1 - i) / k;
+        let needed = args
+            .payment_wei
+            .checked_mul(payments.into())
+            .ok_or_else(|| eyre!("{payments} payments of {} wei overflow", args.payment_wei))?;
+        let balance = tok_client.get_balance(acc, None).await?;
+        ensure!(
+            needed <= balance,
+            "{acc:?} needs {needed} wei for {payments} payments, but has {balance} wei"
         );
+        let pending = Some(BlockNumber::Pending.into());
+        nonces.push(tok_client.get_transaction_count(acc, pending).await?);
     }
-    print_sender_row(DASHES, DASHES, DASHES, DASHES, Some(SEP_BOT));
+    // every payment costs about the same, so estimate the gas once (with a margin)
+    let mut call = tok.bridge_send(Address::random()).from(accounts[0]);
+    call.tx.set_value(args.payment_wei);
+    let gas = call.estimate_gas().await? * 6 / 5;
 
-    println!();
-    print_erc20_row(DASHES, DASHES, DASHES, DASHES, DASHES, Some(SEP_TOP));
-    print_erc20_row(
-        format!("({})", ERC20Bridged::target()).green().bold(),
-        "name".bold(),
-        "address".bold(),
-        "wei".bold(),
-        "fbb".bold(),
-        None,
+    eprintln!(
+        "{} {} payments of {} wei from {} accounts at {} per second",
+        s_action!("Sending"),
+        s_value!(args.count),
+        s_value!(args.payment_wei),
+        s_value!(k),
+        s_value!(args.rate)
     );
-    print_erc20_row(DASHES, DASHES, DASHES, DASHES, DASHES, Some(SEP_MID));
-    let proj = cubist.project(ERC20Bridged::target()).unwrap();
-    for (i, acc) in erc20_accounts().await?.into_iter().enumerate() {
-        print_erc20_row(
-            i,
-            s_contract!(acc.0.unwrap_or_default()),
-            s_value!(format!("{:?}", acc.1)),
-            s_value!(proj.provider().get_balance(acc.1, None).await?.to_string()),
-            s_value!(get_fbb_balance(acc.1)
-                .await?
-                .unwrap_or_default()
-                .to_string()),
-            None,
+    let from_block = erc20_client.get_block_number().await?;
+    let started_at = SystemTime::now();
+    let start = Instant::now();
+    let timeout = Duration::from_secs(args.timeout);
+    // receivers of the payments sent so far, and when their FBB was seen minted
+    let sent: Mutex<Vec<(Address, Instant)>> = Mutex::default();
+    let minted: Mutex<HashMap<Address, Instant>> = Mutex::default();
+    let done = AtomicUsize::new(0);
+    // accounts with a payment that may not have used its nonce; their later
+    // payments would never be mined, so they are not sent
+    let broken: Vec<AtomicBool> = accounts.iter().map(|_| AtomicBool::new(false)).collect();
+
+    let (tok, sent_ref, done_ref, nonces, broken) = (&tok, &sent, &done, &nonces, &broken);
+    let sends = stream::iter(0..args.count)
+        .map(|i| async move {
+            let due = start + Duration::from_secs_f64(i as f64 / args.rate);
+            tokio::time::sleep(due.saturating_duration_since(Instant::now())).await;
+
+            let (sender, receiver) = (accounts[i % k], Address::random());
+            let mut call = tok.bridge_send(receiver).from(sender);
+            call.tx.set_value(args.payment_wei);
+            call.tx.set_gas(gas);
+            call.tx.set_nonce(nonces[i % k] + i / k);
+            let sent_at = Instant::now();
+            let mut payment = BenchPayment {
+                index: i,
+                sender,
+                receiver,
+                sent_at,
+                tx: None,
+                error: None,
+            };
+            let result = if broken[i % k].load(Ordering::SeqCst) {
+                Err(eyre!(
+                    "Not sent: an earlier payment of {sender:?} left a gap in its nonces"
+                ))
+            } else {
+                let mined = tokio::time::timeout(timeout, async {
+                    let pending = call.send().await?;
+                    payment.tx = Some(pending.tx_hash());
+                    Ok::<_, eyre::Report>(pending.await?)
+                })
+                .await;
+                // only a mined payment (even a reverted one) is sure to have used its nonce
+                let (result, gap) = match mined {
+                    Ok(Ok(Some(receipt))) if receipt.status == Some(0.into()) => {
+                        (Err(eyre!("Transaction reverted")), false)
+                    }
+                    Ok(Ok(Some(_))) => (Ok(()), false),
+                    Ok(Ok(None)) => (Err(eyre!("Transaction was dropped")), true),
+                    Ok(Err(e)) => (Err(e), true),
+                    Err(_) => (Err(eyre!("Not mined within {timeout:?}")), true),
+                };
+                if gap {
+                    broken[i % k].store(true, Ordering::SeqCst);
+                }
+                result
+            };
+            match result {
+                Ok(()) => sent_ref.lock().unwrap().push((receiver, sent_at)),
+                Err(e) => payment.error = Some(format!("{e:#}")),
+            }
+            done_ref.fetch_add(1, Ordering::SeqCst);
+            payment
+        })
+        // payments start in order, so this keeps about as many in flight per account
+        .buffer_unordered(k * BENCH_IN_FLIGHT)
+        .collect::<Vec<_>>();
+
+    // watch for mints (a 'Transfer' from the zero address) until every payment
+    // is minted or timed out
+    let watch = async {
+        let mut next_block = from_block;
+        loop {
+            let latest = erc20_client.get_block_number().await?;
+            if latest >= next_block {
+                let filter = erc20
+                    .transfer_filter()
+                    .from_block(next_block)
+                    .to_block(latest)
+                    .topic1(Address::zero())
+                    .filter;
+                let logs = erc20_client.get_logs(&filter).await?;
+                let seen_at = Instant::now();
+                let mut minted = minted.lock().unwrap();
+                for log in logs {
+                    minted
+                        .entry(Address::from(log.topics[2]))
+                        .or_insert(seen_at);
+                }
+                next_block = latest + 1;
+            }
+            if done.load(Ordering::SeqCst) == args.count {
+                let minted = minted.lock().unwrap();
+                let waiting = sent
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|(r, t)| !minted.contains_key(r) && t.elapsed() < timeout);
+                if !waiting {
+                    return Ok::<_, eyre::Report>(());
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(args.poll_ms)).await;
+        }
+    };
+    let (payments, watched) = futures::join!(sends, watch);
+    watched?;
+
+    let minted = minted.into_inner().unwrap();
+    let mut samples: Vec<_> = payments
+        .into_iter()
+        .map(|p| {
+            let (status, latency, error) = match (p.error, minted.get(&p.receiver)) {
+                (Some(e), _) => (SampleStatus::SendFailed, None, Some(e)),
+                (None, Some(at)) => (
+                    SampleStatus::Delivered,
+                    Some(at.saturating_duration_since(p.sent_at)),
+                    None,
+                ),
+                (None, None) => (
+                    SampleStatus::Timeout,
+                    None,
+                    Some(format!("Not minted within {timeout:?}")),
+                ),
+            };
+            Sample {
+                index: p.index,
+                sender: p.sender,
+                tx: p.tx,
+                status,
+                sent_ms: p.sent_at.duration_since(start).as_millis() as u64,
+                latency_ms: latency.map(|l| l.as_millis() as u64),
+                error,
+            }
+        })
+        .collect();
+    samples.sort_by_key(|s| s.index);
+
+    let config = BenchConfig::new(args);
+    let report = BenchReport::new(args.label.clone(), started_at, config, samples);
+    report.save(&args.out)?;
+    if output == OutputFormat::Table {
+        report.print();
+    } else {
+        print_records(output, &[&report.summary])?;
+    }
+    eprintln!(
+        "{} report to {}",
+        s_action!("Saved"),
+        s_value!(args.out.display())
+    );
+    Ok(())
+}
+
+fn quote(payment_wei: U256) -> Result<()> {
+    let quote = Quote::new(payment_wei);
+    quote.print();
+    quote.check()
+}
+
+/// Print the balances of the account that signed a transaction on `target`.
+fn print_signer(target: impl std::fmt::Display, signer: Address, wei: U256, fbb: Option<U256>) {
+    let fbb = fbb
+        .map(|f| format!(", {} FBB", s_value!(f)))
+        .unwrap_or_default();
+    eprintln!(
+        "{} {} on {} now has {} wei{fbb}",
+        s_action!("Sender"),
+        s_value!(format!("{signer:?}")),
+        s_value!(target),
+        s_value!(wei),
+    );
+}
+
+fn print_delivered(what: &str, tx: H256, latency: Duration) {
+    eprintln!(
+        "{} in transaction {} after {}",
+        s_action!(what),
+        s_value!(format!("{tx:?}")),
+        s_value!(format!("{latency:.2?}")),
+    );
+}
+
+/// Decode a 'Transfer(address indexed, address indexed, uint256)' event (or one
+/// like it, whose first data word is the amount) into its sender, recipient and amount.
+fn decode_transfer(log: &Log) -> Result<(Address, Address, U256)> {
+    ensure!(
+        log.topics.len() >= 3,
+        "Unexpected event in {:?}",
+        log.transaction_hash
+    );
+    let amount = log.data.get(..32).map(U256::from_big_endian);
+    Ok((
+        Address::from(log.topics[1]),
+        Address::from(log.topics[2]),
+        amount.unwrap_or_default(),
+    ))
+}
+
+/// Decode the arguments of a call to a function with the given parameter types.
+fn call_args(input: &[u8], types: &[ParamType]) -> Result<Vec<Token>> {
+    Ok(abi::decode(types, input.get(4..).unwrap_or_default())?)
+}
+
+/// Transfers in both directions, in the blocks selected by `scan` (on both chains).
+async fn bridge_transfers(scan: &ScanArgs) -> Result<Vec<Transfer>> {
+    let tok = TokenSender::deployed()
+        .await
+        .context("Contracts not deployed; call 'deploy' first")?;
+    let erc20 = ERC20Bridged::deployed().await?;
+    let (tok_client, erc20_client) = (tok.client(), erc20.client());
+    let (tok_target, erc20_target) = (TokenSender::target(), ERC20Bridged::target());
+    let tok_from = scan.from_block(&tok_client).await?;
+    let erc20_from = scan.from_block(&erc20_client).await?;
+
+    // 'BridgeSent(from, to, paid, sent)' for payments, 'BridgeReceived(to, amount)' for releases
+    let payments = tok.bridge_sent_filter().filter;
+    let mut payments = log_actions(&tok_client, &payments, tok_from, decode_transfer).await?;
+    let releases = tok.bridge_received_filter().filter;
+    let mut releases = log_actions(&tok_client, &releases, tok_from, |log| {
+        ensure!(
+            log.topics.len() >= 2,
+            "Unexpected event in {:?}",
+            log.transaction_hash
         );
+        let amount = log.data.get(..32).map(U256::from_big_endian);
+        Ok((
+            tok.address(),
+            Address::from(log.topics[1]),
+            amount.unwrap_or_default(),
+        ))
+    })
+    .await?;
+
+    // minting emits a 'Transfer' from the zero address, burning one to the zero address
+    let mints = erc20.transfer_filter().topic1(Address::zero()).filter;
+    let mut mints = log_actions(&erc20_client, &mints, erc20_from, decode_transfer).await?;
+    let burns = erc20.transfer_filter().topic2(Address::zero()).filter;
+    let mut burns = log_actions(&erc20_client, &burns, erc20_from, decode_transfer).await?;
+    for burn in &mut burns {
+        // burns only happen in 'ERC20Bridged.bridgeSend', whose first argument is the recipient
+        let input = erc20_client
+            .get_transaction(burn.tx)
+            .await?
+            .unwrap_or_default()
+            .input;
+        let call = call_args(&input, &[ParamType::Address, ParamType::Uint(256)])?;
+        burn.to = call[0].clone().into_address().unwrap_or_default();
     }
-    print_erc20_row(DASHES, DASHES, DASHES, DASHES, DASHES, Some(SEP_BOT));
+
+    // reverted payments, releases, burns and mints emit no events
+    let to_and_amount = |call: &RevertedCall| -> Result<(Address, U256)> {
+        let args = call_args(&call.tx.input, &[ParamType::Address, ParamType::Uint(256)])?;
+        Ok((
+            args[0].clone().into_address().unwrap_or_default(),
+            args[1].clone().into_uint().unwrap_or_default(),
+        ))
+    };
+    for call in reverted_calls(&tok_client, tok.address(), tok_from).await? {
+        if call.calls(id("bridgeSend(address)")) {
+            let args = call_args(&call.tx.input, &[ParamType::Address])?;
+            let to = args[0].clone().into_address().unwrap_or_default();
+            payments.push(call.action(to, call.tx.value));
+        } else if call.calls(id("bridgeReceive(address,uint256)")) {
+            let (to, amount) = to_and_amount(&call)?;
+            releases.push(call.action(to, amount));
+        }
+    }
+    for call in reverted_calls(&erc20_client, erc20.address(), erc20_from).await? {
+        if call.calls(id("bridgeSend(address,uint256)")) {
+            let (to, amount) = to_and_amount(&call)?;
+            burns.push(call.action(to, amount));
+        } else if call.calls(id("bridgeMint(address,uint256)")) {
+            let (to, amount) = to_and_amount(&call)?;
+            mints.push(call.action(to, amount));
+        }
+    }
+
+    let mut transfers = pair_transfers("buy", tok_target, erc20_target, payments, mints, |paid| {
+        Quote::new(paid).minted
+    });
+    transfers.extend(pair_transfers(
+        "sell",
+        erc20_target,
+        tok_target,
+        burns,
+        releases,
+        |burnt| burnt,
+    ));
+    Ok(transfers)
+}
+
+async fn history(args: &HistoryArgs, output: OutputFormat) -> Result<()> {
+    let mut transfers = bridge_transfers(&args.scan).await?;
+    if let Some(addr) = &args.address {
+        let mut contracts = token_sender_contracts().await;
+        contracts.extend(erc20_contracts().await);
+        let addr = resolver(None, contracts).await?.resolve(addr)?;
+        transfers.retain(|t| t.involves(addr));
+    }
+
+    if output != OutputFormat::Table {
+        return print_records(output, &transfers);
+    }
+    print_history(&transfers)
+}
+
+fn print_history(transfers: &[Transfer]) -> Result<()> {
+    println!();
+    let mut table = Table::new(
+        &[
+            Column::left(4),
+            Column::left(10),
+            Column::right(42),
+            Column::right(22),
+            Column::right(22),
+            Column::right(15),
+            Column::right(8),
+        ],
+        &[
+            &s_header!("kind"),
+            &s_header!("status"),
+            &s_header!("to"),
+            &s_header!("sent"),
+            &s_header!("received"),
+            &s_header!("blocks"),
+            &s_header!("latency"),
+        ],
+    )?;
+    for t in transfers {
+        let status: Box<dyn std::fmt::Display + '_> = match t.status {
+            TransferStatus::Delivered => Box::new(s_action!(t.status)),
+            TransferStatus::Pending | TransferStatus::Unmatched => Box::new(s_value!(t.status)),
+            _ => Box::new(s_error!(t.status)),
+        };
+        let block = |b: Option<u64>| b.map(|b| b.to_string()).unwrap_or_else(|| "?".into());
+        table.row(&[
+            &t.kind,
+            &status,
+            &s_value!(format!("{:?}", t.to)),
+            &t.sent.map(|a| a.to_string()).unwrap_or_default(),
+            &t.received.map(|a| a.to_string()).unwrap_or_default(),
+            &format!("{} -> {}", block(t.source_block), block(t.dest_block)),
+            &t.latency_secs.map(|l| format!("{l}s")).unwrap_or_default(),
+        ])?;
+    }
+    table.print();
     println!();
     Ok(())
 }
 
-async fn buy(args: &BuyArgs) -> Result<()> {
+/// The results of 'audit'; amounts are in wei (or FBB base units, which are 1:1).
+#[derive(Debug, Default, Serialize)]
+struct Audit {
+    #[serde(serialize_with = "ser_amount")]
+    token_sender_balance: U256,
+    #[serde(serialize_with = "ser_amount")]
+    total_supply: U256,
+    /// Native tokens 'TokenSender' accounts for as collateral.
+    #[serde(serialize_with = "ser_amount")]
+    collateral: U256,
+    /// Fees kept from all payments.
+    #[serde(serialize_with = "ser_amount")]
+    fees: U256,
+    /// Fees 'TokenSender' kept and not collected yet.
+    #[serde(serialize_with = "ser_amount")]
+    accrued_fees: U256,
+    /// FBB still to be minted for payments in flight.
+    #[serde(serialize_with = "ser_amount")]
+    pending_mints: U256,
+    /// Native tokens still to be released for burns in flight.
+    #[serde(serialize_with = "ser_amount")]
+    pending_releases: U256,
+    /// FBB (or native tokens) owed for transfers whose delivery reverted.
+    #[serde(serialize_with = "ser_amount")]
+    undelivered: U256,
+    /// FBB minted without a matching payment.
+    #[serde(serialize_with = "ser_amount")]
+    unbacked: U256,
+    /// How much 'TokenSender' is missing to back FBB in circulation and in flight.
+    #[serde(serialize_with = "ser_amount")]
+    shortfall: U256,
+    violations: Vec<String>,
+}
+
+impl Audit {
+    /// Check the transfers for unbacked mints and releases and for reverted
+    /// deliveries, and add up fees and transfers in flight.
+    fn check_transfers(&mut self, transfers: &[Transfer]) {
+        for t in transfers {
+            let (sent, expected, received) = (
+                t.sent.unwrap_or_default(),
+                t.expected.unwrap_or_default(),
+                t.received.unwrap_or_default(),
+            );
+            let (to, tx) = (t.to, t.dest_tx.unwrap_or_default());
+            match (t.kind.as_str(), t.status) {
+                ("buy", TransferStatus::Delivered | TransferStatus::Mismatched) => {
+                    self.add_fee(t);
+                    if received > expected {
+                        self.unbacked += received - expected;
+                        self.violations.push(format!(
+                            "{received} FBB minted to {to:?} for a payment of {sent} wei \
+                             (expected {expected}) in {tx:?}"
+                        ));
+                    }
+                }
+                ("buy", TransferStatus::Pending) => {
+                    self.add_fee(t);
+                    self.pending_mints += expected;
+                }
+                ("buy", TransferStatus::Failed) if t.source_tx.is_some() => {
+                    self.add_fee(t);
+                    self.undelivered += expected;
+                    self.violations.push(format!(
+                        "Minting {expected} FBB to {to:?} for a payment of {sent} wei reverted in \
+                         {tx:?}"
+                    ));
+                }
+                ("buy", TransferStatus::Unmatched) => {
+                    self.unbacked += received;
+                    self.violations.push(format!(
+                        "{received} FBB minted to {to:?} without a matching payment in {tx:?}"
+                    ));
+                }
+                ("sell", TransferStatus::Pending) => {
+                    self.pending_releases += expected;
+                }
+                ("sell", TransferStatus::Failed) if t.source_tx.is_some() => {
+                    self.undelivered += expected;
+                    self.violations.push(format!(
+                        "Releasing {expected} wei to {to:?} for a burn of {sent} FBB reverted in \
+                         {tx:?}"
+                    ));
+                }
+                ("sell", TransferStatus::Mismatched) if received > expected => {
+                    self.violations.push(format!(
+                        "{received} wei released to {to:?} for a burn of {sent} FBB in {tx:?}"
+                    ));
+                }
+                // reverted releases left over are 'Failed', not 'Unmatched', and released
+                // nothing (see 'pair_transfers')
+                ("sell", TransferStatus::Unmatched) => {
+                    self.violations.push(format!(
+                        "{received} wei released to {to:?} without a matching burn in {tx:?}"
+                    ));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Add the fee kept from the payment of `t`, which must cover the FBB to mint.
+    fn add_fee(&mut self, t: &Transfer) {
+        let (sent, expected) = (t.sent.unwrap_or_default(), t.expected.unwrap_or_default());
+        match sent.checked_sub(expected) {
+            Some(fee) => self.fees += fee,
+            None => self.violations.push(format!(
+                "{expected} FBB to mint to {:?} for a payment of only {sent} wei in {:?}",
+                t.to,
+                t.source_tx.unwrap_or_default()
+            )),
+        }
+    }
+
+    /// FBB in circulation plus transfers in flight or undelivered, all of which
+    /// 'TokenSender' must back.
+    fn required(&self) -> U256 {
+        self.total_supply + self.pending_mints + self.pending_releases + self.undelivered
+    }
+
+    fn check_solvency(&mut self) {
+        let balance = self.token_sender_balance;
+        self.shortfall = self.required().saturating_sub(balance);
+        if balance < self.total_supply {
+            self.violations.push(format!(
+                "{TOKEN_SENDER} holds {balance} wei, less than the {} FBB in circulation",
+                self.total_supply
+            ));
+        } else if !self.shortfall.is_zero() {
+            self.violations.push(format!(
+                "{TOKEN_SENDER} holds {balance} wei, less than the {} FBB in circulation plus {} \
+                 to mint and {} to release for transfers in flight, and {} owed for reverted \
+                 deliveries",
+                self.total_supply, self.pending_mints, self.pending_releases, self.undelivered
+            ));
+        }
+    }
+}
+
+async fn audit(output: OutputFormat) -> Result<()> {
+    if output == OutputFormat::Csv {
+        bail!("'audit' only supports '--output table' and '--output json'");
+    }
     let tok = TokenSender::deployed()
         .await
         .context("Contracts not deployed; call 'deploy' first")?;
-    let receiver = to_address(&args.fbb_receiver, erc20_accounts().await?)?;
-    let mut call = tok.bridge_send(receiver);
-    call.tx.set_value(args.payment_wei);
+    let erc20 = ERC20Bridged::deployed().await?;
+    let mut audit = Audit {
+        token_sender_balance: tok.client().get_balance(tok.address(), None).await?,
+        total_supply: erc20.total_supply().call().await?,
+        collateral: tok.collateral().call().await?,
+        accrued_fees: tok.accrued_fees().call().await?,
+        ..Default::default()
+    };
+    // fees and transfers in flight are only known from the whole history
+    audit.check_transfers(&bridge_transfers(&ScanArgs::all()).await?);
+    audit.check_solvency();
+
+    if output != OutputFormat::Table {
+        print_records(output, &[&audit])?;
+        if !audit.violations.is_empty() {
+            bail!("{} violation(s)", audit.violations.len());
+        }
+        return Ok(());
+    }
+
     println!(
-        "\n{} {}.bridge_send{{value: {}}}({:?})\n",
-        s_action!("Calling"),
+        "{} {} on {} against {} on {}",
+        s_action!("Auditing"),
         s_contract!(TOKEN_SENDER),
-        s_value!(args.payment_wei),
-        s_value!(receiver)
+        s_value!(TokenSender::target()),
+        s_contract!(ERC20_BRIDGED),
+        s_value!(ERC20Bridged::target())
     );
-    call.send().await?.await?;
+    let mut report = Report::default();
+    for violation in &audit.violations {
+        report.fail("audit", violation);
+    }
+    if audit.shortfall.is_zero() {
+        report.ok(
+            "solvency",
+            format!(
+                "{TOKEN_SENDER} holds {} wei, backing the {} FBB in circulation plus {} to mint \
+                 and {} to release for transfers in flight, and {} owed for reverted deliveries",
+                s_value!(audit.token_sender_balance),
+                s_value!(audit.total_supply),
+                s_value!(audit.pending_mints),
+                s_value!(audit.pending_releases),
+                s_value!(audit.undelivered)
+            ),
+        );
+        // 'TokenSender' only gets native tokens from payments, so the surplus should be
+        // the fees not collected yet
+        let surplus = audit.token_sender_balance - audit.required();
+        let collected = audit.fees.saturating_sub(audit.accrued_fees);
+        if surplus == audit.accrued_fees {
+            report.ok(
+                "fees",
+                format!(
+                    "{} wei accumulated, {} collected, {} to collect",
+                    s_value!(audit.fees),
+                    s_value!(collected),
+                    s_value!(audit.accrued_fees)
+                ),
+            );
+        } else {
+            report.warn(
+                "fees",
+                format!(
+                    "{} wei to collect, but the surplus is {} wei",
+                    s_value!(audit.accrued_fees),
+                    s_value!(surplus)
+                ),
+            );
+        }
+    }
+    if audit.collateral != audit.required() {
+        report.warn(
+            "collateral",
+            format!(
+                "{TOKEN_SENDER} accounts for {} wei of collateral, but {} FBB are in circulation \
+                 or in flight",
+                s_value!(audit.collateral),
+                s_value!(audit.required())
+            ),
+        );
+    }
+    report.finish()
+}
+
+/// What 'TokenSender' holds, as shown by 'fees'; amounts are in wei (or FBB base
+/// units, which are 1:1).
+#[derive(Debug, Serialize)]
+struct FeeStatus {
+    #[serde(serialize_with = "ser_amount")]
+    token_sender_balance: U256,
+    /// Native tokens backing FBB in circulation and in flight.
+    #[serde(serialize_with = "ser_amount")]
+    collateral: U256,
+    /// Fees kept and not collected yet.
+    #[serde(serialize_with = "ser_amount")]
+    accrued_fees: U256,
+    #[serde(serialize_with = "ser_amount")]
+    total_supply: U256,
+}
+
+impl FeeStatus {
+    async fn load() -> Result<Self> {
+        let tok = TokenSender::deployed()
+            .await
+            .context("Contracts not deployed; call 'deploy' first")?;
+        let erc20 = ERC20Bridged::deployed().await?;
+        Ok(Self {
+            token_sender_balance: tok.client().get_balance(tok.address(), None).await?,
+            collateral: tok.collateral().call().await?,
+            accrued_fees: tok.accrued_fees().call().await?,
+            total_supply: erc20.total_supply().call().await?,
+        })
+    }
+
+    fn print(&self) {
+        println!(
+            "{:>10} {} wei",
+            s_header!("balance"),
+            s_value!(self.token_sender_balance)
+        );
+        println!(
+            "{:>10} {} wei (backing FBB)",
+            s_header!("collateral"),
+            s_value!(self.collateral)
+        );
+        println!(
+            "{:>10} {} wei (to collect)",
+            s_header!("fees"),
+            s_value!(self.accrued_fees)
+        );
+        println!(
+            "{:>10} {} FBB",
+            s_header!("supply"),
+            s_value!(self.total_supply)
+        );
+    }
+}
+
+async fn fees(output: OutputFormat) -> Result<()> {
+    let fees = FeeStatus::load().await?;
+    if output != OutputFormat::Table {
+        return print_records(output, &[&fees]);
+    }
+    fees.print();
     Ok(())
 }
 
-async fn sell(args: &SellArgs) -> Result<()> {
-    let erc20 = ERC20Bridged::deployed()
+async fn collect_fees(
+    args: &CollectFeesArgs,
+    from: Option<&str>,
+    gas: &GasArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let tok = TokenSender::deployed()
         .await
         .context("Contracts not deployed; call 'deploy' first")?;
-    let receiver = to_address(&args.wei_receiver, token_sender_accounts().await?)?;
-    println!(
-        "\n{} {}.bridge_send({:?}, {})\n",
+    let resolver = resolver(
+        Some(TokenSender::target().to_string()),
+        token_sender_contracts().await,
+    )
+    .await?;
+    let receiver = resolver.resolve(&args.to)?;
+
+    // fail before signing anything, rather than letting the chain revert
+    let target = TokenSender::target();
+    let signer = Signers::load(signers_path())?.signer(
+        &target.to_string(),
+        from,
+        &resolver,
+        tok.project().sender().await?,
+    )?;
+    let sender = signer.address();
+    let owner = tok.owner().call().await?;
+    ensure!(
+        sender == owner,
+        "Only the owner of {TOKEN_SENDER} ({owner:?}) can collect fees, not {sender:?}"
+    );
+    let fees = tok.accrued_fees().call().await?;
+    ensure!(!fees.is_zero(), "{TOKEN_SENDER} has no fees to collect");
+    let policy = gas.policy(env!("CARGO_MANIFEST_DIR"), &target.to_string())?;
+
+    let mut call = tok.collect_fees(receiver).from(sender);
+    eprintln!(
+        "\n{} {}.collect_fees({:?}) from {:?}, collecting {} wei\n",
         s_action!("Calling"),
-        s_contract!(ERC20_BRIDGED),
+        s_contract!(TOKEN_SENDER),
         s_value!(receiver),
-        s_value!(args.amount_fbb),
+        s_value!(sender),
+        s_value!(fees)
     );
-    erc20
-        .bridge_send(receiver, U256::from(args.amount_fbb))
-        .send()
-        .await?
-        .await?;
+    let (receipt, _) = send_call(
+        &tok.client(),
+        target,
+        TOKEN_SENDER,
+        tok.abi(),
+        &mut call,
+        &signer,
+        &policy,
+    )
+    .await?;
+    receipt.print(output)?;
+
+    let balance = tok.client().get_balance(tok.address(), None).await?;
+    let collateral = tok.collateral().call().await?;
+    eprintln!(
+        "{} {} wei; {} now holds {} wei, backing {} wei of collateral",
+        s_action!("Collected"),
+        s_value!(fees),
+        s_contract!(TOKEN_SENDER),
+        s_value!(balance),
+        s_value!(collateral)
+    );
+    Ok(())
+}
+
+/// What 'token info' shows about FBB.
+#[derive(Debug, Serialize)]
+struct TokenInfo {
+    target: String,
+    address: Address,
+    name: String,
+    symbol: String,
+    decimals: u8,
+    #[serde(serialize_with = "ser_amount")]
+    total_supply: U256,
+}
+
+/// What 'token allowance' shows.
+#[derive(Debug, Serialize)]
+struct Allowance {
+    owner: Address,
+    spender: Address,
+    #[serde(serialize_with = "ser_amount")]
+    allowance: U256,
+}
+
+async fn token(
+    cmd: &TokenCommand,
+    from: Option<&str>,
+    gas: &GasArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let erc20 = ERC20Bridged::deployed()
+        .await
+        .context("Contracts not deployed; call 'deploy' first")?;
+    let target = ERC20Bridged::target();
+    let addrs = resolver(Some(target.to_string()), erc20_contracts().await).await?;
+    let signers = Signers::load(signers_path())?;
+    let default_sender = erc20.project().sender().await?;
+
+    match cmd {
+        TokenCommand::Info => {
+            let info = TokenInfo {
+                target: target.to_string(),
+                address: erc20.address(),
+                name: erc20.name().call().await?,
+                symbol: erc20.symbol().call().await?,
+                decimals: erc20.decimals().call().await?,
+                total_supply: erc20.total_supply().call().await?,
+            };
+            if output != OutputFormat::Table {
+                return print_records(output, &[&info]);
+            }
+            println!("{:>10} {}", s_header!("chain"), s_value!(&info.target));
+            println!(
+                "{:>10} {}",
+                s_header!("address"),
+                s_value!(format!("{:?}", info.address))
+            );
+            println!("{:>10} {}", s_header!("name"), s_value!(&info.name));
+            println!("{:>10} {}", s_header!("symbol"), s_value!(&info.symbol));
+            println!("{:>10} {}", s_header!("decimals"), s_value!(info.decimals));
+            println!(
+                "{:>10} {} {}",
+                s_header!("supply"),
+                s_value!(info.total_supply),
+                info.symbol
+            );
+        }
+        TokenCommand::Allowance { owner, spender } => {
+            let (owner, spender) = (addrs.resolve(owner)?, addrs.resolve(spender)?);
+            let allowance = Allowance {
+                owner,
+                spender,
+                allowance: erc20.allowance(owner, spender).call().await?,
+            };
+            if output != OutputFormat::Table {
+                return print_records(output, &[&allowance]);
+            }
+            println!(
+                "{:?} may spend {} FBB of {:?}",
+                s_value!(spender),
+                s_value!(allowance.allowance),
+                s_value!(owner)
+            );
+        }
+        TokenCommand::Transfer { to, amount_fbb } => {
+            let to = addrs.resolve(to)?;
+            let signer = signers.signer(&target.to_string(), from, &addrs, default_sender)?;
+            let sender = signer.address();
+            // fail before signing anything, rather than letting the chain revert
+            let fbb = erc20.balance_of(sender).call().await?;
+            ensure!(
+                *amount_fbb <= fbb,
+                "Transferring {amount_fbb} FBB exceeds the FBB balance of {sender:?} ({fbb})"
+            );
+            eprintln!(
+                "\n{} {}.transfer({:?}, {}) from {:?}\n",
+                s_action!("Calling"),
+                s_contract!(ERC20_BRIDGED),
+                s_value!(to),
+                s_value!(amount_fbb),
+                s_value!(sender)
+            );
+            let mut call = erc20.transfer(to, *amount_fbb).from(sender);
+            send_erc20(
+                &erc20.client(),
+                erc20.abi(),
+                &signer,
+                &mut call,
+                gas,
+                output,
+            )
+            .await?;
+            let wei = erc20.client().get_balance(sender, None).await?;
+            let fbb = erc20.balance_of(sender).call().await?;
+            print_signer(target, sender, wei, Some(fbb));
+        }
+        TokenCommand::Approve {
+            spender,
+            amount_fbb,
+        } => {
+            let spender = addrs.resolve(spender)?;
+            let signer = signers.signer(&target.to_string(), from, &addrs, default_sender)?;
+            let sender = signer.address();
+            eprintln!(
+                "\n{} {}.approve({:?}, {}) from {:?}\n",
+                s_action!("Calling"),
+                s_contract!(ERC20_BRIDGED),
+                s_value!(spender),
+                s_value!(amount_fbb),
+                s_value!(sender)
+            );
+            let mut call = erc20.approve(spender, *amount_fbb).from(sender);
+            send_erc20(
+                &erc20.client(),
+                erc20.abi(),
+                &signer,
+                &mut call,
+                gas,
+                output,
+            )
+            .await?;
+        }
+        TokenCommand::TransferFrom {
+            owner,
+            to,
+            amount_fbb,
+        } => {
+            let (owner, to) = (addrs.resolve(owner)?, addrs.resolve(to)?);
+            let signer = signers.signer(&target.to_string(), from, &addrs, default_sender)?;
+            let sender = signer.address();
+            // fail before signing anything, rather than letting the chain revert
+            let allowance = erc20.allowance(owner, sender).call().await?;
+            ensure!(
+                *amount_fbb <= allowance,
+                "Transferring {amount_fbb} FBB exceeds what {owner:?} allows {sender:?} to \
+                 spend ({allowance})"
+            );
+            let fbb = erc20.balance_of(owner).call().await?;
+            ensure!(
+                *amount_fbb <= fbb,
+                "Transferring {amount_fbb} FBB exceeds the FBB balance of {owner:?} ({fbb})"
+            );
+            eprintln!(
+                "\n{} {}.transferFrom({:?}, {:?}, {}) from {:?}\n",
+                s_action!("Calling"),
+                s_contract!(ERC20_BRIDGED),
+                s_value!(owner),
+                s_value!(to),
+                s_value!(amount_fbb),
+                s_value!(sender)
+            );
+            let mut call = erc20.transfer_from(owner, to, *amount_fbb).from(sender);
+            send_erc20(
+                &erc20.client(),
+                erc20.abi(),
+                &signer,
+                &mut call,
+                gas,
+                output,
+            )
+            .await?;
+            eprintln!(
+                "{} {:?} may still spend {} FBB of {:?}",
+                s_action!("Allowance"),
+                s_value!(sender),
+                s_value!(erc20.allowance(owner, sender).call().await?),
+                s_value!(owner)
+            );
+        }
+    }
     Ok(())
 }
 
-fn to_address<T>(addr: &str, acc: Vec<(T, Address)>) -> Result<Address> {
-    if addr.starts_with("0x") {
-        Ok(Address::from_str(addr).context(format!("Invalid address: {addr}"))?)
-    } else if let Ok(i) = addr.parse::<usize>() {
-        let t = acc
-            .get(i)
-            .ok_or_else(|| eyre!("Index out of bounds, must be between 0..{}", acc.len() - 1))?;
-        Ok(t.1)
+/// Send `call` to 'ERC20Bridged' (with ABI `abi`) through `client`, signed by
+/// `signer`, with the gas settings for its chain, and print its receipt.
+async fn send_erc20<C, M, D>(
+    client: &C,
+    abi: &Abi,
+    signer: &ChainSigner,
+    call: &mut ContractCall<M, D>,
+    gas: &GasArgs,
+    output: OutputFormat,
+) -> Result<()>
+where
+    C: Middleware,
+    C::Error: 'static,
+    C::Provider: Clone + 'static,
+    M: Middleware + 'static,
+    D: Detokenize,
+{
+    let target = ERC20Bridged::target();
+    let policy = gas.policy(env!("CARGO_MANIFEST_DIR"), &target.to_string())?;
+    let (receipt, _) = send_call(client, target, ERC20_BRIDGED, abi, call, signer, &policy).await?;
+    receipt.print(output)
+}
+
+async fn alias(cmd: &AliasCommand) -> Result<()> {
+    let mut book = AddressBook::load(address_book_path())?;
+    match cmd {
+        AliasCommand::List => {
+            let mut table = Table::new(
+                &[Column::left(20), Column::left(42)],
+                &[&s_header!("alias"), &s_header!("address")],
+            )?;
+            for (name, addr) in book.iter() {
+                table.row(&[&s_contract!(name), &s_value!(format!("{addr:?}"))])?;
+            }
+            table.print();
+            return Ok(());
+        }
+        AliasCommand::Add { name, address } => {
+            // contracts on both chains are fair game, but a bare index is ambiguous
+            let contracts = [token_sender_contracts().await, erc20_contracts().await].concat();
+            let addr = resolver(None, contracts).await?.resolve(address)?;
+            book.insert(name, addr)?;
+            println!(
+                "{} {} = {:?}",
+                s_action!("Added"),
+                s_contract!(name),
+                s_value!(addr)
+            );
+        }
+        AliasCommand::Remove { name } => {
+            if book.remove(name).is_none() {
+                bail!("No alias named '{name}' in {}", book.path().display());
+            }
+            println!("{} {}", s_action!("Removed"), s_contract!(name));
+        }
+    }
+    book.save()
+}
+
+async fn doctor() -> Result<()> {
+    let mut doctor = Doctor::new(env!("CARGO_MANIFEST_DIR"))?;
+    doctor.check_networks().await;
+
+    println!("{}", s_header!("deployment"));
+    let cubist = match cubist().await {
+        Ok(cubist) => cubist,
+        Err(e) => {
+            doctor.fail("sdk", format!("Cannot load the Cubist project: {e:#}"));
+            return doctor.finish();
+        }
+    };
+    let mut deployed = false;
+    for (target, contracts) in [
+        (TokenSender::target(), token_sender_contracts().await),
+        (ERC20Bridged::target(), erc20_contracts().await),
+    ] {
+        let provider = cubist.project(target).unwrap().provider();
+        for (name, addr) in contracts {
+            deployed = true;
+            doctor.check_code(target, &name, &provider, addr).await;
+        }
+    }
+    if deployed {
+        doctor
+            .check_bridged(cubist.when_bridged(None), Duration::from_secs(10))
+            .await;
     } else {
-        bail!("Invalid address: {}", addr)
+        doctor.warn("sdk", "Contracts not deployed; call 'deploy' first");
+    }
+    doctor.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(
+        kind: &str,
+        status: TransferStatus,
+        source_tx: Option<u64>,
+        amount: u64,
+    ) -> Transfer {
+        Transfer {
+            kind: kind.to_owned(),
+            status,
+            from: source_tx.map(|_| Address::repeat_byte(0xff)),
+            to: Address::repeat_byte(1),
+            source_chain: "ethereum".to_owned(),
+            source_tx: source_tx.map(H256::from_low_u64_be),
+            source_block: source_tx,
+            sent: source_tx.map(|_| amount.into()),
+            expected: source_tx.map(|_| amount.into()),
+            dest_chain: "polygon".to_owned(),
+            dest_tx: Some(H256::from_low_u64_be(100)),
+            dest_block: Some(100),
+            received: Some(amount.into()),
+            latency_secs: None,
+        }
+    }
+
+    #[test]
+    fn audit_reports_reverted_releases() {
+        let mut audit = Audit {
+            token_sender_balance: 1000.into(),
+            total_supply: 300.into(),
+            ..Default::default()
+        };
+        audit.check_transfers(&[
+            // a burn whose release reverted (e.g., for exceeding the collateral)
+            transfer("sell", TransferStatus::Failed, Some(1), 500),
+            // a reverted release left over, e.g., one retried successfully
+            transfer("sell", TransferStatus::Failed, None, 200),
+            transfer("sell", TransferStatus::Pending, Some(2), 100),
+        ]);
+        assert_eq!(audit.pending_releases, 100.into());
+        assert_eq!(audit.undelivered, 500.into());
+        assert_eq!(audit.violations.len(), 1, "{:?}", audit.violations);
+        assert!(
+            audit.violations[0].starts_with("Releasing 500 wei"),
+            "{:?}",
+            audit.violations
+        );
+
+        // the burnt FBB are still owed
+        audit.check_solvency();
+        assert_eq!(audit.shortfall, U256::zero());
+        audit.token_sender_balance = 800.into();
+        audit.check_solvency();
+        assert_eq!(audit.shortfall, 100.into());
     }
 }
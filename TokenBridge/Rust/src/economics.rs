@@ -0,0 +1,62 @@
+//! The bridge's fee and minimum payment, mirroring 'TokenSender.sol'.
+
+use ethers::types::U256;
+use eyre::{ensure, Result};
+use template_support::{s_header, s_value};
+
+/// 'TokenSender' keeps 1/FEE_INVERSE of each payment as a fee.
+pub const FEE_INVERSE: u64 = 1000;
+
+/// 'TokenSender' rejects payments under 1000 gwei.
+pub const MIN_PAYMENT_WEI: u64 = 1_000_000_000_000;
+
+/// What a payment to 'TokenSender.bridgeSend' buys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quote {
+    /// The payment, in wei.
+    pub payment: U256,
+    /// The part of the payment 'TokenSender' keeps, in wei.
+    pub fee: U256,
+    /// The amount of FBB minted for the payment.
+    pub minted: U256,
+}
+
+impl Quote {
+    pub fn new(payment: U256) -> Self {
+        let fee = payment / FEE_INVERSE;
+        Self {
+            payment,
+            fee,
+            minted: payment - fee,
+        }
+    }
+
+    /// Fail (like 'TokenSender' would) if the payment is under the minimum.
+    pub fn check(&self) -> Result<()> {
+        ensure!(
+            self.payment >= MIN_PAYMENT_WEI.into(),
+            "Payment of {} wei is under the minimum of {MIN_PAYMENT_WEI} wei (1000 gwei)",
+            self.payment
+        );
+        Ok(())
+    }
+
+    pub fn print(&self) {
+        println!(
+            "{:>10} {} wei",
+            s_header!("payment"),
+            s_value!(self.payment)
+        );
+        println!(
+            "{:>10} {} wei (1/{FEE_INVERSE})",
+            s_header!("fee"),
+            s_value!(self.fee)
+        );
+        println!("{:>10} {} FBB", s_header!("minted"), s_value!(self.minted));
+        println!(
+            "{:>10} {} wei (1000 gwei)",
+            s_header!("minimum"),
+            s_value!(MIN_PAYMENT_WEI)
+        );
+    }
+}
@@ -1,21 +1,42 @@
 #![allow(non_snake_case)]
 
 mod cubist_gen;
+mod economics;
+
+use std::time::Instant;
 
 use crate::cubist_gen::*;
+use crate::economics::Quote;
 
+use clap::Parser;
 use ethers::providers::Middleware;
 use ethers::types::{H160, U256};
+use template_support::bridge::{exit_if_not_bridged, when_bridged, BridgeArgs, Pair};
+use template_support::profile::ProfileArgs;
+use template_support::relay::Wait;
 
-// The value to be sent in this simple test case, and the expected amount received
+// The value to be sent in this simple test case
 const SENT_AMOUNT: u64 = 1_000_000_000_000u64;
-const FEE_INVERSE: u64 = 1000;
-const RCVD_AMOUNT: u64 = SENT_AMOUNT - SENT_AMOUNT / FEE_INVERSE;
 
-#[tokio::main]
-async fn main() -> eyre::Result<()> {
+#[derive(Parser)]
+struct Args {
+    #[clap(flatten)]
+    bridge: BridgeArgs,
+    #[clap(flatten)]
+    profile: ProfileArgs,
+}
+
+fn main() -> eyre::Result<()> {
     color_eyre::install()?;
     tracing_subscriber::fmt::init();
+    let args = Args::parse();
+    // selecting the profile sets an environment variable, so it must happen
+    // before the runtime starts its worker threads
+    args.profile.select(env!("CARGO_MANIFEST_DIR"))?;
+    tokio::runtime::Runtime::new()?.block_on(run(args))
+}
+
+async fn run(args: Args) -> eyre::Result<()> {
     let cubist = cubist().await?;
 
     // check if we've already deployed the app, and in that case get the addresses
@@ -44,11 +65,40 @@ async fn main() -> eyre::Result<()> {
     };
 
     // wait for the bridge to be up
-    assert!(cubist.when_bridged(None).await);
-    println!("CUBIST bridged");
+    let toks_client = toks.client();
+    let e20b_client = e20b.client();
+    let (toks_target, e20b_target) = (TokenSender::target(), ERC20Bridged::target());
+    let (toks_shim, e20b_shim) = (toks.addr(e20b_target), e20b.addr(toks_target));
+    let (toks_client_ref, e20b_client_ref) = (&toks_client, &e20b_client);
+    let (toks_ref, e20b_ref) = (&toks, &e20b);
+    let pairs = || async move {
+        vec![
+            Pair::new(
+                "TokenSender",
+                toks_target,
+                e20b_target,
+                toks_shim,
+                e20b_client_ref,
+            )
+            .await
+            .bridged(toks_ref.is_bridged().await),
+            Pair::new(
+                "ERC20Bridged",
+                e20b_target,
+                toks_target,
+                e20b_shim,
+                toks_client_ref,
+            )
+            .await
+            .bridged(e20b_ref.is_bridged().await),
+        ]
+    };
+    let waited = exit_if_not_bridged(
+        when_bridged(cubist.when_bridged(None), args.bridge.timeout(), pairs).await,
+    )?;
+    println!("CUBIST bridged after {waited:?}");
 
     // get the starting balance for the TokenSender contract
-    let toks_client = toks.client();
     let toks_bal_init = toks_client.get_balance(toks.address(), None).await?;
 
     // get the starting ERC20 balance for the recipient address
@@ -57,6 +107,12 @@ async fn main() -> eyre::Result<()> {
 
     // bridge some gas tokens via TokenSender
     println!("Sending tokens");
+    let quote = Quote::new(SENT_AMOUNT.into());
+    quote.check()?;
+    quote.print();
+    let rcvd_amount = quote.minted;
+    let e20b_start_block = e20b_client.get_block_number().await?;
+    let sent_at = Instant::now();
     let mut call = toks.bridge_send(send_to);
     call.tx.set_value(SENT_AMOUNT);
     call.send().await?.await?;
@@ -68,41 +124,55 @@ async fn main() -> eyre::Result<()> {
         toks_bal_new
     );
 
-    // check token balance in ERC20 contract
+    // wait for the mint (a 'Transfer' from the zero address) in the ERC20 contract
     println!("Checking that tokens arrived on remote end");
-    let mut i = 15;
-    while i > 0 {
-        let bal = e20b.balance_of(send_to).call().await?;
-        if bal - e20b_st_bal_init == RCVD_AMOUNT.into() {
-            break;
-        }
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-        i -= 1;
-    }
+    let mint_filter = e20b
+        .transfer_filter()
+        .from_block(e20b_start_block)
+        .topic1(H160::zero())
+        .topic2(send_to)
+        .filter;
+    let minted = Wait::new(format!("FBB mint to {send_to:?}"))
+        .since(sent_at)
+        .for_event(&e20b_client, &mint_filter)
+        .await?;
+    println!("Tokens arrived after {:?}", minted.latency);
     assert_eq!(
         e20b.balance_of(send_to).call().await? - e20b_st_bal_init,
-        RCVD_AMOUNT.into()
+        rcvd_amount
     );
 
     let send_rando = H160::random();
     println!("Sending tokens back to lucky rando {send_rando:?}");
-    let call = e20b.bridge_send(send_rando, RCVD_AMOUNT.into());
+    let sent_at = Instant::now();
+    let call = e20b.bridge_send(send_rando, rcvd_amount);
     call.send().await?.await?;
 
-    let mut i = 15;
-    while i > 0 {
-        let bal = toks_client.get_balance(send_rando, None).await?;
-        if bal == RCVD_AMOUNT.into() {
-            break;
-        }
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-        i -= 1;
-    }
+    let released = Wait::new(format!("{send_rando:?} to receive {rcvd_amount} wei"))
+        .since(sent_at)
+        .for_state(
+            || async move { toks_client_ref.get_balance(send_rando, None).await },
+            |bal| *bal == rcvd_amount,
+        )
+        .await?;
+    println!("Tokens released after {:?}", released.latency);
 
     let tb = toks_client.get_balance(send_rando, None).await?;
     let tcb = toks_client.get_balance(toks.address(), None).await?;
-    assert_eq!(tcb, toks_bal_new - U256::from(RCVD_AMOUNT));
-    assert_eq!(tb, RCVD_AMOUNT.into());
+    assert_eq!(tcb, toks_bal_new - rcvd_amount);
+    assert_eq!(tb, rcvd_amount);
+
+    // collect the fees, which leaves exactly the collateral behind
+    let fees = toks.accrued_fees().call().await?;
+    assert!(fees >= quote.fee);
+    let collector = H160::random();
+    println!("Collecting {fees} wei of fees to {collector:?}");
+    toks.collect_fees(collector).send().await?.await?;
+    assert_eq!(toks_client.get_balance(collector, None).await?, fees);
+    assert_eq!(
+        toks_client.get_balance(toks.address(), None).await?,
+        toks.collateral().call().await?
+    );
 
     Ok(())
 }
@@ -0,0 +1,220 @@
+#![allow(non_snake_case)]
+
+#[path = "../src/cubist_gen.rs"]
+mod cubist_gen;
+
+use crate::cubist_gen::*;
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use std::process::Command;
+use template_support::{relay::Wait, testing::TestEnv};
+
+/// Run the template's 'cli' with `args` (in the test's Cubist environment),
+/// returning its stdout.
+fn cli(args: &[&str]) -> eyre::Result<String> {
+    let out = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .args(args)
+        .output()?;
+    eyre::ensure!(
+        out.status.success(),
+        "'cli {}' failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    Ok(String::from_utf8(out.stdout)?)
+}
+
+#[tokio::test]
+async fn deploys_and_transfers_tokens_cross_chain() -> eyre::Result<()> {
+    let _env = TestEnv::start(env!("CARGO_MANIFEST_DIR"))?;
+    let cubist = cubist().await?;
+
+    // Break the circular dependency between ERC20Bridged and TokenSender by first
+    // deploying ERC20Bridged shims only, then passing that address to TokenSender,
+    // then finally deploying ERC20Bridged with TokenSender's address.
+    let e20b = ERC20Bridged::deploy_shims().await?;
+    let toks = TokenSender::deploy(e20b.addr(TokenSender::target())).await?;
+    let e20b = ERC20Bridged::deploy((
+        "FooBarBaz".to_owned(),
+        "FBB".to_owned(),
+        toks.addr(ERC20Bridged::target()),
+    ))
+    .await?;
+
+    // wait for bridge
+    assert!(cubist.when_bridged(None).await);
+
+    let toks_client = toks.client();
+    let e20b_accounts = e20b.project().accounts().await?;
+    let toks_accounts = toks.project().accounts().await?;
+
+    // send tokens to account[0] on the ERC20Bridged chain
+    let amount = U256::from(2_000_000_000_000u64);
+    let actual_amount = amount - amount / 1000;
+    let e20b_acc0 = e20b_accounts[0];
+    let e20b_acc0_bal = e20b.balance_of(e20b_acc0).call().await?;
+    let mut call = toks.bridge_send(e20b_acc0);
+    call.tx.set_value(amount);
+    call.send().await?.await?;
+
+    // TokenSender holds the payment
+    assert_eq!(amount, toks_client.get_balance(toks.address(), None).await?);
+
+    // make sure the tokens (minus the fee) are received on the other side
+    let e20b_ref = &e20b;
+    Wait::new(format!("{actual_amount} FBB to be minted"))
+        .for_state(
+            || async move { e20b_ref.balance_of(e20b_acc0).call().await },
+            |bal| *bal == e20b_acc0_bal + actual_amount,
+        )
+        .await?;
+
+    // transfer half the tokens to account[1]
+    let e20b_acc1 = e20b_accounts[1];
+    let e20b_acc1_bal = e20b.balance_of(e20b_acc1).call().await?;
+    e20b.transfer(e20b_acc1, actual_amount / 2)
+        .send()
+        .await?
+        .await?;
+    assert_eq!(
+        e20b_acc0_bal + actual_amount / 2,
+        e20b.balance_of(e20b_acc0).call().await?
+    );
+    assert_eq!(
+        e20b_acc1_bal + actual_amount / 2,
+        e20b.balance_of(e20b_acc1).call().await?
+    );
+
+    // bridge all of account[0]'s tokens back to account[1] on the TokenSender chain
+    let toks_acc1 = toks_accounts[1];
+    let toks_acc1_bal = toks_client.get_balance(toks_acc1, None).await?;
+    let e20b_acc0_bal = e20b.balance_of(e20b_acc0).call().await?;
+    e20b.bridge_send(toks_acc1, e20b_acc0_bal)
+        .send()
+        .await?
+        .await?;
+    assert_eq!(U256::zero(), e20b.balance_of(e20b_acc0).call().await?);
+
+    let toks_client_ref = &toks_client;
+    Wait::new(format!("{e20b_acc0_bal} wei to be released"))
+        .for_state(
+            || async move { toks_client_ref.get_balance(toks_acc1, None).await },
+            |bal| *bal == toks_acc1_bal + e20b_acc0_bal,
+        )
+        .await?;
+
+    assert_eq!(
+        amount - e20b_acc0_bal,
+        toks_client.get_balance(toks.address(), None).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn collects_fees_without_touching_collateral() -> eyre::Result<()> {
+    let _env = TestEnv::start(env!("CARGO_MANIFEST_DIR"))?;
+    let cubist = cubist().await?;
+
+    let e20b = ERC20Bridged::deploy_shims().await?;
+    let toks = TokenSender::deploy(e20b.addr(TokenSender::target())).await?;
+    let e20b = ERC20Bridged::deploy((
+        "FooBarBaz".to_owned(),
+        "FBB".to_owned(),
+        toks.addr(ERC20Bridged::target()),
+    ))
+    .await?;
+    assert!(cubist.when_bridged(None).await);
+
+    let toks_client = toks.client();
+    let e20b_accounts = e20b.project().accounts().await?;
+    let toks_accounts = toks.project().accounts().await?;
+
+    // two payments, so that fees accumulate
+    let e20b_acc0 = e20b_accounts[0];
+    let amounts = [
+        U256::from(2_000_000_000_000u64),
+        U256::from(3_000_000_000_000u64),
+    ];
+    for amount in amounts {
+        let mut call = toks.bridge_send(e20b_acc0);
+        call.tx.set_value(amount);
+        call.send().await?.await?;
+    }
+    let paid = amounts[0] + amounts[1];
+    let fees = amounts[0] / 1000 + amounts[1] / 1000;
+    let minted = paid - fees;
+    assert_eq!(fees, toks.accrued_fees().call().await?);
+    assert_eq!(minted, toks.collateral().call().await?);
+
+    let e20b_ref = &e20b;
+    Wait::new(format!("{minted} FBB to be minted"))
+        .for_state(
+            || async move { e20b_ref.total_supply().call().await },
+            |supply| *supply == minted,
+        )
+        .await?;
+
+    // only the owner can collect fees
+    let collector = Address::random();
+    assert!(toks
+        .collect_fees(collector)
+        .from(toks_accounts[1])
+        .send()
+        .await
+        .is_err());
+
+    // the owner collects exactly the fees, leaving the collateral behind
+    toks.collect_fees(collector).send().await?.await?;
+    assert_eq!(fees, toks_client.get_balance(collector, None).await?);
+    assert_eq!(U256::zero(), toks.accrued_fees().call().await?);
+    let supply = e20b.total_supply().call().await?;
+    assert_eq!(supply, toks_client.get_balance(toks.address(), None).await?);
+    assert_eq!(supply, toks.collateral().call().await?);
+
+    // and cannot collect anything more
+    assert!(toks.collect_fees(collector).send().await.is_err());
+
+    // the bridge is still solvent: all FBB in circulation can be redeemed
+    let toks_acc1 = toks_accounts[1];
+    let toks_acc1_bal = toks_client.get_balance(toks_acc1, None).await?;
+    e20b.bridge_send(toks_acc1, supply).send().await?.await?;
+    assert_eq!(U256::zero(), e20b.total_supply().call().await?);
+
+    let toks_client_ref = &toks_client;
+    Wait::new(format!("{supply} wei to be released"))
+        .for_state(
+            || async move { toks_client_ref.get_balance(toks_acc1, None).await },
+            |bal| *bal == toks_acc1_bal + supply,
+        )
+        .await?;
+    assert_eq!(
+        U256::zero(),
+        toks_client.get_balance(toks.address(), None).await?
+    );
+    assert_eq!(U256::zero(), toks.collateral().call().await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_redeploys_shims_deployed_on_their_own() -> eyre::Result<()> {
+    let _env = TestEnv::start(env!("CARGO_MANIFEST_DIR"))?;
+
+    // a deployment interrupted right after deploying the ERC20Bridged shims
+    let e20b = ERC20Bridged::deploy_shims().await?;
+    let lost_shim = format!("{:?}", e20b.addr(TokenSender::target()));
+
+    // a new process cannot reload those shims, so there is nothing to keep
+    let plan = cli(&["deploy", "--plan"])?;
+    assert!(!plan.contains(&lost_shim), "{plan}");
+    assert!(!plan.contains("Keep"), "{plan}");
+
+    // resuming deploys everything, with new shims
+    cli(&["deploy", "--resume"])?;
+    let plan = cli(&["deploy", "--plan"])?;
+    assert!(plan.contains("Already deployed"), "{plan}");
+    assert!(!plan.contains(&lost_shim), "{plan}");
+
+    Ok(())
+}
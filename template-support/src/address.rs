@@ -0,0 +1,388 @@
+//! Resolving user-supplied addresses.
+//!
+//! Wherever a CLI expects an address, a [`Resolver`] accepts:
+//!
+//! - a hex address (`0x...`); mixed-case addresses must have a valid EIP-55 checksum
+//! - an account index (`1`), referring to the local accounts on the default chain
+//! - `chain:index` (`polygon:2`), referring to the local accounts on any chain
+//! - a contract name (`TokenSender`) or shim name (`shim:ERC20Bridged`)
+//! - an alias from the project's [`AddressBook`]
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use ethers::{types::Address, utils::to_checksum};
+use eyre::{bail, eyre, Context, Result};
+
+/// Name of the address book file, kept next to `cubist-config.json`.
+pub const ADDRESS_BOOK_FILE: &str = "address-book.json";
+
+/// Parse a hex address, rejecting mixed-case addresses with a bad EIP-55 checksum.
+pub fn parse_address(s: &str) -> Result<Address> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    // `Address::from_str` only strips a lowercase prefix (and would strip a second one)
+    let addr = Address::from_str(&format!("0x{hex}")).context(format!("Invalid address: {s}"))?;
+    let mixed_case =
+        hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+    let checksummed = to_checksum(&addr, None);
+    if mixed_case && hex != &checksummed[2..] {
+        bail!("Invalid EIP-55 checksum in address {s}; did you mean {checksummed}?");
+    }
+    Ok(addr)
+}
+
+/// User-defined aliases for addresses, stored as a JSON object in a project-local file.
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    path: PathBuf,
+    entries: BTreeMap<String, Address>,
+}
+
+impl AddressBook {
+    /// Load the address book from `path`; a missing file is an empty address book.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut book = Self {
+            path,
+            entries: BTreeMap::new(),
+        };
+        if !book.path.exists() {
+            return Ok(book);
+        }
+        let ctx = || format!("Reading address book {}", book.path.display());
+        let raw: BTreeMap<String, String> =
+            serde_json::from_str(&fs::read_to_string(&book.path).with_context(ctx)?)
+                .with_context(ctx)?;
+        for (alias, addr) in raw {
+            check_alias(&alias).with_context(ctx)?;
+            let addr = parse_address(&addr).with_context(ctx)?;
+            book.entries.insert(alias, addr);
+        }
+        Ok(book)
+    }
+
+    /// Write the address book back to the file it was loaded from.
+    pub fn save(&self) -> Result<()> {
+        let raw: BTreeMap<&String, String> = self
+            .entries
+            .iter()
+            .map(|(alias, addr)| (alias, to_checksum(addr, None)))
+            .collect();
+        fs::write(&self.path, serde_json::to_string_pretty(&raw)? + "\n")
+            .context(format!("Writing address book {}", self.path.display()))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add (or replace) an alias.
+    pub fn insert(&mut self, alias: &str, addr: Address) -> Result<()> {
+        check_alias(alias)?;
+        self.entries.insert(alias.to_owned(), addr);
+        Ok(())
+    }
+
+    /// Remove an alias, returning its address (if it existed).
+    pub fn remove(&mut self, alias: &str) -> Option<Address> {
+        self.entries.remove(alias)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Address)> {
+        self.entries.iter()
+    }
+}
+
+/// Aliases must not be confused with any other way of specifying an address.
+fn check_alias(alias: &str) -> Result<()> {
+    if alias.is_empty()
+        || alias.starts_with("0x")
+        || alias.starts_with("0X")
+        || alias.parse::<usize>().is_ok()
+        || alias.contains(':')
+        || alias.contains(char::is_whitespace)
+    {
+        bail!("Invalid alias '{alias}': aliases must not be empty, look like a hex address or an index, or contain ':' or whitespace");
+    }
+    Ok(())
+}
+
+/// Resolves user-supplied addresses; see the [module docs](self).
+#[derive(Debug, Default)]
+pub struct Resolver {
+    default_chain: Option<String>,
+    accounts: Vec<(String, Vec<Address>)>,
+    contracts: Vec<(String, Address)>,
+    book: AddressBook,
+}
+
+impl Resolver {
+    /// Create a resolver; bare indices refer to accounts on `default_chain`
+    /// (and are rejected as ambiguous if there is none).
+    pub fn new(default_chain: Option<String>) -> Self {
+        Self {
+            default_chain,
+            ..Default::default()
+        }
+    }
+
+    /// Add the local accounts on `chain`, in index order.
+    pub fn add_accounts(&mut self, chain: impl Into<String>, accounts: Vec<Address>) {
+        self.accounts.push((chain.into(), accounts));
+    }
+
+    /// Add a named contract (or shim).
+    pub fn add_contract(&mut self, name: impl Into<String>, addr: Address) {
+        self.contracts.push((name.into(), addr));
+    }
+
+    /// Use the aliases in `book`.
+    pub fn set_address_book(&mut self, book: AddressBook) {
+        self.book = book;
+    }
+
+    /// Resolve `s` to an address.
+    pub fn resolve(&self, s: &str) -> Result<Address> {
+        let s = s.trim();
+        if s.starts_with("0x") || s.starts_with("0X") {
+            return parse_address(s);
+        }
+        if let Ok(i) = s.parse::<usize>() {
+            let chain = self.default_chain.as_deref().ok_or_else(|| {
+                eyre!(
+                    "Account index '{s}' is ambiguous here; use 'chain:{s}' with one of: {}",
+                    self.chain_names().join(", ")
+                )
+            })?;
+            return self.account(chain, i);
+        }
+        if let Some((chain, index)) = s.split_once(':') {
+            if let Ok(i) = index.parse::<usize>() {
+                return self.account(chain, i);
+            }
+        }
+
+        let mut candidates: Vec<(String, Address)> = vec![];
+        for (name, addr) in &self.contracts {
+            if name.eq_ignore_ascii_case(s) {
+                candidates.push((format!("contract '{name}'"), *addr));
+            }
+        }
+        for (alias, addr) in self.book.iter() {
+            if alias.eq_ignore_ascii_case(s) {
+                candidates.push((format!("alias '{alias}'"), *addr));
+            }
+        }
+        match candidates.as_slice() {
+            [] => bail!(
+                "Unknown address '{s}'; expected a hex address, an account index, 'chain:index' \
+                 (chains: {}), a contract name ({}) or an alias from {}",
+                self.chain_names().join(", "),
+                self.contracts
+                    .iter()
+                    .map(|c| c.0.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.book.path().display(),
+            ),
+            [(_, first), rest @ ..] if rest.iter().all(|c| c.1 == *first) => Ok(*first),
+            _ => bail!(
+                "'{s}' is ambiguous; it could be {}",
+                candidates
+                    .iter()
+                    .map(|(what, addr)| format!("{what} ({addr:?})"))
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ),
+        }
+    }
+
+    /// Resolve `s` to one of the local accounts on `chain`, which can sign
+    /// transactions there; bare indices refer to `chain` regardless of the
+    /// default chain.
+    pub fn resolve_signer(&self, chain: &str, s: &str) -> Result<Address> {
+        if let Ok(i) = s.trim().parse::<usize>() {
+            return self.account(chain, i);
+        }
+        let addr = self.resolve(s)?;
+        let (chain, accounts) = self.chain_accounts(chain)?;
+        if !accounts.contains(&addr) {
+            bail!(
+                "{addr:?} cannot sign on '{chain}'; expected one of its local accounts: {}",
+                accounts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, a)| format!("{i} ({a:?})"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        Ok(addr)
+    }
+
+    fn chain_accounts(&self, chain: &str) -> Result<(&str, &[Address])> {
+        self.accounts
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(chain))
+            .map(|(c, accounts)| (c.as_str(), accounts.as_slice()))
+            .ok_or_else(|| {
+                eyre!(
+                    "Unknown chain '{chain}'; expected one of: {}",
+                    self.chain_names().join(", ")
+                )
+            })
+    }
+
+    pub(crate) fn account(&self, chain: &str, i: usize) -> Result<Address> {
+        let (chain, accounts) = self.chain_accounts(chain)?;
+        match accounts.len() {
+            0 => bail!("There are no local accounts on '{chain}'"),
+            n => accounts.get(i).copied().ok_or_else(|| {
+                eyre!(
+                    "Account index {i} out of bounds; '{chain}' has {n} accounts (0 to {})",
+                    n - 1
+                )
+            }),
+        }
+    }
+
+    fn chain_names(&self) -> Vec<&str> {
+        self.accounts.iter().map(|(c, _)| c.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // from the EIP-55 test vectors
+    const CHECKSUMMED: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+    fn addr(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    fn err(res: Result<Address>) -> String {
+        format!("{:#}", res.expect_err("expected an error"))
+    }
+
+    fn resolver() -> Resolver {
+        let mut r = Resolver::new(Some("ethereum".into()));
+        r.add_accounts("ethereum", vec![addr(1), addr(2)]);
+        r.add_accounts("polygon", vec![addr(3)]);
+        r.add_contract("TokenSender", addr(10));
+        r.add_contract("shim:ERC20Bridged", addr(11));
+        r
+    }
+
+    #[test]
+    fn checks_eip55_checksums() {
+        let expected = Address::from_str(&CHECKSUMMED[2..]).unwrap();
+        assert_eq!(parse_address(CHECKSUMMED).unwrap(), expected);
+        assert_eq!(
+            parse_address(&CHECKSUMMED.to_lowercase()).unwrap(),
+            expected
+        );
+        let upper = format!("0x{}", CHECKSUMMED[2..].to_uppercase());
+        assert_eq!(parse_address(&upper).unwrap(), expected);
+        assert_eq!(
+            parse_address(&upper.replacen("0x", "0X", 1)).unwrap(),
+            expected
+        );
+
+        let bad = CHECKSUMMED.replacen("aA", "Aa", 1);
+        assert!(err(parse_address(&bad)).contains(&format!("did you mean {CHECKSUMMED}")));
+    }
+
+    #[test]
+    fn rejects_malformed_addresses() {
+        for s in [
+            "0x",
+            "0x1234",
+            "0x0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed",
+            "0xzz",
+        ] {
+            assert!(err(parse_address(s)).starts_with("Invalid address"), "{s}");
+        }
+    }
+
+    #[test]
+    fn resolves_indices() {
+        let r = resolver();
+        assert_eq!(r.resolve("1").unwrap(), addr(2));
+        assert_eq!(r.resolve("polygon:0").unwrap(), addr(3));
+        assert_eq!(r.resolve("Polygon:0").unwrap(), addr(3));
+        assert!(err(r.resolve("2")).contains("'ethereum' has 2 accounts (0 to 1)"));
+        assert!(err(r.resolve("polygon:1")).contains("'polygon' has 1 accounts (0 to 0)"));
+        assert!(err(r.resolve("avalanche:0")).contains("Unknown chain 'avalanche'"));
+
+        let mut r = Resolver::new(None);
+        r.add_accounts("ethereum", vec![addr(1)]);
+        assert!(err(r.resolve("0")).contains("'0' is ambiguous"));
+        assert_eq!(r.resolve("ethereum:0").unwrap(), addr(1));
+    }
+
+    #[test]
+    fn rejects_indices_without_accounts() {
+        let mut r = Resolver::new(Some("ethereum".into()));
+        r.add_accounts("ethereum", vec![]);
+        assert!(err(r.resolve("0")).contains("no local accounts on 'ethereum'"));
+        assert!(err(r.resolve_signer("ethereum", "0")).contains("no local accounts"));
+    }
+
+    #[test]
+    fn resolves_contracts_and_aliases() {
+        let mut r = resolver();
+        let mut book = AddressBook::load("/nonexistent/address-book.json").unwrap();
+        book.insert("alice", addr(20)).unwrap();
+        r.set_address_book(book);
+        assert_eq!(r.resolve("TokenSender").unwrap(), addr(10));
+        assert_eq!(r.resolve("tokensender").unwrap(), addr(10));
+        assert_eq!(r.resolve("shim:ERC20Bridged").unwrap(), addr(11));
+        assert_eq!(r.resolve("Alice").unwrap(), addr(20));
+        assert!(err(r.resolve("bob")).starts_with("Unknown address 'bob'"));
+    }
+
+    #[test]
+    fn reports_ambiguous_names() {
+        let mut r = resolver();
+        let mut book = AddressBook::load("/nonexistent/address-book.json").unwrap();
+        // an alias for the same address is harmless
+        book.insert("tokensender", addr(10)).unwrap();
+        book.insert("erc20", addr(30)).unwrap();
+        r.set_address_book(book);
+        r.add_contract("ERC20", addr(31));
+        assert_eq!(r.resolve("TokenSender").unwrap(), addr(10));
+        let e = err(r.resolve("erc20"));
+        assert!(e.contains("'erc20' is ambiguous"), "{e}");
+        assert!(
+            e.contains("contract 'ERC20'") && e.contains("alias 'erc20'"),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn rejects_aliases_that_look_like_addresses() {
+        let mut book = AddressBook::default();
+        for alias in ["", "0xabc", "0Xabc", "12", "polygon:1", "two words"] {
+            assert!(book.insert(alias, addr(1)).is_err(), "{alias}");
+        }
+        assert!(book.insert("alice", addr(1)).is_ok());
+    }
+
+    #[test]
+    fn signers_must_be_local_accounts() {
+        let r = resolver();
+        // bare indices refer to the signing chain, not the default one
+        assert_eq!(r.resolve_signer("polygon", "0").unwrap(), addr(3));
+        assert_eq!(r.resolve_signer("ethereum", "ethereum:1").unwrap(), addr(2));
+        assert!(err(r.resolve_signer("polygon", "ethereum:0")).contains("cannot sign on 'polygon'"));
+    }
+}
@@ -0,0 +1,337 @@
+//! Parsing token amounts.
+//!
+//! Amounts given on the command line are parsed into [`U256`] base units
+//! (wei, or the smallest FBB unit).  Accepted forms:
+//!
+//! - plain integers: `1000000000000`
+//! - hex: `0xe8d4a51000`
+//! - scientific notation: `1e12`, `2.5e9`
+//! - decimals with a unit suffix: `1.5ether`, `20gwei`, `3fbb`, `7 wei`
+//!
+//! Amounts that don't fit in a `U256`, or that would need a fraction of a
+//! base unit (e.g., `1.5wei`), are rejected.
+
+use std::fmt;
+
+use ethers::types::U256;
+use serde::{
+    de::{self, Visitor},
+    Deserializer,
+};
+use thiserror::Error;
+
+/// Units accepted as suffixes, with their number of decimals.
+pub const UNITS: &[(&str, u32)] = &[
+    ("wei", 0),
+    ("kwei", 3),
+    ("mwei", 6),
+    ("gwei", 9),
+    ("szabo", 12),
+    ("finney", 15),
+    ("ether", 18),
+    ("eth", 18),
+    // FBB is a standard 18-decimal ERC20 token
+    ("fbb", 18),
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AmountError {
+    #[error("Empty amount")]
+    Empty,
+    #[error("Invalid amount '{0}'; expected e.g. '1000', '0x3e8', '1e18', '1.5ether' or '20gwei'")]
+    Invalid(String),
+    #[error("Unknown unit '{unit}'; expected one of: {}", UNITS.iter().map(|u| u.0).collect::<Vec<_>>().join(", "))]
+    UnknownUnit { unit: String },
+    #[error("Amount '{0}' does not fit in 256 bits")]
+    Overflow(String),
+    #[error("Amount '{0}' is not a whole number of base units")]
+    Fractional(String),
+}
+
+/// Parse an amount (see the [module docs](self)); usable as a clap `value_parser`.
+pub fn parse_amount(s: &str) -> Result<U256, AmountError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(AmountError::Empty);
+    }
+    let invalid = || AmountError::Invalid(s.to_owned());
+    let overflow = || AmountError::Overflow(s.to_owned());
+
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        // leading zeros would count against the 64 digits `from_str_radix` accepts
+        let hex = hex.trim_start_matches('0');
+        if hex.len() > 64 {
+            return Err(overflow());
+        }
+        if hex.is_empty() {
+            return Ok(U256::zero());
+        }
+        return U256::from_str_radix(hex, 16).map_err(|_| invalid());
+    }
+
+    let (int, rest) = split_digits(s);
+    let (frac, rest) = match rest.strip_prefix('.') {
+        Some(rest) => split_digits(rest),
+        None => ("", rest),
+    };
+    if int.is_empty() && frac.is_empty() {
+        return Err(invalid());
+    }
+    let (exp, rest) = split_exponent(rest).ok_or_else(invalid)?;
+    let unit = rest.trim();
+    let decimals = if unit.is_empty() {
+        0
+    } else {
+        UNITS
+            .iter()
+            .find(|u| u.0.eq_ignore_ascii_case(unit))
+            .map(|u| u.1)
+            .ok_or_else(|| AmountError::UnknownUnit {
+                unit: unit.to_owned(),
+            })?
+    };
+
+    // value = int.frac * 10^(exp + decimals) = (int ++ frac) * 10^(exp + decimals - len(frac))
+    let frac = frac.trim_end_matches('0');
+    let digits = format!("{int}{frac}");
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(U256::zero());
+    }
+    // trailing zeros make up for a negative exponent (e.g., "150e-1")
+    let significant = digits.trim_end_matches('0');
+    let zeros = (digits.len() - significant.len()) as i64;
+    let mut scale = exp + i64::from(decimals) - frac.len() as i64;
+    let digits = if scale < 0 {
+        let shift = zeros.min(-scale);
+        scale += shift;
+        &digits[..digits.len() - shift as usize]
+    } else {
+        digits
+    };
+    if scale < 0 {
+        return Err(AmountError::Fractional(s.to_owned()));
+    }
+    let mantissa = U256::from_dec_str(digits).map_err(|_| overflow())?;
+    let scale = U256::from(10)
+        .checked_pow(U256::from(scale))
+        .ok_or_else(overflow)?;
+    mantissa.checked_mul(scale).ok_or_else(overflow)
+}
+
+/// Split `s` into its leading ASCII digits and the rest.
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// Parse an optional exponent (e.g., "e18", "E-3") at the start of `s`.
+/// An 'e' not followed by digits is taken to be the start of a unit (e.g., "ether").
+fn split_exponent(s: &str) -> Option<(i64, &str)> {
+    let Some(rest) = s.strip_prefix(['e', 'E']) else {
+        return Some((0, s));
+    };
+    let (sign, rest) = match rest.strip_prefix(['+', '-']) {
+        Some(r) => (&rest[..1], r),
+        None => ("", rest),
+    };
+    let (digits, rest) = split_digits(rest);
+    if digits.is_empty() {
+        return if sign.is_empty() { Some((0, s)) } else { None };
+    }
+    // anything this large overflows a U256 anyway
+    let exp: i64 = digits.parse().unwrap_or(i64::MAX / 2);
+    Some((if sign == "-" { -exp } else { exp }, rest))
+}
+
+/// Deserialize an amount given as a string (e.g., "30gwei", see
+/// [`parse_amount`]) or a non-negative integer.
+///
+/// JSON and YAML numbers above `u64::MAX`, or with a fraction or exponent, are
+/// read as floats and would lose precision, so they are rejected: such amounts
+/// must be given as strings (e.g., "1e18").
+pub fn de_amount<'de, D: Deserializer<'de>>(de: D) -> Result<U256, D::Error> {
+    struct AmountVisitor;
+
+    impl Visitor<'_> for AmountVisitor {
+        type Value = U256;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an amount such as 1000, \"1e18\" or \"1.5ether\"")
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<U256, E> {
+            parse_amount(s).map_err(E::custom)
+        }
+
+        fn visit_u64<E: de::Error>(self, n: u64) -> Result<U256, E> {
+            Ok(n.into())
+        }
+
+        fn visit_i64<E: de::Error>(self, n: i64) -> Result<U256, E> {
+            u64::try_from(n)
+                .map(U256::from)
+                .map_err(|_| E::custom(format!("Amount '{n}' is negative")))
+        }
+
+        fn visit_f64<E: de::Error>(self, n: f64) -> Result<U256, E> {
+            Err(E::custom(format!(
+                "Amount '{n}' was read as a floating-point number and may be inexact; give \
+                 amounts above {}, or with a fraction or exponent, as strings (e.g., \"1e18\")",
+                u64::MAX
+            )))
+        }
+    }
+
+    de.deserialize_any(AmountVisitor)
+}
+
+/// Deserialize an optional amount (see [`de_amount`]); use with
+/// `#[serde(default)]`.
+pub fn de_opt_amount<'de, D: Deserializer<'de>>(de: D) -> Result<Option<U256>, D::Error> {
+    de_amount(de).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(s: &str) -> U256 {
+        parse_amount(s).unwrap_or_else(|e| panic!("'{s}': {e}"))
+    }
+
+    #[test]
+    fn parses_integers_and_hex() {
+        assert_eq!(ok("1000000000000"), U256::from(1_000_000_000_000u64));
+        assert_eq!(ok(" 42 "), U256::from(42));
+        assert_eq!(ok("0xe8d4a51000"), U256::from(1_000_000_000_000u64));
+        assert_eq!(ok("0X3E8"), U256::from(1000));
+        assert_eq!(ok(&format!("0x{}", "f".repeat(64))), U256::MAX);
+        assert_eq!(ok(&format!("0x00{}", "f".repeat(64))), U256::MAX);
+    }
+
+    #[test]
+    fn parses_scientific_notation() {
+        assert_eq!(ok("1e12"), U256::exp10(12));
+        assert_eq!(ok("1.5e3"), U256::from(1500));
+        assert_eq!(ok("2.5E9"), U256::from(2_500_000_000u64));
+        assert_eq!(ok("150e-1"), U256::from(15));
+        assert_eq!(ok("1e+2"), U256::from(100));
+    }
+
+    #[test]
+    fn parses_units() {
+        let ether = U256::exp10(18);
+        assert_eq!(ok("1.5ether"), ether * 3 / 2);
+        assert_eq!(ok("20gwei"), U256::from(20_000_000_000u64));
+        assert_eq!(ok("3fbb"), ether * 3);
+        assert_eq!(ok("7 wei"), U256::from(7));
+        assert_eq!(ok("1ETH"), ether);
+        assert_eq!(ok("1e3gwei"), U256::exp10(12));
+        assert_eq!(ok(".5ether"), ether / 2);
+        assert_eq!(ok("2.50gwei"), U256::from(2_500_000_000u64));
+        assert_eq!(ok("0.000ether"), U256::zero());
+    }
+
+    #[test]
+    fn rejects_fractions_of_base_units() {
+        for s in [
+            "1.5",
+            "1.5wei",
+            "15e-1",
+            "1.0000000001gwei",
+            "0.0000000000000000001ether",
+        ] {
+            assert_eq!(parse_amount(s), Err(AmountError::Fractional(s.into())));
+        }
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        // U256::MAX is 115792089237316195423570985008687907853269984665640564039457584007913129639935
+        let max = U256::MAX.to_string();
+        assert_eq!(ok(&max), U256::MAX);
+        let above = format!("{max}0");
+        let too_many_hex_digits = format!("0x1{}", "0".repeat(64));
+        for s in [
+            "115792089237316195423570985008687907853269984665640564039457584007913129639936",
+            &above,
+            &too_many_hex_digits,
+            "1e78",
+            "1e100000000000000000000",
+            "1e60ether",
+        ] {
+            assert_eq!(parse_amount(s), Err(AmountError::Overflow(s.into())), "{s}");
+        }
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(parse_amount(""), Err(AmountError::Empty));
+        assert_eq!(parse_amount("  "), Err(AmountError::Empty));
+        for s in ["abc", "0x", "0xzz", "-1", ".", "1e-", "ether", "1.5.5"] {
+            assert!(
+                matches!(
+                    parse_amount(s),
+                    Err(AmountError::Invalid(_) | AmountError::UnknownUnit { .. })
+                ),
+                "{s}: {:?}",
+                parse_amount(s)
+            );
+        }
+        assert_eq!(
+            parse_amount("1 dollars"),
+            Err(AmountError::UnknownUnit {
+                unit: "dollars".into()
+            })
+        );
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Op {
+        #[serde(deserialize_with = "de_amount")]
+        amount: U256,
+    }
+
+    fn from_json(json: &str) -> Result<U256, String> {
+        serde_json::from_str::<Op>(json)
+            .map(|op| op.amount)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn deserializes_strings_and_integers() {
+        assert_eq!(from_json(r#"{"amount": 42}"#), Ok(U256::from(42)));
+        assert_eq!(
+            from_json(r#"{"amount": 18446744073709551615}"#),
+            Ok(U256::from(u64::MAX))
+        );
+        assert_eq!(from_json(r#"{"amount": "1e18"}"#), Ok(U256::exp10(18)));
+        assert_eq!(
+            from_json(r#"{"amount": "1.5ether"}"#),
+            Ok(U256::exp10(18) * 3 / 2)
+        );
+        let op: Op = serde_yaml::from_str("amount: 7").unwrap();
+        assert_eq!(op.amount, U256::from(7));
+    }
+
+    #[test]
+    fn asks_for_strings_instead_of_inexact_numbers() {
+        for json in [
+            r#"{"amount": 1e18}"#,
+            r#"{"amount": 1.5}"#,
+            r#"{"amount": 18446744073709551616}"#,
+        ] {
+            let err = from_json(json).unwrap_err();
+            assert!(err.contains("floating-point number"), "{json}: {err}");
+            assert!(err.contains("as strings"), "{json}: {err}");
+        }
+        let err = from_json(r#"{"amount": -1}"#).unwrap_err();
+        assert!(err.contains("Amount '-1' is negative"), "{err}");
+        let err = from_json(r#"{"amount": "1 dollars"}"#).unwrap_err();
+        assert!(err.contains("Unknown unit 'dollars'"), "{err}");
+    }
+}
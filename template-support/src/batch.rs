@@ -0,0 +1,385 @@
+//! Running a batch of operations from a file (`batch <file>`).
+//!
+//! A batch file is a YAML or JSON list of operations, each naming one of the
+//! template's commands and its arguments:
+//!
+//! ```yaml
+//! - command: buy
+//!   amount: 1.5ether
+//!   receiver: alice
+//! - command: sell
+//!   amount: 1000
+//!   sender: polygon:1
+//! ```
+//!
+//! Each template lists its operations as an enum tagged by `command` (see
+//! [`Operation`]), so unknown commands and arguments are rejected when the file
+//! is loaded, before anything is sent.
+//!
+//! `sender` selects the signing account like `--from` does.  Operations
+//! signed by the same account on the same chain never overlap: they run in
+//! file order, each waiting for the previous one to be mined, so each is sent
+//! with the account's pending nonce.  With `--parallel N`, up to N operations
+//! of different accounts run at once.
+//!
+//! A failed operation does not stop the others unless `--fail-fast` is given,
+//! in which case the operations not started yet are skipped.  Either way,
+//! every operation gets a line in the report, which is printed like listings
+//! (`--output`).
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::Args;
+use ethers::types::{Address, H256};
+use eyre::{bail, eyre, Context, Result};
+use futures::{stream, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    output::{print_records, OutputFormat},
+    receipt::{Receipt, TxStatus},
+    s_action, s_error, s_header, s_value,
+    table::{Column, Table},
+};
+
+/// An entry of a batch file: an enum with a variant per command, deserialized
+/// with `#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]`.
+/// Amounts use [`de_amount`](crate::amount::de_amount).
+pub trait Operation: DeserializeOwned {
+    /// The name of the command, e.g., `buy`.
+    fn command(&self) -> &'static str;
+}
+
+/// Command-line arguments of `batch`.
+#[derive(Debug, Clone, Args)]
+pub struct BatchArgs {
+    /// YAML or JSON file listing the operations; each has a 'command' and, depending
+    /// on the command, an 'amount', a 'receiver' and a 'sender' (like '--from').
+    pub file: PathBuf,
+    /// How many operations may run at once.  Operations signed by the same account
+    /// always run one after the other, in file order.
+    #[clap(long, default_value = "1", value_parser = clap::value_parser!(u16).range(1..))]
+    pub parallel: u16,
+    /// Skip the remaining operations after the first failure.
+    #[clap(long)]
+    pub fail_fast: bool,
+}
+
+/// The chain and the address of the account signing an operation.
+pub type Lane = (String, Address);
+
+impl BatchArgs {
+    /// Load the operations from the batch file.
+    pub fn load<O: Operation>(&self) -> Result<Vec<O>> {
+        load(&self.file)
+    }
+
+    /// Run `ops` with `exec`.  `jobs` has an entry for each operation: the
+    /// account signing it and whatever else `exec` needs to run it, or the
+    /// reason it cannot run.  Returns the result of each operation, in order.
+    pub async fn run<'a, O, J, F, Fut>(
+        &self,
+        ops: &'a [O],
+        jobs: Vec<Result<(Lane, J)>>,
+        exec: F,
+    ) -> Vec<OpResult>
+    where
+        O: Operation,
+        F: Fn(&'a O, J) -> Fut,
+        Fut: Future<Output = Result<Receipt>>,
+    {
+        let mut locks: HashMap<Lane, Mutex<()>> = HashMap::new();
+        for (lane, _) in jobs.iter().flatten() {
+            locks.entry(lane.clone()).or_default();
+        }
+        let stop = AtomicBool::new(false);
+        let (locks, stop, exec) = (&locks, &stop, &exec);
+
+        let jobs = ops
+            .iter()
+            .zip(jobs)
+            .enumerate()
+            .map(|(i, (op, job))| async move {
+                let mut result = OpResult::new(i, op);
+                let ((chain, sender), job) = match job {
+                    Ok(job) => job,
+                    Err(_) if stop.load(Ordering::SeqCst) => return result,
+                    Err(e) => return result.fail(&e, self.fail_fast, stop),
+                };
+                result.chain = Some(chain.clone());
+                result.sender = Some(sender);
+
+                // operations of the same account wait for each other, in file order
+                let _turn = locks[&(chain, sender)].lock().await;
+                if stop.load(Ordering::SeqCst) {
+                    result.status = OpStatus::Skipped;
+                    return result;
+                }
+                eprintln!(
+                    "{} operation {} ({})",
+                    s_action!("Running"),
+                    s_value!(result.index),
+                    s_value!(&result.command)
+                );
+                match exec(op, job).await {
+                    Ok(receipt) => {
+                        result.tx = Some(receipt.tx);
+                        result.block = receipt.block;
+                        if receipt.status == TxStatus::Reverted {
+                            return result.fail(
+                                &eyre!("Transaction reverted"),
+                                self.fail_fast,
+                                stop,
+                            );
+                        }
+                        result.status = OpStatus::Success;
+                        result
+                    }
+                    Err(e) => result.fail(&e, self.fail_fast, stop),
+                }
+            });
+        stream::iter(jobs)
+            .buffered(self.parallel.into())
+            .collect()
+            .await
+    }
+}
+
+/// Load the operations from the batch file at `path`: JSON if it ends in
+/// `.json`, YAML otherwise.
+pub fn load<O: Operation>(path: impl AsRef<Path>) -> Result<Vec<O>> {
+    let path = path.as_ref();
+    let ctx = || format!("Reading batch file {}", path.display());
+    let text = fs::read_to_string(path).with_context(ctx)?;
+    let ops = if path.extension().is_some_and(|e| e == "json") {
+        serde_json::from_str(&text).with_context(ctx)?
+    } else {
+        serde_yaml::from_str(&text).with_context(ctx)?
+    };
+    Ok(ops)
+}
+
+/// Outcome of an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpStatus {
+    Success,
+    /// Not sent, reverted, or not mined.
+    Failed,
+    /// Not started because an earlier operation failed (`--fail-fast`).
+    Skipped,
+}
+
+impl fmt::Display for OpStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            OpStatus::Success => "success",
+            OpStatus::Failed => "failed",
+            OpStatus::Skipped => "skipped",
+        })
+    }
+}
+
+/// The report line of an operation.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpResult {
+    /// Position of the operation in the batch file, from 1.
+    pub index: usize,
+    pub command: String,
+    pub chain: Option<String>,
+    pub sender: Option<Address>,
+    pub status: OpStatus,
+    pub tx: Option<H256>,
+    pub block: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl OpResult {
+    fn new(i: usize, op: &impl Operation) -> Self {
+        Self {
+            index: i + 1,
+            command: op.command().to_owned(),
+            chain: None,
+            sender: None,
+            status: OpStatus::Skipped,
+            tx: None,
+            block: None,
+            error: None,
+        }
+    }
+
+    fn fail(mut self, e: &eyre::Report, fail_fast: bool, stop: &AtomicBool) -> Self {
+        eprintln!(
+            "{} operation {} ({}): {e:#}",
+            s_error!("Failed"),
+            self.index,
+            self.command
+        );
+        self.status = OpStatus::Failed;
+        self.error = Some(format!("{e:#}"));
+        if fail_fast {
+            stop.store(true, Ordering::SeqCst);
+        }
+        self
+    }
+}
+
+/// Print the report of a batch in `format`; fails if any operation did not
+/// succeed.
+pub fn print_results(results: &[OpResult], format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Table {
+        print_table(results)?;
+    } else {
+        print_records(format, results)?;
+    }
+    let failed = results
+        .iter()
+        .filter(|r| r.status == OpStatus::Failed)
+        .count();
+    let skipped = results
+        .iter()
+        .filter(|r| r.status == OpStatus::Skipped)
+        .count();
+    if failed > 0 || skipped > 0 {
+        bail!(
+            "{failed} of {} operation(s) failed, {skipped} skipped",
+            results.len()
+        );
+    }
+    Ok(())
+}
+
+fn print_table(results: &[OpResult]) -> Result<()> {
+    println!();
+    let mut table = Table::new(
+        &[
+            Column::right(4),
+            Column::left(14),
+            Column::left(8),
+            Column::left(10),
+            Column::left(42),
+            Column::left(66),
+        ],
+        &[
+            &s_header!("#"),
+            &s_header!("command"),
+            &s_header!("status"),
+            &s_header!("chain"),
+            &s_header!("sender"),
+            &s_header!("tx / error"),
+        ],
+    )?;
+    for r in results {
+        let status: Box<dyn fmt::Display> = match r.status {
+            OpStatus::Success => Box::new(s_action!(r.status)),
+            OpStatus::Failed => Box::new(s_error!(r.status)),
+            OpStatus::Skipped => Box::new(s_value!(r.status)),
+        };
+        let tx = r.tx.map(|tx| format!("{tx:?}")).unwrap_or_default();
+        let outcome: Box<dyn fmt::Display> = match &r.error {
+            Some(e) => Box::new(s_error!(e)),
+            None => Box::new(s_value!(tx)),
+        };
+        table.row(&[
+            &r.index,
+            &r.command,
+            &status,
+            &r.chain.clone().unwrap_or_default(),
+            &r.sender.map(|s| format!("{s:?}")).unwrap_or_default(),
+            &outcome,
+        ])?;
+    }
+    table.print();
+    println!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::amount::{de_amount, de_opt_amount};
+
+    #[derive(Debug, PartialEq, Eq, Deserialize)]
+    #[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+    enum Op {
+        Buy {
+            #[serde(deserialize_with = "de_amount")]
+            amount: U256,
+            receiver: Option<String>,
+        },
+        Inc {
+            #[serde(default, deserialize_with = "de_opt_amount")]
+            amount: Option<U256>,
+        },
+    }
+
+    impl Operation for Op {
+        fn command(&self) -> &'static str {
+            match self {
+                Op::Buy { .. } => "buy",
+                Op::Inc { .. } => "inc",
+            }
+        }
+    }
+
+    fn load_str(name: &str, text: &str) -> Result<Vec<Op>> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        fs::write(&path, text).unwrap();
+        load(path)
+    }
+
+    #[test]
+    fn loads_yaml_and_json() {
+        let yaml = "- command: buy\n  amount: 1gwei\n  receiver: alice\n- command: inc\n";
+        let expected = vec![
+            Op::Buy {
+                amount: U256::exp10(9),
+                receiver: Some("alice".into()),
+            },
+            Op::Inc { amount: None },
+        ];
+        assert_eq!(load_str("ops.yaml", yaml).unwrap(), expected);
+        let json = r#"[{"command": "buy", "amount": 1000000000, "receiver": "alice"},
+                       {"command": "inc"}]"#;
+        assert_eq!(load_str("ops.json", json).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_arguments() {
+        let err = |text| format!("{:#}", load_str("ops.yaml", text).unwrap_err());
+        assert!(err("- command: sell\n  amount: 1\n").contains("unknown variant `sell`"));
+        assert!(err("- command: inc\n  receiver: bob\n").contains("unknown field `receiver`"));
+        assert!(err("- command: buy\n").contains("missing field `amount`"));
+        assert!(err("- command: buy\n  amount: 1.5wei\n").contains("not a whole number"));
+    }
+
+    #[tokio::test]
+    async fn skips_the_rest_after_a_failure_with_fail_fast() {
+        let args = BatchArgs {
+            file: PathBuf::new(),
+            parallel: 1,
+            fail_fast: true,
+        };
+        let ops = [Op::Inc { amount: None }, Op::Inc { amount: None }];
+        let lane = ("ethereum".to_owned(), Address::repeat_byte(1));
+        let jobs = vec![Ok((lane.clone(), ())), Ok((lane, ()))];
+        let results = args
+            .run(&ops, jobs, |_, ()| async { Err(eyre!("out of gas")) })
+            .await;
+        let statuses: Vec<_> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [OpStatus::Failed, OpStatus::Skipped]);
+        assert_eq!(results[0].error.as_deref(), Some("out of gas"));
+        assert_eq!(results[1].command, "inc");
+    }
+}
@@ -0,0 +1,289 @@
+//! Measuring relay latency and throughput under load (`bench`).
+//!
+//! A benchmark sends many cross-chain transfers and records a [`Sample`] for
+//! each: when it was sent, and how long it took until its effect on the other
+//! chain was observed.  A [`BenchReport`] sums the samples up (latency
+//! percentiles, throughput and failures), prints the summary and saves
+//! everything as JSON, so that runs against different relayer versions can be
+//! compared.
+//!
+//! Latencies are measured by polling the destination chain, so they include
+//! up to one polling interval.
+
+use std::{
+    fmt, fs,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ethers::types::{Address, H256};
+use eyre::{Context, Result};
+use serde::Serialize;
+
+use crate::{s_action, s_error, s_header, s_value};
+
+/// Outcome of a benchmarked transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleStatus {
+    Delivered,
+    /// The transaction could not be sent, or reverted.
+    SendFailed,
+    /// Sent, but not delivered before the timeout.
+    Timeout,
+}
+
+impl fmt::Display for SampleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            SampleStatus::Delivered => "delivered",
+            SampleStatus::SendFailed => "send failed",
+            SampleStatus::Timeout => "timeout",
+        })
+    }
+}
+
+/// A benchmarked transfer.
+#[derive(Debug, Clone, Serialize)]
+pub struct Sample {
+    pub index: usize,
+    pub sender: Address,
+    pub tx: Option<H256>,
+    pub status: SampleStatus,
+    /// When the transfer was sent, in milliseconds since the start of the run.
+    pub sent_ms: u64,
+    /// Time from sending the transfer until its delivery was observed, in
+    /// milliseconds.
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Latency percentiles of the delivered transfers, in milliseconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Latency {
+    pub min_ms: u64,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    pub mean_ms: u64,
+}
+
+impl Latency {
+    /// Percentiles of `latencies` (nearest rank), or `None` if there are none.
+    fn of(mut latencies: Vec<u64>) -> Option<Self> {
+        latencies.sort_unstable();
+        let n = latencies.len();
+        let pct = |p: usize| latencies[(p * n).div_ceil(100).max(1) - 1];
+        Some(Self {
+            min_ms: *latencies.first()?,
+            p50_ms: pct(50),
+            p90_ms: pct(90),
+            p99_ms: pct(99),
+            max_ms: *latencies.last()?,
+            mean_ms: latencies.iter().sum::<u64>() / n as u64,
+        })
+    }
+}
+
+/// What a run amounts to.
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub transfers: usize,
+    pub delivered: usize,
+    pub send_failures: usize,
+    pub timeouts: usize,
+    pub latency: Option<Latency>,
+    /// Transfers sent per second, from the start of the run to the last send.
+    pub send_rate: f64,
+    /// Transfers delivered per second, from the start of the run to the last
+    /// delivery.
+    pub throughput: f64,
+    /// From the start of the run to the last send or delivery, in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl Summary {
+    fn of(samples: &[Sample]) -> Self {
+        let count = |status| samples.iter().filter(|s| s.status == status).count();
+        let delivered = count(SampleStatus::Delivered);
+        let last_sent = samples.iter().map(|s| s.sent_ms).max().unwrap_or(0);
+        let last_delivered = samples
+            .iter()
+            .filter_map(|s| Some(s.sent_ms + s.latency_ms?))
+            .max();
+        let per_sec = |n: usize, ms: u64| {
+            if ms == 0 {
+                0.0
+            } else {
+                n as f64 * 1000.0 / ms as f64
+            }
+        };
+        Self {
+            transfers: samples.len(),
+            delivered,
+            send_failures: count(SampleStatus::SendFailed),
+            timeouts: count(SampleStatus::Timeout),
+            latency: Latency::of(samples.iter().filter_map(|s| s.latency_ms).collect()),
+            send_rate: per_sec(samples.len(), last_sent),
+            throughput: per_sec(delivered, last_delivered.unwrap_or(0)),
+            duration_ms: last_delivered.unwrap_or(0).max(last_sent),
+        }
+    }
+}
+
+/// The results of a benchmark run, with the settings (`C`) it ran with.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport<C> {
+    /// Free-form label of the run, e.g., the relayer version.
+    pub label: Option<String>,
+    /// When the run started, in seconds since the Unix epoch.
+    pub started_at: u64,
+    pub config: C,
+    pub summary: Summary,
+    pub samples: Vec<Sample>,
+}
+
+impl<C: Serialize> BenchReport<C> {
+    pub fn new(
+        label: Option<String>,
+        started_at: SystemTime,
+        config: C,
+        samples: Vec<Sample>,
+    ) -> Self {
+        Self {
+            label,
+            started_at: started_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            config,
+            summary: Summary::of(&samples),
+            samples,
+        }
+    }
+
+    /// Save the report to `path` as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+            .with_context(|| format!("Writing benchmark report {}", path.display()))
+    }
+
+    /// Print the summary.
+    pub fn print(&self) {
+        let s = &self.summary;
+        let ms = |ms: u64| format!("{:.3?}", Duration::from_millis(ms));
+        println!();
+        println!(
+            "{:>10} {} in {} (sent at {} per second)",
+            s_header!("transfers"),
+            s_value!(s.transfers),
+            s_value!(ms(s.duration_ms)),
+            s_value!(format!("{:.2}", s.send_rate))
+        );
+        println!(
+            "{:>10} {} ({} per second)",
+            s_header!("delivered"),
+            s_value!(s.delivered),
+            s_value!(format!("{:.2}", s.throughput))
+        );
+        let failed = format!("{} send failures, {} timeouts", s.send_failures, s.timeouts);
+        if s.send_failures + s.timeouts == 0 {
+            println!("{:>10} {}", s_header!("failed"), s_action!(failed));
+        } else {
+            println!("{:>10} {}", s_header!("failed"), s_error!(failed));
+        }
+        match &s.latency {
+            Some(l) => println!(
+                "{:>10} p50 {}, p90 {}, p99 {} (min {}, max {}, mean {})",
+                s_header!("latency"),
+                s_value!(ms(l.p50_ms)),
+                s_value!(ms(l.p90_ms)),
+                s_value!(ms(l.p99_ms)),
+                s_value!(ms(l.min_ms)),
+                s_value!(ms(l.max_ms)),
+                s_value!(ms(l.mean_ms))
+            ),
+            None => println!("{:>10} -", s_header!("latency")),
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(index: usize, status: SampleStatus, sent_ms: u64, latency_ms: Option<u64>) -> Sample {
+        Sample {
+            index,
+            sender: Address::zero(),
+            tx: None,
+            status,
+            sent_ms,
+            latency_ms,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn computes_latency_percentiles() {
+        assert!(Latency::of(vec![]).is_none());
+
+        let one = Latency::of(vec![42]).unwrap();
+        assert_eq!(
+            [
+                one.min_ms,
+                one.p50_ms,
+                one.p90_ms,
+                one.p99_ms,
+                one.max_ms,
+                one.mean_ms
+            ],
+            [42; 6]
+        );
+
+        // 1 through 100, shuffled
+        let latencies = (1..=100).map(|i| (i * 37) % 100 + 1).collect();
+        let l = Latency::of(latencies).unwrap();
+        assert_eq!(
+            [l.min_ms, l.p50_ms, l.p90_ms, l.p99_ms, l.max_ms, l.mean_ms],
+            [1, 50, 90, 99, 100, 50]
+        );
+    }
+
+    #[test]
+    fn summarizes_samples() {
+        let s = Summary::of(&[]);
+        assert_eq!((s.transfers, s.delivered, s.duration_ms), (0, 0, 0));
+        assert_eq!((s.send_rate, s.throughput), (0.0, 0.0));
+
+        let s = Summary::of(&[
+            sample(0, SampleStatus::Delivered, 0, Some(1500)),
+            sample(1, SampleStatus::Delivered, 500, Some(1500)),
+            sample(2, SampleStatus::SendFailed, 1000, None),
+            sample(3, SampleStatus::Timeout, 2000, None),
+        ]);
+        assert_eq!((s.transfers, s.delivered), (4, 2));
+        assert_eq!((s.send_failures, s.timeouts), (1, 1));
+        assert_eq!(s.send_rate, 2.0);
+        assert_eq!(s.throughput, 1.0);
+        assert_eq!(s.duration_ms, 2000);
+        assert_eq!(s.latency.unwrap().mean_ms, 1500);
+    }
+
+    #[test]
+    fn has_no_throughput_without_deliveries() {
+        let s = Summary::of(&[
+            sample(0, SampleStatus::Timeout, 0, None),
+            sample(1, SampleStatus::SendFailed, 1000, None),
+        ]);
+        assert_eq!(s.delivered, 0);
+        assert!(s.latency.is_none());
+        assert_eq!(s.throughput, 0.0);
+        assert_eq!(s.send_rate, 2.0);
+        assert_eq!(s.duration_ms, 1000);
+    }
+}
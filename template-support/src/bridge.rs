@@ -0,0 +1,206 @@
+//! Waiting for the relayer to bridge deployed contracts.
+//!
+//! After deploying, the templates wait for the relayer to connect every
+//! contract to its shims on the other chains before calling anything
+//! cross-chain.  [`when_bridged`] bounds that wait and, if it times out,
+//! fails with a [`NotBridged`] error listing the contract/shim pairs that are
+//! not bridged yet.  [`exit_if_not_bridged`] turns that error into the
+//! distinct [`NOT_BRIDGED_EXIT_CODE`].
+
+use std::{
+    fmt,
+    future::Future,
+    process,
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+use ethers::{providers::Middleware, types::Address};
+use eyre::Result;
+use thiserror::Error;
+
+use crate::s_error;
+
+/// Default time to wait for contracts to be bridged.
+pub const DEFAULT_BRIDGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Exit code of the templates' binaries when contracts are not bridged in time.
+pub const NOT_BRIDGED_EXIT_CODE: i32 = 3;
+
+/// The `--bridge-timeout` flag.
+#[derive(Debug, Args)]
+pub struct BridgeArgs {
+    /// How long to wait for the relayer to bridge the deployed contracts, in seconds.
+    #[clap(long, default_value_t = DEFAULT_BRIDGE_TIMEOUT.as_secs())]
+    pub bridge_timeout: u64,
+}
+
+impl BridgeArgs {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.bridge_timeout)
+    }
+}
+
+/// A contract and one of its shims, which the relayer connects.
+#[derive(Debug, Clone)]
+pub struct Pair {
+    pub contract: String,
+    pub target: String,
+    pub shim_target: String,
+    pub shim: Address,
+    /// Whether the shim has code (`None` if that could not be checked).
+    pub shim_deployed: Option<bool>,
+    /// Whether the relayer has bridged the contract to its shims.
+    pub bridged: bool,
+}
+
+impl Pair {
+    /// Describe the shim of `contract` (on `target`) at `shim` on `shim_target`,
+    /// checking for its code via `shim_provider`.
+    pub async fn new<M: Middleware>(
+        contract: impl Into<String>,
+        target: impl ToString,
+        shim_target: impl ToString,
+        shim: Address,
+        shim_provider: &M,
+    ) -> Self {
+        let code = shim_provider.get_code(shim, None).await;
+        Self {
+            contract: contract.into(),
+            target: target.to_string(),
+            shim_target: shim_target.to_string(),
+            shim,
+            shim_deployed: code.ok().map(|c| !c.is_empty()),
+            bridged: false,
+        }
+    }
+
+    /// Record whether the relayer has bridged the contract (typically, the
+    /// contract's `is_bridged()`).
+    pub fn bridged(mut self, bridged: bool) -> Self {
+        self.bridged = bridged;
+        self
+    }
+}
+
+impl fmt::Display for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' on {} <- shim on {} ({:?})",
+            self.contract, self.target, self.shim_target, self.shim
+        )?;
+        match self.shim_deployed {
+            Some(true) => Ok(()),
+            Some(false) => write!(f, ": the shim has no code"),
+            None => write!(f, ": could not check the shim's code"),
+        }
+    }
+}
+
+/// The relayer did not bridge the contracts in time.
+#[derive(Debug, Error)]
+pub struct NotBridged {
+    pub waited: Duration,
+    /// The pairs not bridged yet.
+    pub pairs: Vec<Pair>,
+}
+
+impl fmt::Display for NotBridged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Contracts not bridged after {:.1?}; is the relayer running ('cubist start')?",
+            self.waited
+        )?;
+        if self.pairs.is_empty() {
+            return write!(f, "  (All of them were bridged right after the timeout.)");
+        }
+        write!(f, "  Waiting on:")?;
+        for pair in &self.pairs {
+            write!(f, "\n  - {pair}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Wait up to `timeout` for `bridged` (typically `cubist.when_bridged(None)`)
+/// to resolve to `true`, returning how long that took.  Otherwise, fail with
+/// [`NotBridged`], listing the contract/shim pairs returned by `pairs` that are
+/// not bridged.
+pub async fn when_bridged<P, PFut>(
+    bridged: impl Future<Output = bool>,
+    timeout: Duration,
+    pairs: P,
+) -> Result<Duration>
+where
+    P: FnOnce() -> PFut,
+    PFut: Future<Output = Vec<Pair>>,
+{
+    let start = Instant::now();
+    if let Ok(true) = tokio::time::timeout(timeout, bridged).await {
+        return Ok(start.elapsed());
+    }
+    let mut pairs = pairs().await;
+    pairs.retain(|p| !p.bridged);
+    Err(NotBridged {
+        waited: start.elapsed(),
+        pairs,
+    }
+    .into())
+}
+
+/// Exit with [`NOT_BRIDGED_EXIT_CODE`] (rather than an error report with a
+/// backtrace) if `result` is a [`NotBridged`] error.
+pub fn exit_if_not_bridged<T>(result: Result<T>) -> Result<T> {
+    match result {
+        Err(e) if e.is::<NotBridged>() => {
+            eprintln!("{} {e}", s_error!("Error:"));
+            process::exit(NOT_BRIDGED_EXIT_CODE);
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future;
+
+    use super::*;
+
+    fn pair(contract: &str, bridged: bool) -> Pair {
+        Pair {
+            contract: contract.to_owned(),
+            target: "polygon".to_owned(),
+            shim_target: "ethereum".to_owned(),
+            shim: Address::repeat_byte(1),
+            shim_deployed: Some(true),
+            bridged,
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_only_pairs_not_bridged() {
+        let timeout = Duration::from_millis(10);
+        let pairs = || async { vec![pair("A", true), pair("B", false)] };
+        let e = when_bridged(future::pending(), timeout, pairs)
+            .await
+            .unwrap_err();
+        let not_bridged = e.downcast_ref::<NotBridged>().unwrap();
+        assert_eq!(not_bridged.pairs.len(), 1);
+        assert_eq!(not_bridged.pairs[0].contract, "B");
+        let msg = e.to_string();
+        assert!(msg.ends_with("Waiting on:\n  - 'B' on polygon <- shim on ethereum (0x0101010101010101010101010101010101010101)"), "{msg}");
+
+        let pairs = || async { vec![pair("A", true)] };
+        let e = when_bridged(future::pending(), timeout, pairs)
+            .await
+            .unwrap_err();
+        assert!(e.to_string().ends_with("right after the timeout.)"), "{e}");
+
+        let waited = when_bridged(future::ready(true), timeout, || async { vec![] })
+            .await
+            .unwrap();
+        assert!(waited < timeout);
+    }
+}
@@ -0,0 +1,96 @@
+//! Locating and copying a template's `cubist-config.json`.
+//!
+//! The Cubist SDK reads the config file named by the `CUBIST_CONFIG`
+//! environment variable, if set, or else the project's own.  Both the test
+//! harness ([`crate::testing`]) and profile selection ([`crate::profile`])
+//! point that variable at an edited copy of the config.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, Context, Result};
+use serde_json::{Map, Value};
+
+/// Name of the config file in each template.
+pub const CONFIG_FILE: &str = "cubist-config.json";
+
+/// Environment variable used to select the config file.
+pub const CONFIG_ENV: &str = "CUBIST_CONFIG";
+
+/// The config file used for the project in `project_dir`: the one selected with
+/// the `CUBIST_CONFIG` environment variable, if any, or the project's own.
+pub fn config_path(project_dir: impl AsRef<Path>) -> PathBuf {
+    env::var_os(CONFIG_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| project_dir.as_ref().join(CONFIG_FILE))
+}
+
+/// The path and contents of the config file used for the project in `project_dir`.
+pub(crate) fn read_config(project_dir: impl AsRef<Path>) -> Result<(PathBuf, Value)> {
+    let src = config_path(project_dir);
+    let ctx = || format!("Reading config file {}", src.display());
+    let config = serde_json::from_str(&std::fs::read_to_string(&src).with_context(ctx)?)
+        .with_context(ctx)?;
+    Ok((src, config))
+}
+
+/// The targets (chains) of the project in `project_dir`, as listed in its config file.
+pub fn config_targets(project_dir: impl AsRef<Path>) -> Result<Vec<String>> {
+    let (_, config) = read_config(project_dir)?;
+    Ok(config["contracts"]["targets"]
+        .as_object()
+        .map(|targets| targets.keys().cloned().collect())
+        .unwrap_or_default())
+}
+
+/// Copy the config file `src` to `dest`, making paths absolute (relative to the
+/// directory of `src`), then applying `edit` to the copy.
+pub(crate) fn copy_config(
+    src: &Path,
+    dest: &Path,
+    edit: impl FnOnce(&mut Map<String, Value>),
+) -> Result<()> {
+    let mut json: Value = serde_json::from_str(
+        &std::fs::read_to_string(src).context(format!("Reading {}", src.display()))?,
+    )?;
+    let dir = src.parent().unwrap_or(Path::new("."));
+    let abs = |p: &Value| -> Result<Value> {
+        let p = p
+            .as_str()
+            .ok_or_else(|| eyre!("Expected a path, got {p}"))?;
+        Ok(Value::from(dir.join(p).to_string_lossy().into_owned()))
+    };
+
+    let obj = json
+        .as_object_mut()
+        .ok_or_else(|| eyre!("{} is not a JSON object", src.display()))?;
+    for key in ["build_dir", "deploy_dir"] {
+        if let Some(path) = obj.get(key) {
+            let path = abs(path)?;
+            obj.insert(key.into(), path);
+        }
+    }
+    if let Some(contracts) = obj.get_mut("contracts").and_then(Value::as_object_mut) {
+        if let Some(root_dir) = contracts.get("root_dir") {
+            let root_dir = abs(root_dir)?;
+            contracts.insert("root_dir".into(), root_dir);
+        }
+        let targets = contracts.get_mut("targets").and_then(Value::as_object_mut);
+        for target in targets.into_iter().flat_map(|t| t.values_mut()) {
+            if let Some(files) = target.get_mut("files").and_then(Value::as_array_mut) {
+                for file in files {
+                    *file = abs(file)?;
+                }
+            }
+        }
+    }
+    edit(obj);
+
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(dest, serde_json::to_string_pretty(&json)?)?;
+    Ok(())
+}
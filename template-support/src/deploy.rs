@@ -0,0 +1,286 @@
+//! Deployment directory handling and deployment plans.
+//!
+//! A template's `deploy` command describes its deployment as a [`Plan`]: the
+//! steps it runs, in order, and which of them an earlier deployment (as
+//! reported by `::deployed()`) already completed.  [`Plan::prepare`] then
+//! decides, based on [`DeployFlags`], whether to deploy from scratch, resume an
+//! interrupted deployment, or refuse to touch an existing one.
+
+use std::{fmt, fs, path::Path};
+
+use clap::Args;
+use ethers::types::Address;
+use eyre::{bail, eyre, Context, Result};
+
+use crate::{bridge::BridgeArgs, s_action, s_contract, s_header, s_value};
+
+/// Delete the deployment directory (if any) left behind by a previous deployment.
+pub fn clean_deploy_dir(deploy_dir: &Path) -> Result<()> {
+    if deploy_dir.is_dir() {
+        println!(
+            "{} deployment dir: {}",
+            s_action!("Deleting"),
+            s_value!(deploy_dir.display()),
+        );
+        fs::remove_dir_all(deploy_dir).context("Deleting previous deployment dir")?;
+    }
+    Ok(())
+}
+
+/// Flags shared by the templates' `deploy` commands.
+#[derive(Debug, Args)]
+pub struct DeployFlags {
+    /// Redeploy everything, forgetting any existing deployment.
+    #[clap(long, conflicts_with = "resume")]
+    pub force: bool,
+    /// Finish an interrupted deployment, keeping the contracts it already deployed.
+    #[clap(long)]
+    pub resume: bool,
+    /// Only print the steps (and constructor arguments) that would run.
+    #[clap(long)]
+    pub plan: bool,
+    #[clap(flatten)]
+    pub bridge: BridgeArgs,
+}
+
+/// Status of a deployment step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Not deployed yet.
+    Pending,
+    /// Already deployed at this address.
+    Done(Address),
+    /// Deployed at this address, but unusable without the steps that follow it
+    /// (e.g., because it refers to shims that cannot be recovered), so it has
+    /// to be redeployed.
+    Stale(Address),
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Pending => f.pad("pending"),
+            Status::Done(addr) => f.pad(&format!("deployed at {addr:?}")),
+            Status::Stale(addr) => f.pad(&format!("stale, at {addr:?}")),
+        }
+    }
+}
+
+/// A deployment step.
+#[derive(Debug, Clone)]
+pub struct Step {
+    /// The chain the step deploys to.
+    pub target: String,
+    /// What gets deployed (e.g., `TokenSender` or `ERC20Bridged shims`).
+    pub contract: String,
+    /// Constructor arguments, as they should be displayed.
+    pub args: Vec<String>,
+    pub status: Status,
+}
+
+impl Step {
+    pub fn new(target: impl ToString, contract: impl Into<String>, status: Status) -> Self {
+        Self {
+            target: target.to_string(),
+            contract: contract.into(),
+            args: vec![],
+            status,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl ToString) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status == Status::Pending
+    }
+}
+
+/// The steps of a deployment, in order; see the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    pub fn new(steps: Vec<Step>) -> Self {
+        Self { steps }
+    }
+
+    /// The step deploying `contract`.
+    pub fn step(&self, contract: &str) -> Result<&Step> {
+        self.steps
+            .iter()
+            .find(|s| s.contract == contract)
+            .ok_or_else(|| eyre!("No deployment step for {contract}"))
+    }
+
+    fn deployed(&self) -> Vec<&Step> {
+        self.steps.iter().filter(|s| !s.is_pending()).collect()
+    }
+
+    fn describe(steps: &[&Step]) -> String {
+        steps
+            .iter()
+            .map(|s| format!("'{}' ({})", s.contract, s.status))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Decide what to do about earlier deployments, given the `deploy` flags.
+    ///
+    /// Returns `false` if nothing should be deployed (with `--plan`); otherwise
+    /// returns `true`, after deleting the deployment dir if the deployment
+    /// starts from scratch.  Steps that should run are left pending.
+    pub fn prepare(&mut self, args: &DeployFlags, deploy_dir: &Path) -> Result<bool> {
+        let deployed = self.deployed();
+        let fresh = args.force || deployed.is_empty();
+        let refusal = if fresh {
+            None
+        } else if deployed
+            .iter()
+            .any(|s| matches!(s.status, Status::Stale(_)))
+        {
+            Some(format!(
+                "An earlier deployment was interrupted and cannot be resumed: {}.  \
+                 Use '--force' to start over",
+                Self::describe(&deployed)
+            ))
+        } else if deployed.len() == self.steps.len() {
+            Some(format!(
+                "Already deployed: {}.  Use '--force' to redeploy (forgetting the existing deployment)",
+                Self::describe(&deployed)
+            ))
+        } else if !args.resume {
+            Some(format!(
+                "An earlier deployment was interrupted after deploying {}.  \
+                 Use '--resume' to finish it or '--force' to start over",
+                Self::describe(&deployed)
+            ))
+        } else {
+            None
+        };
+
+        if fresh {
+            for step in &mut self.steps {
+                step.status = Status::Pending;
+            }
+        }
+        if args.plan {
+            self.print();
+            if let Some(refusal) = refusal {
+                println!("\n{refusal}");
+            }
+            return Ok(false);
+        }
+        if let Some(refusal) = refusal {
+            bail!(refusal);
+        }
+        if fresh {
+            if args.resume {
+                println!("Nothing to resume, deploying from scratch");
+            }
+            // anything left behind (e.g., shims from an interrupted deployment) is unused
+            clean_deploy_dir(deploy_dir)?;
+        } else {
+            println!(
+                "{} deployment, keeping {}",
+                s_action!("Resuming"),
+                Self::describe(&self.deployed())
+            );
+        }
+        Ok(true)
+    }
+
+    /// Print the steps and their status.
+    pub fn print(&self) {
+        println!("{}", s_header!("Deployment plan:"));
+        for (i, step) in self.steps.iter().enumerate() {
+            let args = if step.args.is_empty() || !step.is_pending() {
+                String::new()
+            } else {
+                let args: Vec<_> = step.args.iter().map(|a| s_value!(a).to_string()).collect();
+                format!("({})", args.join(", "))
+            };
+            println!(
+                "  {}. {} {}{args} on {}: {}",
+                i + 1,
+                s_action!(match step.status {
+                    Status::Pending => "Deploy",
+                    Status::Done(_) => "Keep",
+                    // shown by '--plan' next to the refusal to proceed without '--force'
+                    Status::Stale(_) => "Replace",
+                }),
+                s_contract!(&step.contract),
+                s_value!(&step.target),
+                step.status,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(force: bool, resume: bool) -> DeployFlags {
+        DeployFlags {
+            force,
+            resume,
+            plan: false,
+            bridge: BridgeArgs { bridge_timeout: 0 },
+        }
+    }
+
+    fn plan(first: Status, second: Status) -> Plan {
+        Plan::new(vec![
+            Step::new("ethereum", "A", first),
+            Step::new("polygon", "B", second),
+        ])
+    }
+
+    #[test]
+    fn resumes_interrupted_deployments() {
+        let dir = tempfile::tempdir().unwrap();
+        let done = Status::Done(Address::repeat_byte(1));
+
+        let mut p = plan(done, Status::Pending);
+        let e = p.prepare(&flags(false, false), dir.path()).unwrap_err();
+        assert!(e.to_string().contains("Use '--resume'"), "{e}");
+
+        assert!(p.prepare(&flags(false, true), dir.path()).unwrap());
+        assert_eq!(p.step("A").unwrap().status, done);
+        assert!(p.step("B").unwrap().is_pending());
+        assert!(dir.path().exists());
+        assert!(p.step("C").is_err());
+    }
+
+    #[test]
+    fn refuses_to_clobber_deployments() {
+        let dir = tempfile::tempdir().unwrap();
+        let done = Status::Done(Address::repeat_byte(1));
+
+        let mut p = plan(done, done);
+        let e = p.prepare(&flags(false, true), dir.path()).unwrap_err();
+        assert!(e.to_string().starts_with("Already deployed"), "{e}");
+
+        let mut p = plan(Status::Stale(Address::repeat_byte(1)), Status::Pending);
+        let e = p.prepare(&flags(false, true), dir.path()).unwrap_err();
+        assert!(e.to_string().contains("cannot be resumed"), "{e}");
+
+        assert!(p.prepare(&flags(true, false), dir.path()).unwrap());
+        assert!(p.steps.iter().all(Step::is_pending));
+        assert!(!dir.path().exists());
+    }
+
+    #[test]
+    fn resumes_nothing_from_scratch() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut p = plan(Status::Pending, Status::Pending);
+        assert!(p.prepare(&flags(false, true), dir.path()).unwrap());
+        assert!(p.steps.iter().all(Step::is_pending));
+        assert!(!dir.path().exists());
+    }
+}
@@ -0,0 +1,529 @@
+//! Diagnosing the local multi-chain environment.
+//!
+//! The templates' `doctor` commands check everything `cubist()` and
+//! `when_bridged` depend on, and report each problem instead of failing on
+//! the first one.  [`Doctor::check_networks`] only needs the config file, so
+//! it works even when the Cubist SDK cannot load the project; the templates
+//! then add the checks that need the SDK (deployed contracts, the relayer).
+
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    signers::{coins_bip39::English, MnemonicBuilder, Signer},
+    types::{Address, U256},
+};
+use eyre::{bail, eyre, Context, Result};
+use serde_json::Value;
+
+use crate::{
+    config::config_path,
+    profile::DEFAULT_PROFILE,
+    s_action, s_changed, s_error, s_header, s_value,
+    signers::{parse_private_key, SignerSource, Signers, SIGNERS_FILE},
+};
+
+/// How long to wait for each RPC request.
+const RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Name of the file, next to the config file, that secrets are read from
+/// when they are not set in the environment.
+pub const DOTENV_FILE: &str = ".env";
+
+/// Reports the results of checks, counting failures and warnings.
+#[derive(Debug, Default)]
+pub struct Report {
+    failures: usize,
+    warnings: usize,
+}
+
+impl Report {
+    pub fn ok(&mut self, target: impl Display, msg: impl Display) {
+        println!("  {} {:<10} {msg}", s_action!("ok  "), target);
+    }
+
+    pub fn warn(&mut self, target: impl Display, msg: impl Display) {
+        self.warnings += 1;
+        println!("  {} {:<10} {msg}", s_changed!("warn"), target);
+    }
+
+    pub fn fail(&mut self, target: impl Display, msg: impl Display) {
+        self.failures += 1;
+        println!("  {} {:<10} {msg}", s_error!("FAIL"), target);
+    }
+
+    /// Print a summary; fails if any check failed.
+    pub fn finish(self) -> Result<()> {
+        println!();
+        if self.failures > 0 {
+            bail!(
+                "{} check(s) failed, {} warning(s)",
+                self.failures,
+                self.warnings
+            );
+        }
+        println!(
+            "{} ({} warning(s))",
+            s_action!("All checks passed"),
+            self.warnings
+        );
+        Ok(())
+    }
+}
+
+/// Runs checks and reports their results; see the [module docs](self).
+#[derive(Debug)]
+pub struct Doctor {
+    config: Value,
+    /// Variables from the [`DOTENV_FILE`] next to the config file.
+    dotenv: HashMap<String, String>,
+    signers_path: PathBuf,
+    report: Report,
+}
+
+impl Doctor {
+    /// Read the config file of the project in `project_dir`
+    /// (typically `env!("CARGO_MANIFEST_DIR")`).
+    pub fn new(project_dir: impl AsRef<Path>) -> Result<Self> {
+        let path = config_path(&project_dir);
+        let ctx = || format!("Reading config file {}", path.display());
+        let config = serde_json::from_str(&std::fs::read_to_string(&path).with_context(ctx)?)
+            .with_context(ctx)?;
+        println!("{} {}", s_action!("Checking"), s_value!(path.display()));
+        let mut doctor = Self {
+            config,
+            dotenv: HashMap::new(),
+            signers_path: project_dir.as_ref().join(SIGNERS_FILE),
+            report: Report::default(),
+        };
+        // like the SDK, fall back to the '.env' next to the config file
+        let dotenv = path.with_file_name(DOTENV_FILE);
+        match read_dotenv(&dotenv) {
+            Ok(vars) => doctor.dotenv = vars,
+            Err(e) => doctor.fail("config", format!("{e:#}")),
+        }
+        Ok(doctor)
+    }
+
+    pub fn ok(&mut self, target: impl Display, msg: impl Display) {
+        self.report.ok(target, msg);
+    }
+
+    pub fn warn(&mut self, target: impl Display, msg: impl Display) {
+        self.report.warn(target, msg);
+    }
+
+    pub fn fail(&mut self, target: impl Display, msg: impl Display) {
+        self.report.fail(target, msg);
+    }
+
+    /// For every target in the current network profile, check that its RPC
+    /// endpoint is reachable, that the secrets its accounts are derived from
+    /// (including the keystore or private key selected in the signers file, if
+    /// any) are set, in the environment or the [`DOTENV_FILE`], and that those
+    /// accounts have funds.
+    pub async fn check_networks(&mut self) {
+        let name = self.config["current_network_profile"]
+            .as_str()
+            .unwrap_or(DEFAULT_PROFILE)
+            .to_owned();
+        let profile = self.config["network_profiles"][&name].clone();
+        let Some(targets) = profile.as_object() else {
+            self.fail(
+                "config",
+                format!("No 'network_profiles.{name}' in the config file"),
+            );
+            return;
+        };
+        let signers = match Signers::load(&self.signers_path) {
+            Ok(signers) => signers,
+            Err(e) => {
+                self.fail("config", format!("{e:#}"));
+                Signers::default()
+            }
+        };
+        for (target, network) in targets {
+            println!("{}", s_header!(target));
+            let provider = self.check_rpc(target, network).await;
+            for (var, secret) in env_secrets(network, &self.dotenv) {
+                match (&secret.value, secret.from_dotenv) {
+                    (Some(_), false) => self.ok(target, format!("{} is set", s_value!(var))),
+                    (Some(_), true) => self.ok(
+                        target,
+                        format!("{} is set (in {DOTENV_FILE})", s_value!(var)),
+                    ),
+                    (None, _) => self.fail(
+                        target,
+                        format!(
+                            "{} is not set (in the environment or {DOTENV_FILE})",
+                            s_value!(var)
+                        ),
+                    ),
+                }
+                if let (Some(provider), Some(accounts)) = (&provider, secret.accounts()) {
+                    self.check_funds(target, provider, accounts).await;
+                }
+            }
+            self.check_signer(target, &signers, provider.as_ref()).await;
+        }
+    }
+
+    /// Check the keystore or private key selected for `target` in the signers
+    /// file, if any.
+    async fn check_signer(
+        &mut self,
+        target: &str,
+        signers: &Signers,
+        provider: Option<&Provider<Http>>,
+    ) {
+        let source = signers.source(target);
+        let account = match source {
+            SignerSource::Mnemonic => return,
+            SignerSource::Keystore {
+                password_file: None,
+                ..
+            } => {
+                // without a password file, all we can check without prompting is
+                // the address the keystore claims to hold
+                match signers.address(target) {
+                    Ok(address) => {
+                        self.ok(
+                            target,
+                            format!(
+                                "{} exists; its password will be prompted for",
+                                s_value!(source)
+                            ),
+                        );
+                        address
+                    }
+                    Err(e) => {
+                        self.fail(target, format!("{e:#}"));
+                        None
+                    }
+                }
+            }
+            _ => match signers.wallet(target) {
+                Ok(wallet) => {
+                    let address = wallet.map(|w| w.address());
+                    self.ok(target, format!("{} is usable", s_value!(source)));
+                    address
+                }
+                Err(e) => {
+                    self.fail(target, format!("{e:#}"));
+                    None
+                }
+            },
+        };
+        if let (Some(provider), Some(account)) = (provider, account) {
+            self.check_funds(target, provider, Ok(vec![account])).await;
+        }
+    }
+
+    async fn check_rpc(&mut self, target: &str, network: &Value) -> Option<Provider<Http>> {
+        let Some(url) = network["url"].as_str() else {
+            self.fail(target, "No 'url' in the network profile");
+            return None;
+        };
+        let provider = match Provider::<Http>::try_from(url) {
+            Ok(p) => p,
+            Err(e) => {
+                self.fail(target, format!("Invalid RPC URL {}: {e}", s_value!(url)));
+                return None;
+            }
+        };
+        let status = async {
+            let chain_id = provider.get_chainid().await?;
+            let block = provider.get_block_number().await?;
+            Ok::<_, eyre::Report>((chain_id, block))
+        };
+        match tokio::time::timeout(RPC_TIMEOUT, status).await {
+            Ok(Ok((chain_id, block))) => {
+                self.ok(
+                    target,
+                    format!(
+                        "{} is reachable: chain id {}, latest block {}",
+                        s_value!(url),
+                        s_value!(chain_id),
+                        s_value!(block)
+                    ),
+                );
+                Some(provider)
+            }
+            Ok(Err(e)) => {
+                self.fail(target, format!("{} is unreachable: {e}", s_value!(url)));
+                None
+            }
+            Err(_) => {
+                self.fail(
+                    target,
+                    format!("{} did not respond within {RPC_TIMEOUT:?}", s_value!(url)),
+                );
+                None
+            }
+        }
+    }
+
+    async fn check_funds(
+        &mut self,
+        target: &str,
+        provider: &Provider<Http>,
+        accounts: Result<Vec<Address>>,
+    ) {
+        let accounts = match accounts {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                self.fail(target, format!("Cannot derive accounts: {e:#}"));
+                return;
+            }
+        };
+        for (i, addr) in accounts.into_iter().enumerate() {
+            match provider.get_balance(addr, None).await {
+                Ok(bal) if bal > U256::zero() => self.ok(
+                    target,
+                    format!(
+                        "account {i} ({}) has {} wei",
+                        s_value!(format!("{addr:?}")),
+                        s_value!(bal)
+                    ),
+                ),
+                Ok(_) => self.warn(
+                    target,
+                    format!(
+                        "account {i} ({}) has no funds",
+                        s_value!(format!("{addr:?}"))
+                    ),
+                ),
+                Err(e) => self.fail(target, format!("Cannot get balance of {addr:?}: {e}")),
+            }
+        }
+    }
+
+    /// Check that there is code at `addr` on the chain behind `provider`.
+    pub async fn check_code<M>(
+        &mut self,
+        target: impl Display,
+        name: &str,
+        provider: &M,
+        addr: Address,
+    ) where
+        M: Middleware,
+    {
+        let what = format!("{} ({})", s_value!(name), s_value!(format!("{addr:?}")));
+        match provider.get_code(addr, None).await {
+            Ok(code) if !code.is_empty() => self.ok(target, format!("{what} has code")),
+            Ok(_) => self.fail(
+                target,
+                format!("{what} has no code; was the chain restarted since 'deploy'?"),
+            ),
+            Err(e) => self.fail(target, format!("Cannot get code of {what}: {e}")),
+        }
+    }
+
+    /// Check that `bridged` (typically `cubist.when_bridged(None)`) resolves to
+    /// `true` within `timeout`.
+    pub async fn check_bridged(&mut self, bridged: impl Future<Output = bool>, timeout: Duration) {
+        match tokio::time::timeout(timeout, bridged).await {
+            Ok(true) => self.ok("relayer", "all contracts are bridged"),
+            Ok(false) => self.fail("relayer", "contracts are not bridged; is the relayer running ('cubist start')?"),
+            Err(_) => self.fail(
+                "relayer",
+                format!("contracts are not bridged after {timeout:?}; is the relayer running ('cubist start')?"),
+            ),
+        }
+    }
+
+    /// Print a summary; fails if any check failed.
+    pub fn finish(self) -> Result<()> {
+        self.report.finish()
+    }
+}
+
+/// A secret (mnemonic or private key) taken from an environment variable.
+struct EnvSecret<'a> {
+    spec: &'a Value,
+    value: Option<String>,
+    /// Whether the value comes from the [`DOTENV_FILE`] rather than the environment.
+    from_dotenv: bool,
+}
+
+impl EnvSecret<'_> {
+    /// The accounts derived from the secret, if known.
+    fn accounts(&self) -> Option<Result<Vec<Address>>> {
+        let value = self.value.as_deref()?;
+        if self.spec.get("seed").is_some() {
+            // a mnemonic, e.g., '{ "seed": { "env": ... }, "account_count": 2 }'
+            let count = self.spec["account_count"].as_u64().unwrap_or(1) as u32;
+            Some(
+                (0..count)
+                    .map(|i| {
+                        Ok(MnemonicBuilder::<English>::default()
+                            .phrase(value)
+                            .index(i)?
+                            .build()?
+                            .address())
+                    })
+                    .collect(),
+            )
+        } else {
+            // a private key
+            Some(parse_private_key(value).map(|w| vec![w.address()]))
+        }
+    }
+}
+
+/// All secrets in `network` that are taken from environment variables (or,
+/// failing that, from `dotenv`), along with the names of those variables.
+fn env_secrets<'a>(
+    network: &'a Value,
+    dotenv: &HashMap<String, String>,
+) -> Vec<(String, EnvSecret<'a>)> {
+    let mut result = vec![];
+    collect_env_secrets(network, None, dotenv, &mut result);
+    result
+}
+
+fn collect_env_secrets<'a>(
+    val: &'a Value,
+    parent: Option<&'a Value>,
+    dotenv: &HashMap<String, String>,
+    result: &mut Vec<(String, EnvSecret<'a>)>,
+) {
+    match val {
+        Value::Object(obj) => {
+            if let Some(var) = obj.get("env").and_then(Value::as_str) {
+                // the secret's spec is the object containing '{ "env": ... }'
+                // (e.g., a mnemonic's '"seed"'), or the object itself
+                let spec = parent.filter(|p| p.get("seed") == Some(val)).unwrap_or(val);
+                let from_env = env::var(var).ok().filter(|v| !v.is_empty());
+                let dotenv = dotenv.get(var).filter(|v| !v.is_empty());
+                let from_dotenv = from_env.is_none() && dotenv.is_some();
+                let secret = EnvSecret {
+                    spec,
+                    value: from_env.or_else(|| dotenv.cloned()),
+                    from_dotenv,
+                };
+                result.push((var.to_owned(), secret));
+                return;
+            }
+            for child in obj.values() {
+                collect_env_secrets(child, Some(val), dotenv, result);
+            }
+        }
+        Value::Array(arr) => {
+            for child in arr {
+                collect_env_secrets(child, Some(val), dotenv, result);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Read the variables set in a `.env` file: `NAME=value` lines, optionally
+/// prefixed with `export` and with the value in quotes; `#` starts a comment.
+/// A missing file sets no variables.
+pub fn read_dotenv(path: &Path) -> Result<HashMap<String, String>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let ctx = || format!("Reading {}", path.display());
+    let mut vars = HashMap::new();
+    for (i, line) in fs::read_to_string(path)
+        .with_context(ctx)?
+        .lines()
+        .enumerate()
+    {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| eyre!("Expected 'NAME=value' on line {}", i + 1))
+            .with_context(ctx)?;
+        let value = value.trim();
+        let value = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => value[1..]
+                .split_once(q)
+                .map(|(quoted, _)| quoted)
+                .ok_or_else(|| eyre!("Unterminated quote on line {}", i + 1))
+                .with_context(ctx)?,
+            _ => value.split(" #").next().unwrap_or_default().trim_end(),
+        };
+        vars.insert(name.trim().to_owned(), value.to_owned());
+    }
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reads_dotenv_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DOTENV_FILE);
+        assert!(read_dotenv(&path).unwrap().is_empty());
+
+        fs::write(
+            &path,
+            "# secrets\n\
+             ETHEREUM_MNEMONIC=\"leg multiply opera\"\n\
+             \n\
+             export POLYGON_KEY = 'abc # def' # trailing\n\
+             PLAIN=value # comment\n\
+             EMPTY=\n",
+        )
+        .unwrap();
+        let vars = read_dotenv(&path).unwrap();
+        assert_eq!(vars["ETHEREUM_MNEMONIC"], "leg multiply opera");
+        assert_eq!(vars["POLYGON_KEY"], "abc # def");
+        assert_eq!(vars["PLAIN"], "value");
+        assert_eq!(vars["EMPTY"], "");
+        assert_eq!(vars.len(), 4);
+
+        fs::write(&path, "A=1\nnot a variable\n").unwrap();
+        let e = format!("{:#}", read_dotenv(&path).unwrap_err());
+        assert!(e.contains("line 2"), "{e}");
+        fs::write(&path, "A=\"1\n").unwrap();
+        assert!(read_dotenv(&path).is_err());
+    }
+
+    #[test]
+    fn finds_secrets_in_the_environment_or_dotenv() {
+        let network = json!({
+            "url": "http://127.0.0.1:8545",
+            "bootstrap_mnemonic": {
+                "seed": { "env": "DOCTOR_TEST_UNSET_MNEMONIC" },
+                "account_count": 2
+            },
+            "local_accounts": [{ "private_key": { "env": "DOCTOR_TEST_UNSET_KEY" } }]
+        });
+        let secrets = env_secrets(&network, &HashMap::new());
+        let names: Vec<_> = secrets.iter().map(|(var, _)| var.as_str()).collect();
+        assert_eq!(
+            names,
+            ["DOCTOR_TEST_UNSET_MNEMONIC", "DOCTOR_TEST_UNSET_KEY"]
+        );
+        assert!(secrets.iter().all(|(_, s)| s.value.is_none()));
+
+        let phrase = "leg multiply opera spice season matter void long liquid test snack degree";
+        let dotenv = HashMap::from([("DOCTOR_TEST_UNSET_MNEMONIC".to_owned(), phrase.to_owned())]);
+        let secrets = env_secrets(&network, &dotenv);
+        let (_, mnemonic) = &secrets[0];
+        assert!(mnemonic.from_dotenv);
+        assert_eq!(mnemonic.value.as_deref(), Some(phrase));
+        assert_eq!(mnemonic.accounts().unwrap().unwrap().len(), 2);
+        assert!(secrets[1].1.value.is_none());
+    }
+}
@@ -0,0 +1,488 @@
+//! Gas and fee settings of sent transactions.
+//!
+//! Before a CLI sends a transaction, [`GasPolicy::prepare`] fills in its type,
+//! gas limit, fees and nonce, and prints the effective values.  Each value
+//! comes from the first of:
+//!
+//! 1. the command line ([`GasArgs`])
+//! 2. the project's [`GAS_FILE`], per chain, with per-profile overrides:
+//!
+//!    ```json
+//!    {
+//!      "polygon": { "tx_type": "legacy", "gas_price": "30gwei" },
+//!      "ethereum": { "priority_fee": "1gwei" },
+//!      "profiles": {
+//!        "staging": { "ethereum": { "priority_fee": "3gwei", "max_fee": "200gwei" } }
+//!      }
+//!    }
+//!    ```
+//!
+//! 3. the node: the simulated gas, fee estimates and the signer's pending nonce
+//!
+//! Giving a gas price on the command line switches to a legacy transaction
+//! (and giving an EIP-1559 fee switches back), ignoring the other type's fees
+//! from the file.  The nonce can only be given on the command line.  The file's
+//! chains must be targets of the project's `cubist-config.json`.
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use clap::{Args, ValueEnum};
+use ethers::{
+    providers::Middleware,
+    types::{
+        transaction::eip2718::TypedTransaction, BlockNumber, Eip1559TransactionRequest,
+        TransactionRequest, U256,
+    },
+};
+use eyre::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amount::{de_opt_amount, parse_amount},
+    config::config_targets,
+    profile::current_profile,
+    receipt::format_amount,
+    s_action, s_value,
+};
+
+/// Name of the gas settings file, kept next to `cubist-config.json`.
+pub const GAS_FILE: &str = "gas.json";
+
+/// Transaction type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TxType {
+    /// Pre-EIP-1559 transactions, with a single gas price.
+    Legacy,
+    /// EIP-1559 transactions, with a max fee and a priority fee.
+    Eip1559,
+}
+
+impl fmt::Display for TxType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            TxType::Legacy => "legacy",
+            TxType::Eip1559 => "eip1559",
+        })
+    }
+}
+
+/// Gas settings, each of which is filled in from the node if not given.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GasPolicy {
+    pub tx_type: Option<TxType>,
+    #[serde(default, deserialize_with = "de_opt_amount")]
+    pub gas_limit: Option<U256>,
+    #[serde(default, deserialize_with = "de_opt_amount")]
+    pub gas_price: Option<U256>,
+    #[serde(default, deserialize_with = "de_opt_amount")]
+    pub max_fee: Option<U256>,
+    #[serde(default, deserialize_with = "de_opt_amount")]
+    pub priority_fee: Option<U256>,
+    #[serde(skip)]
+    pub nonce: Option<U256>,
+}
+
+/// Command-line gas settings, overriding the [`GAS_FILE`].
+#[derive(Debug, Clone, Default, Args)]
+pub struct GasArgs {
+    /// Gas limit of sent transactions.  Defaults to the simulated gas.
+    #[clap(long, global = true, value_parser = parse_amount)]
+    gas_limit: Option<U256>,
+    /// Gas price (e.g., '30gwei') of sent transactions; implies '--tx-type legacy'.
+    #[clap(long, global = true, value_parser = parse_amount)]
+    gas_price: Option<U256>,
+    /// Max fee per gas (e.g., '100gwei') of sent EIP-1559 transactions.
+    #[clap(long, global = true, value_parser = parse_amount)]
+    max_fee: Option<U256>,
+    /// Max priority fee (tip) per gas (e.g., '2gwei') of sent EIP-1559 transactions.
+    #[clap(long, global = true, value_parser = parse_amount)]
+    priority_fee: Option<U256>,
+    /// Type of sent transactions.  Defaults to 'eip1559', unless a gas price is given.
+    #[clap(long, global = true, value_enum)]
+    tx_type: Option<TxType>,
+    /// Nonce of the sent transaction.  Defaults to the signer's pending nonce.
+    #[clap(long, global = true, value_parser = parse_amount)]
+    nonce: Option<U256>,
+}
+
+impl GasArgs {
+    /// The gas settings for `chain` in the project in `project_dir` (typically
+    /// `env!("CARGO_MANIFEST_DIR")`): these arguments, then the project's
+    /// [`GAS_FILE`] for the current network profile.
+    pub fn policy(&self, project_dir: impl AsRef<Path>, chain: &str) -> Result<GasPolicy> {
+        let project_dir = project_dir.as_ref();
+        let path = project_dir.join(GAS_FILE);
+        let file = GasFile::load(&path)?;
+        file.check_chains(&config_targets(project_dir)?)
+            .context(format!("Reading gas settings {}", path.display()))?;
+        let file = file.policy(&current_profile(project_dir)?, chain);
+        let args = GasPolicy {
+            tx_type: self.tx_type,
+            gas_limit: self.gas_limit,
+            gas_price: self.gas_price,
+            max_fee: self.max_fee,
+            priority_fee: self.priority_fee,
+            nonce: self.nonce,
+        };
+        let policy = args.or(file);
+        policy
+            .check()
+            .context(format!("Gas settings for {chain}"))?;
+        Ok(policy)
+    }
+}
+
+impl GasPolicy {
+    /// The transaction type these settings ask for, if any.
+    fn requested_type(&self) -> Option<TxType> {
+        let eip1559 = self.max_fee.is_some() || self.priority_fee.is_some();
+        self.tx_type
+            .or(self.gas_price.map(|_| TxType::Legacy))
+            .or(eip1559.then_some(TxType::Eip1559))
+    }
+
+    pub fn tx_type(&self) -> TxType {
+        self.requested_type().unwrap_or(TxType::Eip1559)
+    }
+
+    /// These settings, with the ones not given taken from `fallback`; if these
+    /// ask for a transaction type, `fallback`'s fees for the other type are
+    /// ignored.
+    fn or(self, fallback: GasPolicy) -> GasPolicy {
+        let fallback = match self.requested_type() {
+            Some(TxType::Legacy) => GasPolicy {
+                max_fee: None,
+                priority_fee: None,
+                ..fallback
+            },
+            Some(TxType::Eip1559) => GasPolicy {
+                gas_price: None,
+                ..fallback
+            },
+            None => fallback,
+        };
+        GasPolicy {
+            tx_type: self.requested_type().or(fallback.tx_type),
+            gas_limit: self.gas_limit.or(fallback.gas_limit),
+            gas_price: self.gas_price.or(fallback.gas_price),
+            max_fee: self.max_fee.or(fallback.max_fee),
+            priority_fee: self.priority_fee.or(fallback.priority_fee),
+            nonce: self.nonce.or(fallback.nonce),
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        match self.tx_type() {
+            TxType::Legacy => ensure!(
+                self.max_fee.is_none() && self.priority_fee.is_none(),
+                "Max and priority fees only apply to EIP-1559 transactions"
+            ),
+            TxType::Eip1559 => ensure!(
+                self.gas_price.is_none(),
+                "A gas price only applies to legacy transactions; use a max fee instead"
+            ),
+        }
+        if let (Some(max_fee), Some(priority_fee)) = (self.max_fee, self.priority_fee) {
+            ensure!(
+                priority_fee <= max_fee,
+                "Priority fee ({}) exceeds the max fee ({})",
+                gwei(priority_fee),
+                gwei(max_fee)
+            );
+        }
+        Ok(())
+    }
+
+    /// Fill in the type, gas limit, fees and nonce of `tx` (sent on `chain` with
+    /// `client`, using `gas` as simulated), and print them.
+    pub async fn prepare<C>(
+        &self,
+        client: &C,
+        chain: impl fmt::Display,
+        tx: &mut TypedTransaction,
+        gas: U256,
+    ) -> Result<()>
+    where
+        C: Middleware,
+        C::Error: 'static,
+    {
+        let tx_type = self.tx_type();
+        *tx = match (tx_type, tx.clone()) {
+            (TxType::Legacy, TypedTransaction::Eip1559(inner)) => {
+                TypedTransaction::Legacy(inner.into())
+            }
+            (TxType::Eip1559, TypedTransaction::Legacy(inner)) => {
+                TypedTransaction::Eip1559(to_eip1559(inner))
+            }
+            (_, other) => other,
+        };
+        let gas_limit = self.gas_limit.unwrap_or(gas);
+        tx.set_gas(gas_limit);
+
+        let fees = match tx {
+            TypedTransaction::Eip1559(inner) => {
+                let (max_fee, priority_fee) = match (self.max_fee, self.priority_fee) {
+                    (Some(max_fee), Some(priority_fee)) => (max_fee, priority_fee),
+                    (max_fee, priority_fee) => {
+                        let (est_max, est_priority) = client.estimate_eip1559_fees(None).await?;
+                        let priority_fee = priority_fee.unwrap_or(est_priority);
+                        // keep the estimated base fee margin on top of the tip
+                        let est_base = est_max.saturating_sub(est_priority);
+                        (max_fee.unwrap_or(est_base + priority_fee), priority_fee)
+                    }
+                };
+                ensure!(
+                    priority_fee <= max_fee,
+                    "Priority fee ({}) exceeds the max fee ({})",
+                    gwei(priority_fee),
+                    gwei(max_fee)
+                );
+                inner.max_fee_per_gas = Some(max_fee);
+                inner.max_priority_fee_per_gas = Some(priority_fee);
+                format!(
+                    "max fee {}, priority fee {}",
+                    s_value!(gwei(max_fee)),
+                    s_value!(gwei(priority_fee))
+                )
+            }
+            _ => {
+                let gas_price = match self.gas_price {
+                    Some(gas_price) => gas_price,
+                    None => client.get_gas_price().await?,
+                };
+                tx.set_gas_price(gas_price);
+                format!("gas price {}", s_value!(gwei(gas_price)))
+            }
+        };
+
+        let nonce = match (self.nonce, tx.from().copied().or(client.default_sender())) {
+            (Some(nonce), _) => Some(nonce),
+            (None, Some(from)) => Some(
+                client
+                    .get_transaction_count(from, Some(BlockNumber::Pending.into()))
+                    .await?,
+            ),
+            (None, None) => None,
+        };
+        let nonce = match nonce {
+            Some(nonce) => {
+                tx.set_nonce(nonce);
+                s_value!(nonce).to_string()
+            }
+            None => "from the node".to_owned(),
+        };
+
+        eprintln!(
+            "{} on {chain}: {} transaction, gas limit {}, {fees}, nonce {nonce}",
+            s_action!("Gas"),
+            s_value!(tx_type),
+            s_value!(gas_limit),
+        );
+        Ok(())
+    }
+}
+
+/// The [`GAS_FILE`]: gas settings per chain, with per-profile overrides.
+#[derive(Debug, Default, Deserialize)]
+struct GasFile {
+    #[serde(default)]
+    profiles: BTreeMap<String, BTreeMap<String, GasPolicy>>,
+    #[serde(flatten)]
+    chains: BTreeMap<String, GasPolicy>,
+}
+
+impl GasFile {
+    /// Load the gas file from `path`; a missing file has no settings.
+    fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let ctx = || format!("Reading gas settings {}", path.display());
+        serde_json::from_str(&fs::read_to_string(path).with_context(ctx)?).with_context(ctx)
+    }
+
+    /// Fail if any chain (of any profile) is not one of `targets`.
+    fn check_chains(&self, targets: &[String]) -> Result<()> {
+        let profiles = self.profiles.values().flat_map(|p| p.keys());
+        for chain in self.chains.keys().chain(profiles) {
+            if !targets.contains(chain) {
+                bail!(
+                    "Unknown chain '{chain}'; the targets are: {}",
+                    targets.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn policy(&self, profile: &str, chain: &str) -> GasPolicy {
+        let base = self.chains.get(chain).cloned().unwrap_or_default();
+        match self.profiles.get(profile).and_then(|p| p.get(chain)) {
+            Some(overrides) => overrides.clone().or(base),
+            None => base,
+        }
+    }
+}
+
+/// The legacy transaction `tx`, as an EIP-1559 one.
+fn to_eip1559(tx: TransactionRequest) -> Eip1559TransactionRequest {
+    Eip1559TransactionRequest {
+        from: tx.from,
+        to: tx.to,
+        gas: tx.gas,
+        value: tx.value,
+        data: tx.data,
+        nonce: tx.nonce,
+        chain_id: tx.chain_id,
+        ..Default::default()
+    }
+}
+
+fn gwei(wei: U256) -> String {
+    format!("{} gwei", format_amount(wei, "gwei"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gwei_amount(n: u64) -> Option<U256> {
+        Some(U256::from(n) * 1_000_000_000u64)
+    }
+
+    fn eip1559(max_fee: u64, priority_fee: u64) -> GasPolicy {
+        GasPolicy {
+            max_fee: gwei_amount(max_fee),
+            priority_fee: gwei_amount(priority_fee),
+            ..Default::default()
+        }
+    }
+
+    fn legacy(gas_price: u64) -> GasPolicy {
+        GasPolicy {
+            gas_price: gwei_amount(gas_price),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn switching_type_drops_the_other_types_fees() {
+        let fallback = GasPolicy {
+            gas_limit: Some(21_000.into()),
+            ..eip1559(100, 2)
+        };
+        let policy = legacy(30).or(fallback.clone());
+        assert_eq!(policy.tx_type, Some(TxType::Legacy));
+        assert_eq!((policy.max_fee, policy.priority_fee), (None, None));
+        assert_eq!(policy.gas_price, gwei_amount(30));
+        assert_eq!(policy.gas_limit, Some(21_000.into()));
+        policy.check().unwrap();
+
+        let policy = eip1559(50, 1).or(legacy(30));
+        assert_eq!(policy.tx_type(), TxType::Eip1559);
+        assert_eq!(policy.gas_price, None);
+        policy.check().unwrap();
+
+        // without a type of their own, settings take the fallback's
+        let policy = GasPolicy::default().or(legacy(30));
+        assert_eq!(policy.tx_type(), TxType::Legacy);
+        assert_eq!(policy.gas_price, gwei_amount(30));
+        let policy = GasPolicy {
+            priority_fee: gwei_amount(3),
+            ..Default::default()
+        }
+        .or(fallback);
+        assert_eq!(
+            policy,
+            GasPolicy {
+                tx_type: Some(TxType::Eip1559),
+                gas_limit: Some(21_000.into()),
+                ..eip1559(100, 3)
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_inconsistent_settings() {
+        let mixed = GasPolicy {
+            tx_type: Some(TxType::Legacy),
+            ..eip1559(100, 2)
+        };
+        let e = mixed.check().unwrap_err();
+        assert!(e.to_string().contains("only apply to EIP-1559"), "{e}");
+
+        let mixed = GasPolicy {
+            tx_type: Some(TxType::Eip1559),
+            ..legacy(30)
+        };
+        let e = mixed.check().unwrap_err();
+        assert!(e.to_string().contains("only applies to legacy"), "{e}");
+
+        let e = eip1559(1, 2).check().unwrap_err();
+        assert!(e.to_string().starts_with("Priority fee (2 gwei)"), "{e}");
+
+        eip1559(2, 2).check().unwrap();
+        GasPolicy::default().check().unwrap();
+    }
+
+    fn file(json: &str) -> GasFile {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn applies_profile_overrides_on_top_of_chain_settings() {
+        let file = file(
+            r#"{
+                "polygon": { "tx_type": "legacy", "gas_price": "30gwei", "gas_limit": 100000 },
+                "ethereum": { "priority_fee": "1gwei", "max_fee": "100gwei" },
+                "profiles": {
+                    "staging": {
+                        "ethereum": { "priority_fee": "3gwei" },
+                        "polygon": { "max_fee": "200gwei" }
+                    }
+                }
+            }"#,
+        );
+        assert_eq!(file.policy("default", "ethereum"), eip1559(100, 1));
+        assert_eq!(
+            file.policy("staging", "ethereum"),
+            GasPolicy {
+                tx_type: Some(TxType::Eip1559),
+                ..eip1559(100, 3)
+            }
+        );
+        // the override switches polygon to EIP-1559, dropping its gas price
+        assert_eq!(
+            file.policy("staging", "polygon"),
+            GasPolicy {
+                tx_type: Some(TxType::Eip1559),
+                gas_limit: Some(100_000.into()),
+                max_fee: gwei_amount(200),
+                ..Default::default()
+            }
+        );
+        assert_eq!(file.policy("staging", "avalanche"), GasPolicy::default());
+    }
+
+    #[test]
+    fn rejects_unknown_chains() {
+        let targets = ["ethereum".to_owned(), "polygon".to_owned()];
+        file(r#"{ "polygon": {}, "profiles": { "staging": { "ethereum": {} } } }"#)
+            .check_chains(&targets)
+            .unwrap();
+
+        let e = file(r#"{ "polgon": { "gas_price": "30gwei" } }"#)
+            .check_chains(&targets)
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Unknown chain 'polgon'; the targets are: ethereum, polygon"
+        );
+        let e = file(r#"{ "profiles": { "staging": { "polgon": {} } } }"#)
+            .check_chains(&targets)
+            .unwrap_err();
+        assert!(e.to_string().contains("'polgon'"), "{e}");
+    }
+}
@@ -0,0 +1,686 @@
+//! Cross-chain transfer history.
+//!
+//! A bridge transfer is an action on a source chain (e.g., a payment to
+//! `TokenSender.bridgeSend`) followed, once the relayer delivers it, by an
+//! effect on the destination chain (e.g., an ERC20 mint).  The templates
+//! collect both sides as [`Action`]s, and [`pair_transfers`] matches each
+//! source action with its destination effect, producing [`Transfer`]s with a
+//! [`TransferStatus`] and a latency.
+//!
+//! Both sides are found in event logs ([`log_actions`]), by default in the
+//! latest [`DEFAULT_LOOKBACK`] blocks of each chain (see [`ScanArgs`]), so only
+//! contracts that emit events for both sides can be scanned.  Reverted
+//! transactions emit no events, so they are found by fetching the scanned
+//! blocks and the receipts of calls to the contracts ([`reverted_calls`]).
+//! Latencies are computed from block timestamps, so they have a resolution
+//! of one second.
+
+use std::{collections::HashMap, fmt};
+
+use clap::Args;
+use ethers::{
+    providers::Middleware,
+    types::{Address, Filter, Log, Transaction, H256, U256},
+};
+use eyre::Result;
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+
+use crate::output::ser_decimal;
+
+/// One side of a transfer: an action on the source chain or its effect on the
+/// destination chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Action {
+    pub tx: H256,
+    pub block: u64,
+    /// Timestamp of the block, in seconds.
+    pub timestamp: u64,
+    pub from: Address,
+    /// The recipient on the destination chain.
+    pub to: Address,
+    pub amount: U256,
+    /// Whether the transaction succeeded.
+    pub ok: bool,
+}
+
+/// Status of a [`Transfer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferStatus {
+    /// Not delivered (yet).
+    Pending,
+    /// Delivered with the expected amount.
+    Delivered,
+    /// Delivered, but with a different amount than expected.
+    Mismatched,
+    /// The source transaction reverted, so there is nothing to deliver.
+    Reverted,
+    /// The delivering transaction reverted (whether or not it matches a source
+    /// action).
+    Failed,
+    /// A successful destination effect without a matching source action (e.g.,
+    /// because the source action happened before the scanned blocks).
+    Unmatched,
+}
+
+impl fmt::Display for TransferStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            TransferStatus::Pending => "pending",
+            TransferStatus::Delivered => "delivered",
+            TransferStatus::Mismatched => "mismatched",
+            TransferStatus::Reverted => "reverted",
+            TransferStatus::Failed => "failed",
+            TransferStatus::Unmatched => "unmatched",
+        })
+    }
+}
+
+/// A source action paired with its destination effect (if any).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Transfer {
+    /// What kind of transfer this is, e.g., "buy".
+    pub kind: String,
+    pub status: TransferStatus,
+    pub from: Option<Address>,
+    pub to: Address,
+    pub source_chain: String,
+    pub source_tx: Option<H256>,
+    pub source_block: Option<u64>,
+    /// Amount sent on the source chain.
+    #[serde(serialize_with = "ser_decimal")]
+    pub sent: Option<U256>,
+    /// Amount the destination effect should have.
+    #[serde(serialize_with = "ser_decimal")]
+    pub expected: Option<U256>,
+    pub dest_chain: String,
+    pub dest_tx: Option<H256>,
+    pub dest_block: Option<u64>,
+    /// Amount actually delivered.
+    #[serde(serialize_with = "ser_decimal")]
+    pub received: Option<U256>,
+    /// Seconds from the source action to the destination effect.
+    pub latency_secs: Option<u64>,
+}
+
+impl Transfer {
+    /// Whether `addr` sent or received this transfer.
+    pub fn involves(&self, addr: Address) -> bool {
+        self.from == Some(addr) || self.to == addr
+    }
+}
+
+/// Pair source actions (on `source_chain`) with destination effects (on
+/// `dest_chain`) of the given `kind` of transfer.  `expected` computes the
+/// amount an effect should have from the amount sent (e.g., after fees).
+///
+/// Each source action is paired with the earliest unpaired effect for the same
+/// recipient that is not older than the action, preferring successful effects
+/// with the expected amount.  Effects left over are reported as
+/// [`TransferStatus::Unmatched`], or as [`TransferStatus::Failed`] if they
+/// reverted.
+pub fn pair_transfers(
+    kind: &str,
+    source_chain: impl ToString,
+    dest_chain: impl ToString,
+    mut sources: Vec<Action>,
+    mut effects: Vec<Action>,
+    expected: impl Fn(U256) -> U256,
+) -> Vec<Transfer> {
+    let (source_chain, dest_chain) = (source_chain.to_string(), dest_chain.to_string());
+    sources.sort_by_key(|a| a.block);
+    effects.sort_by_key(|a| a.block);
+    let mut paired = vec![false; effects.len()];
+
+    let mut transfers = vec![];
+    for src in sources {
+        let want = expected(src.amount);
+        let mut transfer = Transfer {
+            kind: kind.to_owned(),
+            status: TransferStatus::Reverted,
+            from: Some(src.from),
+            to: src.to,
+            source_chain: source_chain.clone(),
+            source_tx: Some(src.tx),
+            source_block: Some(src.block),
+            sent: Some(src.amount),
+            expected: Some(want),
+            dest_chain: dest_chain.clone(),
+            dest_tx: None,
+            dest_block: None,
+            received: None,
+            latency_secs: None,
+        };
+        if src.ok {
+            let candidates: Vec<usize> = (0..effects.len())
+                .filter(|&i| {
+                    !paired[i] && effects[i].to == src.to && effects[i].timestamp >= src.timestamp
+                })
+                .collect();
+            let best = candidates
+                .iter()
+                .find(|&&i| effects[i].ok && effects[i].amount == want)
+                .or_else(|| candidates.iter().find(|&&i| effects[i].ok))
+                .or_else(|| candidates.first());
+            transfer.status = match best {
+                None => TransferStatus::Pending,
+                Some(&i) => {
+                    paired[i] = true;
+                    let effect = &effects[i];
+                    transfer.dest_tx = Some(effect.tx);
+                    transfer.dest_block = Some(effect.block);
+                    transfer.received = Some(effect.amount);
+                    transfer.latency_secs = Some(effect.timestamp - src.timestamp);
+                    if !effect.ok {
+                        TransferStatus::Failed
+                    } else if effect.amount != want {
+                        TransferStatus::Mismatched
+                    } else {
+                        TransferStatus::Delivered
+                    }
+                }
+            };
+        }
+        transfers.push(transfer);
+    }
+
+    for (effect, _) in effects.into_iter().zip(paired).filter(|(_, p)| !p) {
+        transfers.push(Transfer {
+            kind: kind.to_owned(),
+            status: if effect.ok {
+                TransferStatus::Unmatched
+            } else {
+                TransferStatus::Failed
+            },
+            from: None,
+            to: effect.to,
+            source_chain: source_chain.clone(),
+            source_tx: None,
+            source_block: None,
+            sent: None,
+            expected: None,
+            dest_chain: dest_chain.clone(),
+            dest_tx: Some(effect.tx),
+            dest_block: Some(effect.block),
+            received: Some(effect.amount),
+            latency_secs: None,
+        });
+    }
+    transfers
+}
+
+/// How many of the latest blocks of each chain are scanned by default.
+pub const DEFAULT_LOOKBACK: u64 = 10_000;
+
+/// How many blocks to fetch logs for at once; nodes commonly cap the block
+/// range of `eth_getLogs`.
+pub const LOG_PAGE: u64 = 5_000;
+
+/// Which blocks to look for transfers in.
+#[derive(Debug, Clone, Args)]
+pub struct ScanArgs {
+    /// Only scan blocks from this one on (on both chains).
+    #[clap(long, conflicts_with = "lookback")]
+    pub since_block: Option<u64>,
+    /// Only scan this many of the latest blocks on each chain.
+    #[clap(long, default_value_t = DEFAULT_LOOKBACK)]
+    pub lookback: u64,
+}
+
+impl ScanArgs {
+    /// Scan every block.
+    pub fn all() -> Self {
+        Self {
+            since_block: Some(0),
+            lookback: DEFAULT_LOOKBACK,
+        }
+    }
+
+    /// The first block to scan on the chain behind `provider`.
+    pub async fn from_block<M>(&self, provider: &M) -> Result<u64>
+    where
+        M: Middleware,
+        M::Error: 'static,
+    {
+        Ok(match self.since_block {
+            Some(block) => block,
+            None => {
+                let latest = provider.get_block_number().await?.as_u64();
+                (latest + 1).saturating_sub(self.lookback)
+            }
+        })
+    }
+}
+
+/// The logs matching `filter` in blocks `from_block` through the latest one on
+/// the chain behind `provider` (fetched [`LOG_PAGE`] blocks at a time), as
+/// actions.  `decode` extracts the sender, recipient and amount from each log;
+/// the logs are of successful transactions only.
+pub async fn log_actions<M, F>(
+    provider: &M,
+    filter: &Filter,
+    from_block: u64,
+    decode: F,
+) -> Result<Vec<Action>>
+where
+    M: Middleware,
+    M::Error: 'static,
+    F: Fn(&Log) -> Result<(Address, Address, U256)>,
+{
+    let latest = provider.get_block_number().await?.as_u64();
+    let mut timestamps = HashMap::new();
+    let mut result = vec![];
+    let mut start = from_block;
+    while start <= latest {
+        let end = latest.min(start.saturating_add(LOG_PAGE - 1));
+        let page = filter.clone().from_block(start).to_block(end);
+        for log in provider.get_logs(&page).await? {
+            let block = log.block_number.unwrap_or_default().as_u64();
+            let timestamp = match timestamps.get(&block) {
+                Some(&t) => t,
+                None => {
+                    let t = block_timestamp(provider, block).await?;
+                    timestamps.insert(block, t);
+                    t
+                }
+            };
+            let (from, to, amount) = decode(&log)?;
+            result.push(Action {
+                tx: log.transaction_hash.unwrap_or_default(),
+                block,
+                timestamp,
+                from,
+                to,
+                amount,
+                ok: true,
+            });
+        }
+        start = end + 1;
+    }
+    Ok(result)
+}
+
+/// How many blocks [`reverted_calls`] fetches at once.
+pub const BLOCK_CONCURRENCY: usize = 16;
+
+/// A reverted transaction found by [`reverted_calls`].
+#[derive(Debug, Clone)]
+pub struct RevertedCall {
+    pub tx: Transaction,
+    /// Timestamp of the block, in seconds.
+    pub timestamp: u64,
+}
+
+impl RevertedCall {
+    /// Whether this is a call to the function with the given selector.
+    pub fn calls(&self, selector: [u8; 4]) -> bool {
+        self.tx.input.starts_with(&selector)
+    }
+
+    /// This call as an action (that failed) sending `amount` to `to`.
+    pub fn action(&self, to: Address, amount: U256) -> Action {
+        Action {
+            tx: self.tx.hash,
+            block: self.tx.block_number.unwrap_or_default().as_u64(),
+            timestamp: self.timestamp,
+            from: self.tx.from,
+            to,
+            amount,
+            ok: false,
+        }
+    }
+}
+
+/// The transactions to `contract` in blocks `from_block` through the latest one
+/// on the chain behind `provider` that reverted.  This fetches every block (up
+/// to [`BLOCK_CONCURRENCY`] at once) and the receipt of every call to
+/// `contract`, so it is much slower than [`log_actions`].
+pub async fn reverted_calls<M>(
+    provider: &M,
+    contract: Address,
+    from_block: u64,
+) -> Result<Vec<RevertedCall>>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    let latest = provider.get_block_number().await?.as_u64();
+    let mut blocks = stream::iter(from_block..=latest)
+        .map(|num| provider.get_block_with_txs(num))
+        .buffered(BLOCK_CONCURRENCY);
+
+    let mut result = vec![];
+    while let Some(block) = blocks.try_next().await? {
+        let Some(block) = block else { continue };
+        let timestamp = block.timestamp.as_u64();
+        for tx in block.transactions {
+            if tx.to != Some(contract) {
+                continue;
+            }
+            let receipt = provider.get_transaction_receipt(tx.hash).await?;
+            if receipt.and_then(|r| r.status) == Some(0.into()) {
+                result.push(RevertedCall { tx, timestamp });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The timestamp of block `num` (in seconds) on the chain behind `provider`.
+pub async fn block_timestamp<M>(provider: &M, num: u64) -> Result<u64>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    Ok(provider
+        .get_block(num)
+        .await?
+        .map(|b| b.timestamp.as_u64())
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use ethers::{
+        providers::{JsonRpcClient, MockError, Provider},
+        types::{Block, TransactionReceipt, U64},
+    };
+    use serde::de::DeserializeOwned;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn action(n: u64, block: u64, to: u8, amount: u64) -> Action {
+        Action {
+            tx: H256::from_low_u64_be(n),
+            block,
+            timestamp: block * 2,
+            from: Address::repeat_byte(0xff),
+            to: Address::repeat_byte(to),
+            amount: amount.into(),
+            ok: true,
+        }
+    }
+
+    fn pair(sources: Vec<Action>, effects: Vec<Action>) -> Vec<Transfer> {
+        // 10% fee
+        pair_transfers("buy", "ethereum", "polygon", sources, effects, |a| {
+            a * 9 / 10
+        })
+    }
+
+    fn statuses(transfers: &[Transfer]) -> Vec<TransferStatus> {
+        transfers.iter().map(|t| t.status).collect()
+    }
+
+    #[test]
+    fn pairs_sources_with_effects() {
+        let transfers = pair(
+            vec![action(1, 10, 1, 100), action(2, 11, 2, 200)],
+            vec![action(3, 15, 2, 180), action(4, 12, 1, 90)],
+        );
+        use TransferStatus::*;
+        assert_eq!(statuses(&transfers), [Delivered, Delivered]);
+        let t = &transfers[0];
+        assert_eq!(t.kind, "buy");
+        assert_eq!(
+            (t.source_tx, t.dest_tx),
+            (
+                Some(H256::from_low_u64_be(1)),
+                Some(H256::from_low_u64_be(4))
+            )
+        );
+        assert_eq!(
+            (t.sent, t.expected, t.received),
+            (Some(100.into()), Some(90.into()), Some(90.into()))
+        );
+        assert_eq!((t.source_block, t.dest_block), (Some(10), Some(12)));
+        assert_eq!(t.latency_secs, Some(4));
+        assert_eq!(transfers[1].latency_secs, Some(8));
+    }
+
+    #[test]
+    fn reports_pending_mismatched_and_unmatched() {
+        use TransferStatus::*;
+        let transfers = pair(
+            vec![action(1, 10, 1, 100), action(2, 10, 2, 100)],
+            vec![
+                // too early for the source
+                action(3, 9, 1, 90),
+                // wrong amount
+                action(4, 11, 2, 95),
+            ],
+        );
+        assert_eq!(statuses(&transfers), [Pending, Mismatched, Unmatched]);
+        assert_eq!(transfers[2].from, None);
+        assert_eq!(transfers[2].dest_tx, Some(H256::from_low_u64_be(3)));
+        assert_eq!(transfers[2].received, Some(90.into()));
+    }
+
+    #[test]
+    fn prefers_successful_effects_with_the_expected_amount() {
+        use TransferStatus::*;
+        let failed = Action {
+            ok: false,
+            ..action(3, 11, 1, 90)
+        };
+        let transfers = pair(
+            vec![action(1, 10, 1, 100)],
+            vec![failed.clone(), action(4, 12, 1, 80), action(5, 13, 1, 90)],
+        );
+        // the failed delivery left over is no unmatched effect
+        assert_eq!(statuses(&transfers), [Delivered, Failed, Unmatched]);
+        assert_eq!(transfers[0].dest_tx, Some(H256::from_low_u64_be(5)));
+        assert_eq!(transfers[1].source_tx, None);
+
+        // without a better candidate, a failed delivery is still a delivery attempt
+        let transfers = pair(vec![action(1, 10, 1, 100)], vec![failed]);
+        assert_eq!(statuses(&transfers), [Failed]);
+    }
+
+    #[test]
+    fn pairs_each_effect_once() {
+        use TransferStatus::*;
+        let transfers = pair(
+            vec![action(2, 11, 1, 100), action(1, 10, 1, 100)],
+            vec![action(3, 12, 1, 90)],
+        );
+        // the earlier source gets the effect
+        assert_eq!(statuses(&transfers), [Delivered, Pending]);
+        assert_eq!(transfers[0].source_tx, Some(H256::from_low_u64_be(1)));
+    }
+
+    #[test]
+    fn reports_reverted_sources() {
+        let reverted = Action {
+            ok: false,
+            ..action(1, 10, 1, 100)
+        };
+        let transfers = pair(vec![reverted], vec![action(3, 12, 1, 90)]);
+        use TransferStatus::*;
+        assert_eq!(statuses(&transfers), [Reverted, Unmatched]);
+        assert!(transfers[0].involves(Address::repeat_byte(0xff)));
+        assert!(transfers[0].involves(Address::repeat_byte(1)));
+        assert!(!transfers[1].involves(Address::repeat_byte(0xff)));
+    }
+
+    /// A chain with one log per block, recording the block ranges logs are
+    /// requested for.
+    #[derive(Debug)]
+    struct Chain {
+        latest: u64,
+        ranges: Mutex<Vec<(u64, u64)>>,
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for Chain {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, method: &str, params: T) -> Result<R, MockError>
+        where
+            T: fmt::Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            let params = serde_json::to_value(params)?;
+            let num = |v: &Value| {
+                U64::from_str_radix(v.as_str().unwrap(), 16)
+                    .unwrap()
+                    .as_u64()
+            };
+            let response = match method {
+                "eth_blockNumber" => json!(U64::from(self.latest)),
+                "eth_getBlockByNumber" => json!(Block::<H256> {
+                    timestamp: (num(&params[0]) * 2).into(),
+                    ..Default::default()
+                }),
+                "eth_getLogs" => {
+                    let (from, to) = (num(&params[0]["fromBlock"]), num(&params[0]["toBlock"]));
+                    self.ranges.lock().unwrap().push((from, to));
+                    let logs: Vec<Log> = (from..=to)
+                        .map(|b| Log {
+                            block_number: Some(b.into()),
+                            transaction_hash: Some(H256::from_low_u64_be(b)),
+                            topics: vec![H256::zero(), Address::repeat_byte(1).into()],
+                            data: vec![0; 32].into(),
+                            ..Default::default()
+                        })
+                        .collect();
+                    json!(logs)
+                }
+                _ => panic!("unexpected request {method}"),
+            };
+            Ok(serde_json::from_value(response)?)
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_logs_in_pages() {
+        let latest = 2 * LOG_PAGE + 10;
+        let provider = Provider::new(Chain {
+            latest,
+            ranges: Mutex::default(),
+        });
+        let scan = ScanArgs {
+            since_block: None,
+            lookback: DEFAULT_LOOKBACK,
+        };
+        let from = scan.from_block(&provider).await.unwrap();
+        assert_eq!(from, latest + 1 - DEFAULT_LOOKBACK);
+        assert_eq!(ScanArgs::all().from_block(&provider).await.unwrap(), 0);
+        let short = ScanArgs {
+            since_block: None,
+            lookback: 3 * LOG_PAGE,
+        };
+        assert_eq!(short.from_block(&provider).await.unwrap(), 0);
+
+        let actions = log_actions(&provider, &Filter::new(), from, |log| {
+            Ok((Address::zero(), Address::from(log.topics[1]), U256::from(7)))
+        })
+        .await
+        .unwrap();
+        assert_eq!(actions.len() as u64, DEFAULT_LOOKBACK);
+        let first = &actions[0];
+        assert_eq!((first.block, first.timestamp), (from, from * 2));
+        assert_eq!(first.tx, H256::from_low_u64_be(from));
+        assert_eq!(first.to, Address::repeat_byte(1));
+        assert_eq!(actions.last().unwrap().block, latest);
+
+        let ranges = provider.as_ref().ranges.lock().unwrap().clone();
+        assert_eq!(
+            ranges,
+            [(from, from + LOG_PAGE - 1), (from + LOG_PAGE, latest)]
+        );
+    }
+
+    /// A chain whose block `n` has a call to `CONTRACT` (which reverts in odd
+    /// blocks) and a transaction to another address, recording the receipts
+    /// requested.
+    #[derive(Debug, Default)]
+    struct Calls {
+        receipts: Mutex<Vec<H256>>,
+    }
+
+    const CONTRACT: Address = Address::repeat_byte(0xcc);
+
+    fn tx(block: u64, n: u64, to: Address) -> Transaction {
+        Transaction {
+            hash: H256::from_low_u64_be(block * 10 + n),
+            block_number: Some(block.into()),
+            from: Address::repeat_byte(0xff),
+            to: Some(to),
+            input: vec![1, 2, 3, 4, block as u8].into(),
+            ..Default::default()
+        }
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for Calls {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, method: &str, params: T) -> Result<R, MockError>
+        where
+            T: fmt::Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            let params = serde_json::to_value(params)?;
+            let response = match method {
+                "eth_blockNumber" => json!(U64::from(3)),
+                "eth_getBlockByNumber" => {
+                    let num = U64::from_str_radix(params[0].as_str().unwrap(), 16)
+                        .unwrap()
+                        .as_u64();
+                    json!(Block::<Transaction> {
+                        timestamp: (num * 2).into(),
+                        transactions: vec![tx(num, 1, CONTRACT), tx(num, 2, Address::zero())],
+                        ..Default::default()
+                    })
+                }
+                "eth_getTransactionReceipt" => {
+                    let hash: H256 = serde_json::from_value(params[0].clone())?;
+                    self.receipts.lock().unwrap().push(hash);
+                    let block = hash.to_low_u64_be() / 10;
+                    json!(TransactionReceipt {
+                        transaction_hash: hash,
+                        status: Some(U64::from((block + 1) % 2)),
+                        ..Default::default()
+                    })
+                }
+                _ => panic!("unexpected request {method}"),
+            };
+            Ok(serde_json::from_value(response)?)
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_reverted_calls() {
+        let provider = Provider::new(Calls::default());
+        let calls = reverted_calls(&provider, CONTRACT, 1).await.unwrap();
+        let hashes: Vec<_> = calls.iter().map(|c| c.tx.hash).collect();
+        assert_eq!(
+            hashes,
+            [H256::from_low_u64_be(11), H256::from_low_u64_be(31)]
+        );
+        // only calls to the contract are checked
+        let receipts = provider.as_ref().receipts.lock().unwrap().clone();
+        assert_eq!(receipts.len(), 3);
+
+        let call = &calls[1];
+        assert!(call.calls([1, 2, 3, 4]));
+        assert!(!call.calls([1, 2, 3, 5]));
+        let failed = call.action(Address::repeat_byte(1), 5.into());
+        assert_eq!((failed.block, failed.timestamp), (3, 6));
+        assert_eq!(failed.from, Address::repeat_byte(0xff));
+        assert!(!failed.ok);
+
+        // the failed delivery of a transfer
+        let transfers = pair(
+            vec![action(1, 1, 1, 100)],
+            vec![call.action(Address::repeat_byte(1), 90.into())],
+        );
+        assert_eq!(statuses(&transfers), [TransferStatus::Failed]);
+    }
+}
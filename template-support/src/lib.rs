@@ -0,0 +1,27 @@
+//! Support code shared by the Rust templates.
+//!
+//! Everything in here used to be copied between the `cli` and `main`
+//! binaries of the individual templates; keeping it in one crate means a
+//! fix (or a new feature) lands in every template at once.
+
+pub mod address;
+pub mod amount;
+pub mod batch;
+pub mod bench;
+pub mod bridge;
+pub mod config;
+pub mod deploy;
+pub mod doctor;
+pub mod gas;
+pub mod history;
+pub mod manifest;
+pub mod output;
+pub mod preflight;
+pub mod profile;
+pub mod receipt;
+pub mod relay;
+pub mod signers;
+pub mod style;
+pub mod table;
+pub mod testing;
+pub mod watch;
@@ -0,0 +1,536 @@
+//! Portable deployment manifests.
+//!
+//! `manifest export` describes a deployment in a single JSON file: every
+//! contract's name, chain, address, shims, constructor arguments, deploying
+//! account and deployment transaction.  The manifest also carries the contents
+//! of the deployment dir, which is what `::deployed()` reads, so that
+//! `manifest import` on another machine can restore them and the other
+//! commands see the same deployment, without redeploying anything.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use clap::Subcommand;
+use ethers::{
+    abi::{self, ParamType, Token},
+    providers::Middleware,
+    types::{Address, BlockId, Bytes, H256},
+};
+use eyre::{bail, ensure, eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deploy::clean_deploy_dir, receipt::format_token, s_action, s_contract, s_error, s_value,
+};
+
+/// Version of the manifest format written by [`Manifest::save`].
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The templates' `manifest` subcommands.
+#[derive(Debug, Subcommand)]
+pub enum ManifestCommand {
+    /// Write a manifest describing the current deployment.
+    Export {
+        /// Where to write the manifest (defaults to stdout).
+        #[clap(long, short)]
+        file: Option<PathBuf>,
+    },
+    /// Reuse the deployment described by a manifest, without redeploying anything.
+    #[clap(alias = "attach")]
+    Import {
+        /// The manifest to import.
+        file: PathBuf,
+        /// Replace an existing deployment.
+        #[clap(long)]
+        force: bool,
+    },
+}
+
+/// A deployed contract, as listed in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractEntry {
+    pub name: String,
+    /// Chain target, e.g., "ethereum".
+    pub target: String,
+    pub chain_id: u64,
+    pub address: Address,
+    /// Addresses of the contract's shims, by chain target.
+    pub shims: BTreeMap<String, Address>,
+    pub constructor_args: Vec<String>,
+    /// The account that deployed the contract, if known.
+    pub deployer: Option<Address>,
+    /// The deployment transaction, if known.
+    pub tx_hash: Option<H256>,
+}
+
+/// The transaction that created a contract.
+#[derive(Debug, Clone)]
+pub struct Creation {
+    pub tx_hash: H256,
+    pub deployer: Address,
+    /// Init code followed by the ABI-encoded constructor arguments.
+    pub input: Bytes,
+}
+
+impl ContractEntry {
+    /// Describe the contract `name` deployed at `address` on the chain behind
+    /// `provider`, whose constructor takes arguments of type `constructor`.
+    ///
+    /// The deploying account, deployment transaction and constructor arguments
+    /// come from the transaction that created the contract; if it cannot be
+    /// found (see [`find_creation`]), they are left empty, with a warning.
+    /// Shims are left for the caller to fill in.
+    pub async fn describe<M>(
+        name: impl Into<String>,
+        target: impl ToString,
+        provider: &M,
+        address: Address,
+        constructor: &[ParamType],
+    ) -> Result<Self>
+    where
+        M: Middleware,
+        M::Error: 'static,
+    {
+        let name = name.into();
+        let creation = find_creation(provider, address).await?;
+        let constructor_args = match &creation {
+            Some(creation) => match constructor_args(creation, constructor) {
+                Ok(args) => args.iter().map(format_token).collect(),
+                Err(e) => {
+                    warn(&format!(
+                        "{e}; leaving the constructor arguments of '{name}' empty"
+                    ));
+                    vec![]
+                }
+            },
+            None => {
+                warn(&format!(
+                    "Could not find the transaction that created '{name}' at {address:?} \
+                     (the node may not serve historical state); leaving its deployer, \
+                     deployment transaction and constructor arguments empty"
+                ));
+                vec![]
+            }
+        };
+        Ok(Self {
+            name,
+            target: target.to_string(),
+            chain_id: provider.get_chainid().await?.as_u64(),
+            address,
+            shims: BTreeMap::new(),
+            constructor_args,
+            deployer: creation.as_ref().map(|c| c.deployer),
+            tx_hash: creation.as_ref().map(|c| c.tx_hash),
+        })
+    }
+
+    /// Check that the chain behind `provider` is the one this contract was
+    /// deployed to, and that the contract is still there.
+    pub async fn verify<M>(&self, provider: &M) -> Result<()>
+    where
+        M: Middleware,
+        M::Error: 'static,
+    {
+        let chain_id = provider.get_chainid().await?.as_u64();
+        ensure!(
+            chain_id == self.chain_id,
+            "'{}' was deployed to chain {}, but '{}' is chain {chain_id}",
+            self.name,
+            self.chain_id,
+            self.target
+        );
+        ensure!(
+            !provider.get_code(self.address, None).await?.is_empty(),
+            "No code at {:?} on '{}' (chain {chain_id}), where '{}' should be",
+            self.address,
+            self.target,
+            self.name
+        );
+        Ok(())
+    }
+
+    /// Check that the contract's shim on `target` (the chain behind `provider`) is there.
+    pub async fn verify_shim<M>(&self, target: impl ToString, provider: &M) -> Result<()>
+    where
+        M: Middleware,
+        M::Error: 'static,
+    {
+        let target = target.to_string();
+        let addr = self
+            .shims
+            .get(&target)
+            .ok_or_else(|| eyre!("Manifest has no '{target}' shim for '{}'", self.name))?;
+        ensure!(
+            !provider.get_code(*addr, None).await?.is_empty(),
+            "No code at {addr:?} on '{target}', where the shim for '{}' should be",
+            self.name
+        );
+        Ok(())
+    }
+}
+
+/// Find the transaction that created the contract at `address`, if it was
+/// created directly (rather than by another contract).
+///
+/// Binary-searches for the block in which the contract's code appeared, which
+/// requires a node that serves historical state (as local dev nodes do); a node
+/// that doesn't reports no code in old blocks, so nothing is found.
+pub async fn find_creation<M>(provider: &M, address: Address) -> Result<Option<Creation>>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    let has_code = |block: u64| async move {
+        let code = provider
+            .get_code(address, Some(BlockId::Number(block.into())))
+            .await?;
+        Ok::<_, eyre::Report>(!code.is_empty())
+    };
+    let latest = provider.get_block_number().await?.as_u64();
+    if !has_code(latest).await? {
+        return Ok(None);
+    }
+    let (mut lo, mut hi) = (0, latest);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if has_code(mid).await? {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+
+    let Some(block) = provider.get_block_with_txs(lo).await? else {
+        return Ok(None);
+    };
+    for tx in block.transactions.into_iter().filter(|tx| tx.to.is_none()) {
+        let receipt = provider.get_transaction_receipt(tx.hash).await?;
+        if receipt.and_then(|r| r.contract_address) == Some(address) {
+            return Ok(Some(Creation {
+                tx_hash: tx.hash,
+                deployer: tx.from,
+                input: tx.input,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// Decode constructor arguments of the given types from the end of a creation
+/// transaction's input.
+///
+/// The arguments are appended to init code of unknown length, so this looks for
+/// the shortest suffix of the input that decodes to arguments which encode back
+/// to exactly that suffix.
+pub fn constructor_args(creation: &Creation, types: &[ParamType]) -> Result<Vec<Token>> {
+    let input = &creation.input;
+    (32 * types.len()..=input.len())
+        .step_by(32)
+        .find_map(|len| {
+            let suffix = &input[input.len() - len..];
+            let tokens = abi::decode(types, suffix).ok()?;
+            (abi::encode(&tokens) == suffix).then_some(tokens)
+        })
+        .ok_or_else(|| {
+            eyre!(
+                "Cannot decode constructor arguments {types:?} of {:?}",
+                creation.tx_hash
+            )
+        })
+}
+
+fn warn(msg: &str) {
+    eprintln!("{} {msg}", s_error!("Warning:"));
+}
+
+/// A deployment manifest; see the [module docs](self).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub contracts: Vec<ContractEntry>,
+    /// Contents of the deployment dir, by path relative to it.
+    pub deploy_dir: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// A manifest for `contracts`, including the contents of `deploy_dir`.
+    pub fn new(contracts: Vec<ContractEntry>, deploy_dir: &Path) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        read_dir_into(deploy_dir, deploy_dir, &mut files)
+            .context(format!("Reading deployment dir {}", deploy_dir.display()))?;
+        Ok(Self {
+            version: MANIFEST_VERSION,
+            contracts,
+            deploy_dir: files,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let ctx = || format!("Reading manifest {}", path.display());
+        let manifest: Self =
+            serde_json::from_str(&fs::read_to_string(path).with_context(ctx)?).with_context(ctx)?;
+        ensure!(
+            manifest.version == MANIFEST_VERSION,
+            "Unsupported manifest version {} (expected {MANIFEST_VERSION})",
+            manifest.version
+        );
+        Ok(manifest)
+    }
+
+    /// Write the manifest to `path`, or to stdout if there is none.
+    pub fn save(&self, path: Option<&Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)? + "\n";
+        match path {
+            Some(path) => {
+                fs::write(path, json).context(format!("Writing manifest {}", path.display()))?;
+                println!(
+                    "{} {} contracts to {}",
+                    s_action!("Exported"),
+                    self.contracts.len(),
+                    s_value!(path.display())
+                );
+            }
+            None => print!("{json}"),
+        }
+        Ok(())
+    }
+
+    /// The entry for contract `name`.
+    pub fn contract(&self, name: &str) -> Result<&ContractEntry> {
+        self.contracts
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| eyre!("Manifest has no entry for '{name}'"))
+    }
+
+    /// Replace the contents of `deploy_dir` with the manifest's, so that
+    /// `::deployed()` finds the manifest's contracts.
+    pub fn restore_deploy_dir(&self, deploy_dir: &Path) -> Result<()> {
+        for rel in self.deploy_dir.keys() {
+            let path = Path::new(rel);
+            if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+                bail!("Invalid path in manifest: {rel}");
+            }
+        }
+        clean_deploy_dir(deploy_dir)?;
+        for (rel, contents) in &self.deploy_dir {
+            let path = deploy_dir.join(rel);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, contents).context(format!("Writing {}", path.display()))?;
+        }
+        for c in &self.contracts {
+            println!(
+                "{} {} on {} at {}",
+                s_action!("Attached"),
+                s_contract!(&c.name),
+                s_value!(&c.target),
+                s_value!(format!("{:?}", c.address))
+            );
+        }
+        Ok(())
+    }
+}
+
+fn read_dir_into(root: &Path, dir: &Path, files: &mut BTreeMap<String, String>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            read_dir_into(root, &path, files)?;
+        } else {
+            let rel = path
+                .strip_prefix(root)?
+                .to_string_lossy()
+                .replace('\\', "/");
+            let contents =
+                fs::read_to_string(&path).context(format!("Reading {}", path.display()))?;
+            files.insert(rel, contents);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use ethers::{
+        providers::{JsonRpcClient, MockError, Provider},
+        types::{Block, Transaction, TransactionReceipt, U256, U64},
+    };
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const INIT_CODE: &[u8] = &[0x60, 0x80, 0x60, 0x40, 0x52, 0x34, 0x80, 0x15, 0x61];
+
+    fn creation(args: &[Token]) -> Creation {
+        Creation {
+            tx_hash: H256::repeat_byte(7),
+            deployer: Address::repeat_byte(8),
+            input: [INIT_CODE, &abi::encode(args)].concat().into(),
+        }
+    }
+
+    #[test]
+    fn decodes_static_constructor_args() {
+        let args = [
+            Token::Uint(42.into()),
+            Token::Address(Address::repeat_byte(1)),
+        ];
+        let types = [ParamType::Uint(256), ParamType::Address];
+        assert_eq!(constructor_args(&creation(&args), &types).unwrap(), args);
+    }
+
+    #[test]
+    fn decodes_dynamic_constructor_args() {
+        let args = [
+            Token::String("FooBarBaz".into()),
+            Token::String("FBB".into()),
+            Token::Address(Address::repeat_byte(1)),
+        ];
+        let types = [ParamType::String, ParamType::String, ParamType::Address];
+        let decoded = constructor_args(&creation(&args), &types).unwrap();
+        assert_eq!(decoded, args);
+        assert_eq!(
+            decoded.iter().map(format_token).collect::<Vec<_>>(),
+            [
+                "FooBarBaz".to_owned(),
+                "FBB".to_owned(),
+                format!("{:?}", Address::repeat_byte(1))
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_constructor_args() {
+        let types = [ParamType::String, ParamType::Address];
+        assert!(constructor_args(&creation(&[]), &types).is_err());
+    }
+
+    /// A chain on which the contract at [`Chain::contract`] was created in
+    /// block [`Chain::created`], optionally without historical state.
+    #[derive(Debug)]
+    struct Chain {
+        latest: u64,
+        created: u64,
+        historical_state: bool,
+        contract: Address,
+    }
+
+    impl Chain {
+        fn new(latest: u64, created: u64, historical_state: bool) -> Self {
+            Self {
+                latest,
+                created,
+                historical_state,
+                contract: Address::repeat_byte(9),
+            }
+        }
+
+        fn respond(&self, method: &str, params: Value) -> Value {
+            let block = |v: &Value| {
+                u64::from_str_radix(v.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+            };
+            match method {
+                "eth_chainId" => json!(U256::from(31337)),
+                "eth_blockNumber" => json!(U64::from(self.latest)),
+                "eth_getCode" => {
+                    let b = block(&params[1]);
+                    let visible = b >= self.created && (self.historical_state || b == self.latest);
+                    json!(if visible { "0x6080" } else { "0x" })
+                }
+                "eth_getBlockByNumber" => {
+                    let b = block(&params[0]);
+                    let tx = |nonce: u64, to: Option<Address>| Transaction {
+                        hash: H256::from_low_u64_be(b * 10 + nonce),
+                        nonce: nonce.into(),
+                        from: Address::repeat_byte(8),
+                        to,
+                        input: vec![0x60, 0x80].into(),
+                        ..Default::default()
+                    };
+                    json!(Block {
+                        number: Some(b.into()),
+                        transactions: vec![tx(0, Some(Address::zero())), tx(1, None), tx(2, None)],
+                        ..Default::default()
+                    })
+                }
+                "eth_getTransactionReceipt" => {
+                    let hash: H256 = serde_json::from_value(params[0].clone()).unwrap();
+                    let created = hash == H256::from_low_u64_be(self.created * 10 + 2);
+                    json!(TransactionReceipt {
+                        transaction_hash: hash,
+                        contract_address: Some(if created {
+                            self.contract
+                        } else {
+                            Address::repeat_byte(2)
+                        }),
+                        ..Default::default()
+                    })
+                }
+                _ => panic!("unexpected request {method}"),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for Chain {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, method: &str, params: T) -> Result<R, MockError>
+        where
+            T: std::fmt::Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            let params = serde_json::to_value(params)?;
+            Ok(serde_json::from_value(self.respond(method, params))?)
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_creation() {
+        for (latest, created) in [(0, 0), (100, 0), (100, 37), (100, 100), (1, 1)] {
+            let chain = Chain::new(latest, created, true);
+            let contract = chain.contract;
+            let provider = Provider::new(chain);
+            let found = find_creation(&provider, contract).await.unwrap().unwrap();
+            assert_eq!(found.tx_hash, H256::from_low_u64_be(created * 10 + 2));
+            assert_eq!(found.deployer, Address::repeat_byte(8));
+
+            let entry = ContractEntry::describe("C", "ethereum", &provider, contract, &[])
+                .await
+                .unwrap();
+            assert_eq!(entry.tx_hash, Some(found.tx_hash));
+            assert_eq!(entry.chain_id, 31337);
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_nothing_without_historical_state() {
+        let chain = Chain::new(100, 37, false);
+        let contract = chain.contract;
+        let provider = Provider::new(chain);
+        assert!(find_creation(&provider, contract).await.unwrap().is_none());
+
+        let entry = ContractEntry::describe("C", "ethereum", &provider, contract, &[])
+            .await
+            .unwrap();
+        assert_eq!((entry.deployer, entry.tx_hash), (None, None));
+        assert!(entry.constructor_args.is_empty());
+
+        // code that no transaction created, i.e., created by another contract
+        let other = Provider::new(Chain::new(100, 37, true));
+        assert!(find_creation(&other, Address::repeat_byte(1))
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
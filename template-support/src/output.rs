@@ -0,0 +1,83 @@
+//! Machine-readable output.
+//!
+//! Commands that list accounts or contracts (e.g., `balances`, `list`)
+//! collect their data as [`Record`]s, which are then either rendered as
+//! tables (the default) or emitted as JSON or CSV.
+
+use std::io;
+
+use clap::ValueEnum;
+use ethers::types::{Address, U256};
+use eyre::{bail, Result};
+use serde::{Serialize, Serializer};
+
+/// Output format selected with the global `--output` flag.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable tables.
+    #[default]
+    Table,
+    /// A JSON array of records.
+    Json,
+    /// CSV with a header row.
+    Csv,
+}
+
+/// A single row of output: an account or a contract on some chain.
+///
+/// Fields that don't apply to a particular command are left empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Record {
+    /// Chain target, e.g., "ethereum".
+    pub target: String,
+    /// Index of the account on its chain (usable wherever an address is expected).
+    pub index: Option<usize>,
+    /// Contract name (or shim name), if this is not a plain account.
+    pub label: Option<String>,
+    pub address: Option<Address>,
+    /// Native balance.
+    #[serde(serialize_with = "ser_decimal")]
+    pub wei: Option<U256>,
+    /// FBB token balance.
+    #[serde(serialize_with = "ser_decimal")]
+    pub fbb: Option<U256>,
+    /// Value stored in a contract.
+    #[serde(serialize_with = "ser_decimal")]
+    pub value: Option<U256>,
+}
+
+/// Serialize amounts as decimal strings (rather than `U256`'s default hex),
+/// so they match what the tables show.
+pub(crate) fn ser_decimal<S: Serializer>(val: &Option<U256>, s: S) -> Result<S::Ok, S::Error> {
+    match val {
+        Some(v) => s.serialize_str(&v.to_string()),
+        None => s.serialize_none(),
+    }
+}
+
+/// Serialize an amount as a decimal string, like [`Record`]'s amounts.
+pub fn ser_amount<S: Serializer>(val: &U256, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&val.to_string())
+}
+
+/// Write `records` (typically [`Record`]s) to stdout as JSON or CSV.
+///
+/// Fails with [`OutputFormat::Table`]; tables are rendered by the commands
+/// themselves.
+pub fn print_records<T: Serialize>(format: OutputFormat, records: &[T]) -> Result<()> {
+    match format {
+        OutputFormat::Table => bail!("Records cannot be printed as a table"),
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(io::stdout().lock(), records)?;
+            println!();
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout().lock());
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
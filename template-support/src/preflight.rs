@@ -0,0 +1,230 @@
+//! Simulating transactions before sending them.
+//!
+//! State-changing commands call [`simulate`] before sending a transaction: it
+//! runs the call with `eth_call` and estimates its gas, so a call that would
+//! revert fails with a [`Rejected`] error naming the contract, the function
+//! and the decoded revert reason, without anything being signed or sent.
+
+use ethers::{
+    abi::{self, Abi, ParamType, Token},
+    contract::builders::ContractCall,
+    providers::{HttpClientError, Middleware, ProviderError},
+    types::{Bytes, U256},
+};
+use eyre::Result;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{receipt::format_token, s_action, s_contract, s_value};
+
+/// Selector of `Error(string)`, used by `require` and `revert` with a message.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of `Panic(uint256)`, used by failed `assert`s, overflows, etc.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// A simulated call failed.
+#[derive(Debug, Error)]
+#[error("{contract}.{function} would fail: {reason}")]
+pub struct Rejected {
+    pub contract: String,
+    pub function: String,
+    pub reason: String,
+}
+
+/// Simulate `call` to contract `contract` (whose ABI, used to decode custom
+/// errors, is `abi`) as sent by `client`, and estimate its gas.
+pub async fn simulate<C, M, D>(
+    client: &C,
+    contract: &str,
+    abi: &Abi,
+    call: &ContractCall<M, D>,
+) -> Result<U256>
+where
+    C: Middleware,
+{
+    // go straight to the provider, whose errors carry the revert data; the
+    // sender is set here, like the client would
+    let mut tx = call.tx.clone();
+    if tx.from().is_none() {
+        if let Some(from) = client.default_sender() {
+            tx.set_from(from);
+        }
+    }
+    let provider = client.provider();
+    let simulated = match provider.call(&tx, call.block).await {
+        Ok(_) => provider.estimate_gas(&tx, call.block).await,
+        Err(e) => Err(e),
+    };
+    let gas = simulated.map_err(|e| match rpc_error(&e) {
+        Some((message, data)) => Rejected {
+            contract: contract.to_owned(),
+            function: call.function.name.clone(),
+            reason: data
+                .map(|data| decode_revert(abi, &data))
+                .unwrap_or(message),
+        }
+        .into(),
+        None => eyre::Report::from(e),
+    })?;
+    eprintln!(
+        "{} {}.{}: {} gas",
+        s_action!("Simulated"),
+        s_contract!(contract),
+        call.function.name,
+        s_value!(gas)
+    );
+    Ok(gas)
+}
+
+/// The message and revert data (if any) of a JSON-RPC error response.
+fn rpc_error(e: &ProviderError) -> Option<(String, Option<Bytes>)> {
+    let ProviderError::JsonRpcClientError(e) = e else {
+        return None;
+    };
+    let Some(HttpClientError::JsonRpcError(e)) = e.downcast_ref() else {
+        return None;
+    };
+    // nodes return the revert data either directly or nested in an object
+    let data = match &e.data {
+        Some(Value::String(s)) => Some(s.as_str()),
+        Some(Value::Object(obj)) => obj.get("data").and_then(Value::as_str),
+        _ => None,
+    };
+    Some((e.message.clone(), data.and_then(|d| d.parse().ok())))
+}
+
+/// A human-readable description of revert data.
+pub fn decode_revert(abi: &Abi, data: &[u8]) -> String {
+    if data.is_empty() {
+        return "reverted without a reason".to_owned();
+    }
+    let (selector, args) = data.split_at(data.len().min(4));
+    if selector == ERROR_SELECTOR {
+        if let Ok(Some(Token::String(reason))) =
+            abi::decode(&[ParamType::String], args).map(|t| t.into_iter().next())
+        {
+            return reason;
+        }
+    } else if selector == PANIC_SELECTOR {
+        if let Ok(Some(Token::Uint(code))) =
+            abi::decode(&[ParamType::Uint(256)], args).map(|t| t.into_iter().next())
+        {
+            return format!("panic: {} ({code:#x})", panic_reason(code));
+        }
+    } else {
+        for error in abi.errors() {
+            if error.signature()[..4] == *selector {
+                if let Ok(tokens) = error.decode(args) {
+                    let args: Vec<_> = tokens.iter().map(format_token).collect();
+                    return format!("{}({})", error.name, args.join(", "));
+                }
+            }
+        }
+    }
+    format!("reverted with data {}", Bytes::from(data.to_vec()))
+}
+
+/// See <https://docs.soliditylang.org/en/latest/control-structures.html#panic-via-assert-and-error-via-require>.
+fn panic_reason(code: U256) -> &'static str {
+    match code.low_u64() {
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array",
+        0x31 => "pop from an empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to an uninitialized function",
+        _ => "unknown panic",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Address;
+
+    use super::*;
+
+    fn revert(selector: [u8; 4], args: &[Token]) -> Vec<u8> {
+        [&selector[..], &abi::encode(args)].concat()
+    }
+
+    fn abi() -> Abi {
+        serde_json::from_str(
+            r#"[{
+                "type": "error",
+                "name": "Unauthorized",
+                "inputs": [
+                    { "name": "caller", "type": "address" },
+                    { "name": "amount", "type": "uint256" }
+                ]
+            }]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn uses_the_standard_selectors() {
+        assert_eq!(ERROR_SELECTOR, ethers::utils::id("Error(string)"));
+        assert_eq!(PANIC_SELECTOR, ethers::utils::id("Panic(uint256)"));
+    }
+
+    #[test]
+    fn decodes_error_strings() {
+        let data = revert(
+            ERROR_SELECTOR,
+            &[Token::String(
+                "Bridge error: transaction size too small".to_owned(),
+            )],
+        );
+        assert_eq!(
+            decode_revert(&abi(), &data),
+            "Bridge error: transaction size too small"
+        );
+    }
+
+    #[test]
+    fn decodes_panics() {
+        let data = revert(PANIC_SELECTOR, &[Token::Uint(0x11.into())]);
+        assert_eq!(
+            decode_revert(&abi(), &data),
+            "panic: arithmetic overflow or underflow (0x11)"
+        );
+        let data = revert(PANIC_SELECTOR, &[Token::Uint(0x99.into())]);
+        assert_eq!(decode_revert(&abi(), &data), "panic: unknown panic (0x99)");
+    }
+
+    #[test]
+    fn decodes_custom_errors_from_the_abi() {
+        let abi = abi();
+        let caller = Address::repeat_byte(0xab);
+        let selector = abi.error("Unauthorized").unwrap().signature()[..4]
+            .try_into()
+            .unwrap();
+        let data = revert(selector, &[Token::Address(caller), Token::Uint(5.into())]);
+        assert_eq!(
+            decode_revert(&abi, &data),
+            format!("Unauthorized({caller:?}, 5)")
+        );
+    }
+
+    #[test]
+    fn describes_empty_and_unknown_data() {
+        assert_eq!(decode_revert(&abi(), &[]), "reverted without a reason");
+        assert_eq!(
+            decode_revert(&abi(), &[0xde, 0xad, 0xbe, 0xef, 0x01]),
+            "reverted with data 0xdeadbeef01"
+        );
+        // too short for a selector
+        assert_eq!(
+            decode_revert(&abi(), &[0x08, 0xc3]),
+            "reverted with data 0x08c3"
+        );
+        // a known selector with malformed arguments
+        assert_eq!(
+            decode_revert(&abi(), &ERROR_SELECTOR),
+            "reverted with data 0x08c379a0"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Selecting the network profile.
+//!
+//! A config file can define several `network_profiles` (e.g., local nodes, a
+//! shared staging cluster, a fork); the Cubist SDK uses the one named by its
+//! `current_network_profile`, `default` unless set.  [`ProfileArgs`] selects
+//! another one (`--profile` or [`PROFILE_ENV`]) without editing the config:
+//! it writes a copy of the config (with all paths made absolute) that selects
+//! the profile, and points the `CUBIST_CONFIG` environment variable at it.
+//!
+//! Each profile other than the config's own deploys into its own directory
+//! (`deploy-<profile>` next to `deploy`), so deployments on different
+//! networks don't mix.  To run the relayer for such a profile, point
+//! `CUBIST_CONFIG` at the copy when running `cubist start`; its path is shown
+//! in the header.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use eyre::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::{
+    config::{copy_config, read_config, CONFIG_ENV, CONFIG_FILE},
+    s_header, s_value,
+};
+
+/// Environment variable selecting the network profile, like `--profile`.
+pub const PROFILE_ENV: &str = "CUBIST_PROFILE";
+
+/// The profile the SDK uses when the config does not name one.
+pub(crate) const DEFAULT_PROFILE: &str = "default";
+
+/// Command-line argument selecting the network profile.
+#[derive(Debug, Clone, Args)]
+pub struct ProfileArgs {
+    /// Network profile to use (one of 'network_profiles' in cubist-config.json).
+    /// Defaults to the config's 'current_network_profile'.
+    #[clap(long, global = true, env = PROFILE_ENV)]
+    pub profile: Option<String>,
+}
+
+impl ProfileArgs {
+    /// Select the profile for the project in `project_dir` (typically
+    /// `env!("CARGO_MANIFEST_DIR")`) and print it as the command's header.
+    /// Must be called before the Cubist SDK loads the project, and before any
+    /// other threads start (e.g., those of a multi-threaded tokio runtime),
+    /// since it may set `CUBIST_CONFIG`.  Returns the name of the active
+    /// profile.
+    pub fn select(&self, project_dir: impl AsRef<Path>) -> Result<String> {
+        let (src, config) = read_config(project_dir)?;
+        let current = current_of(&config);
+
+        let name = self.profile.as_deref().unwrap_or(current);
+        let profiles = config["network_profiles"].as_object();
+        if !profiles.is_some_and(|p| p.contains_key(name)) {
+            let known: Vec<_> = profiles.into_iter().flat_map(|p| p.keys()).collect();
+            bail!(
+                "No network profile '{name}' in {}; known profiles: {known:?}",
+                src.display()
+            );
+        }
+        if name == current {
+            print_header(name, None);
+            return Ok(name.to_owned());
+        }
+
+        let dest = profile_config(&src, &config, name);
+        copy_config(&src, &dest, |obj| {
+            let deploy_dir = obj
+                .get("deploy_dir")
+                .and_then(Value::as_str)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| src.with_file_name("deploy"));
+            let mut file_name = deploy_dir.file_name().unwrap_or_default().to_owned();
+            file_name.push(format!("-{name}"));
+            let deploy_dir = deploy_dir.with_file_name(file_name);
+            obj.insert("current_network_profile".into(), name.into());
+            obj.insert("deploy_dir".into(), deploy_dir.to_string_lossy().into());
+        })
+        .context(format!("Writing the config for network profile '{name}'"))?;
+        env::set_var(CONFIG_ENV, &dest);
+        print_header(name, Some(&dest));
+        Ok(name.to_owned())
+    }
+}
+
+/// The network profile the Cubist SDK uses for the project in `project_dir`
+/// (after [`ProfileArgs::select`], the selected one).
+pub fn current_profile(project_dir: impl AsRef<Path>) -> Result<String> {
+    let (_, config) = read_config(project_dir)?;
+    Ok(current_of(&config).to_owned())
+}
+
+fn current_of(config: &Value) -> &str {
+    config["current_network_profile"]
+        .as_str()
+        .unwrap_or(DEFAULT_PROFILE)
+}
+
+/// Where the copy of config file `src` (holding `config`) selecting `profile`
+/// goes: under the config's build directory.
+fn profile_config(src: &Path, config: &Value, profile: &str) -> PathBuf {
+    let dir = src.parent().unwrap_or(Path::new("."));
+    let build_dir = config["build_dir"].as_str().unwrap_or("build");
+    dir.join(build_dir)
+        .join("profiles")
+        .join(profile)
+        .join(CONFIG_FILE)
+}
+
+/// Printed to stderr, so that JSON and CSV output stays parseable.
+fn print_header(profile: &str, config: Option<&Path>) {
+    let config = config
+        .map(|c| format!(" ({})", s_value!(c.display())))
+        .unwrap_or_default();
+    eprintln!("{} {}{config}", s_header!("profile"), s_value!(profile));
+}
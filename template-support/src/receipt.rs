@@ -0,0 +1,485 @@
+//! Reporting the receipts of sent transactions.
+//!
+//! After a command sends a transaction, it prints a [`Receipt`]: the
+//! transaction's hash, block, status, gas used, effective gas price and cost,
+//! and the events the called contract emitted, decoded with its ABI.  Like
+//! listings, receipts are printed as a table or as JSON or CSV (`--output`);
+//! the messages leading up to them go to stderr, so that JSON and CSV output
+//! stays parseable.
+//!
+//! [`send_call`] sends a transaction the way every command does: simulating it
+//! first ([`crate::preflight`]), applying the gas settings ([`crate::gas`]) and
+//! signing it with the selected signer ([`crate::signers`]).
+
+use std::{fmt, time::Instant};
+
+use ethers::{
+    abi::{Abi, Detokenize, RawLog, Token},
+    contract::builders::ContractCall,
+    providers::Middleware,
+    types::{Log, TransactionReceipt, H256, U256},
+    utils::format_units,
+};
+use eyre::{bail, Result};
+use serde::Serialize;
+
+use crate::{
+    gas::GasPolicy,
+    output::{print_records, ser_decimal, OutputFormat},
+    preflight::simulate,
+    s_contract, s_error, s_header, s_value,
+    signers::ChainSigner,
+    table::{Column, Table},
+};
+
+/// Status of a mined transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxStatus {
+    Success,
+    Reverted,
+}
+
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            TxStatus::Success => "success",
+            TxStatus::Reverted => "reverted",
+        })
+    }
+}
+
+/// An event emitted by the called contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Event {
+    /// The event's name, or `None` if the contract's ABI has no such event.
+    pub name: Option<String>,
+    /// The event's arguments, in order; the raw topics and data if the event
+    /// could not be decoded.
+    pub args: Vec<EventArg>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EventArg {
+    pub name: String,
+    pub value: String,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args: Vec<_> = self
+            .args
+            .iter()
+            .map(|a| format!("{}={}", a.name, a.value))
+            .collect();
+        let name = self.name.as_deref().unwrap_or("unknown");
+        write!(f, "{name}({})", args.join(", "))
+    }
+}
+
+/// What a command reports about a transaction it sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Receipt {
+    pub chain: String,
+    pub contract: String,
+    pub function: String,
+    pub tx: H256,
+    pub block: Option<u64>,
+    pub status: TxStatus,
+    #[serde(serialize_with = "ser_decimal")]
+    pub gas_used: Option<U256>,
+    /// Price paid per unit of gas, in wei.
+    #[serde(serialize_with = "ser_decimal")]
+    pub effective_gas_price: Option<U256>,
+    /// Gas used times the effective gas price, in wei.
+    #[serde(serialize_with = "ser_decimal")]
+    pub cost: Option<U256>,
+    pub events: Vec<Event>,
+}
+
+impl Receipt {
+    /// The report for `receipt`, of a call to `function` of contract `contract`
+    /// (with ABI `abi`) on `chain`.
+    pub fn new(
+        chain: impl ToString,
+        contract: &str,
+        abi: &Abi,
+        function: &str,
+        receipt: &TransactionReceipt,
+    ) -> Self {
+        let gas_used = receipt.gas_used;
+        let effective_gas_price = receipt.effective_gas_price;
+        let events = receipt
+            .logs
+            .iter()
+            .filter(|log| Some(log.address) == receipt.to)
+            .map(|log| decode_event(abi, log))
+            .collect();
+        Self {
+            chain: chain.to_string(),
+            contract: contract.to_owned(),
+            function: function.to_owned(),
+            tx: receipt.transaction_hash,
+            block: receipt.block_number.map(|b| b.as_u64()),
+            status: match receipt.status.map(|s| s.as_u64()) {
+                Some(0) => TxStatus::Reverted,
+                _ => TxStatus::Success,
+            },
+            gas_used,
+            effective_gas_price,
+            cost: gas_used
+                .zip(effective_gas_price)
+                .map(|(gas, price)| gas * price),
+            events,
+        }
+    }
+
+    /// Print the receipt in `format`; fails if the transaction reverted.
+    pub fn print(&self, format: OutputFormat) -> Result<()> {
+        match format {
+            OutputFormat::Table => self.print_table()?,
+            OutputFormat::Json => print_records(format, std::slice::from_ref(self))?,
+            // CSV has no nested records, so events are flattened into one cell
+            OutputFormat::Csv => print_records(format, &[CsvReceipt::from(self)])?,
+        }
+        if self.status == TxStatus::Reverted {
+            bail!(
+                "Transaction {:?} calling {}.{} reverted",
+                self.tx,
+                self.contract,
+                self.function
+            );
+        }
+        Ok(())
+    }
+
+    fn print_table(&self) -> Result<()> {
+        let none = || "-".to_owned();
+        let amount = |a: Option<U256>, unit: &str| {
+            a.map(|a| format!("{a} wei ({} {unit})", format_amount(a, unit)))
+                .unwrap_or_else(none)
+        };
+        let status: Box<dyn fmt::Display> = match self.status {
+            TxStatus::Success => Box::new(s_value!(self.status)),
+            TxStatus::Reverted => Box::new(s_error!(self.status)),
+        };
+
+        println!();
+        let mut table = Table::new(
+            &[Column::left(14), Column::left(66)],
+            &[
+                &s_header!("receipt"),
+                &s_contract!(format!("{}.{}", self.contract, self.function)),
+            ],
+        )?;
+        table
+            .row(&[&"chain", &s_value!(&self.chain)])?
+            .row(&[&"tx", &s_value!(format!("{:?}", self.tx))])?
+            .row(&[
+                &"block",
+                &s_value!(self.block.map(|b| b.to_string()).unwrap_or_else(none)),
+            ])?
+            .row(&[&"status", &status])?
+            .row(&[
+                &"gas used",
+                &s_value!(self.gas_used.map(|g| g.to_string()).unwrap_or_else(none)),
+            ])?
+            .row(&[
+                &"gas price",
+                &s_value!(amount(self.effective_gas_price, "gwei")),
+            ])?
+            .row(&[&"cost", &s_value!(amount(self.cost, "ether"))])?;
+        for event in &self.events {
+            table.row(&[
+                &"event",
+                &s_contract!(event.name.as_deref().unwrap_or("unknown")),
+            ])?;
+            for arg in &event.args {
+                table.row(&[&format!("  {}", arg.name), &s_value!(&arg.value)])?;
+            }
+        }
+        table.print();
+        println!();
+        Ok(())
+    }
+}
+
+/// Send `call` to `contract` (with ABI `abi`) on `chain` through `client`,
+/// signed by `signer`: simulate it, apply the gas `policy` and wait for it to be
+/// mined.  Returns its receipt and when it was sent.
+pub async fn send_call<C, M, D>(
+    client: &C,
+    chain: impl fmt::Display,
+    contract: &str,
+    abi: &Abi,
+    call: &mut ContractCall<M, D>,
+    signer: &ChainSigner,
+    policy: &GasPolicy,
+) -> Result<(Receipt, Instant)>
+where
+    C: Middleware,
+    C::Error: 'static,
+    C::Provider: Clone + 'static,
+    M: Middleware + 'static,
+    D: Detokenize,
+{
+    let gas = simulate(client, contract, abi, call).await?;
+    policy.prepare(client, &chain, &mut call.tx, gas).await?;
+    let sent_at = Instant::now();
+    let receipt = signer.send(client, call).await?;
+    let receipt = Receipt::new(chain, contract, abi, &call.function.name, &receipt);
+    Ok((receipt, sent_at))
+}
+
+/// A [`Receipt`] as a CSV row.
+#[derive(Debug, Serialize)]
+struct CsvReceipt<'a> {
+    chain: &'a str,
+    contract: &'a str,
+    function: &'a str,
+    tx: H256,
+    block: Option<u64>,
+    status: TxStatus,
+    #[serde(serialize_with = "ser_decimal")]
+    gas_used: Option<U256>,
+    #[serde(serialize_with = "ser_decimal")]
+    effective_gas_price: Option<U256>,
+    #[serde(serialize_with = "ser_decimal")]
+    cost: Option<U256>,
+    /// Events, separated by semicolons.
+    events: String,
+}
+
+impl<'a> From<&'a Receipt> for CsvReceipt<'a> {
+    fn from(r: &'a Receipt) -> Self {
+        let events: Vec<_> = r.events.iter().map(ToString::to_string).collect();
+        Self {
+            chain: &r.chain,
+            contract: &r.contract,
+            function: &r.function,
+            tx: r.tx,
+            block: r.block,
+            status: r.status,
+            gas_used: r.gas_used,
+            effective_gas_price: r.effective_gas_price,
+            cost: r.cost,
+            events: events.join("; "),
+        }
+    }
+}
+
+/// Decode `log` with the events in `abi`.
+fn decode_event(abi: &Abi, log: &Log) -> Event {
+    let raw = RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    };
+    let decoded = log.topics.first().and_then(|topic| {
+        abi.events()
+            .filter(|e| !e.anonymous && e.signature() == *topic)
+            .find_map(|e| Some((e, e.parse_log(raw.clone()).ok()?)))
+    });
+    match decoded {
+        Some((event, parsed)) => Event {
+            name: Some(event.name.clone()),
+            args: parsed
+                .params
+                .into_iter()
+                .map(|p| EventArg {
+                    name: p.name,
+                    value: format_token(&p.value),
+                })
+                .collect(),
+        },
+        None => {
+            let topics = log.topics.iter().enumerate().map(|(i, t)| EventArg {
+                name: format!("topic{i}"),
+                value: format!("{t:?}"),
+            });
+            let data = EventArg {
+                name: "data".to_owned(),
+                value: log.data.to_string(),
+            };
+            Event {
+                name: None,
+                args: topics.chain([data]).collect(),
+            }
+        }
+    }
+}
+
+/// Format `token` like the rest of the output: addresses and hashes as `0x...`
+/// hex, numbers in decimal.
+pub(crate) fn format_token(token: &Token) -> String {
+    match token {
+        Token::Address(a) => format!("{a:?}"),
+        Token::Uint(n) => n.to_string(),
+        Token::Int(n) => ethers::types::I256::from_raw(*n).to_string(),
+        Token::Bool(b) => b.to_string(),
+        Token::String(s) => s.clone(),
+        Token::Bytes(b) | Token::FixedBytes(b) => format!("0x{}", ethers::utils::hex::encode(b)),
+        Token::Array(ts) | Token::FixedArray(ts) | Token::Tuple(ts) => {
+            let ts: Vec<_> = ts.iter().map(format_token).collect();
+            format!("[{}]", ts.join(", "))
+        }
+    }
+}
+
+/// `wei` in `unit` (e.g., "gwei"), without trailing zeros.
+pub(crate) fn format_amount(wei: U256, unit: &str) -> String {
+    match format_units(wei, unit) {
+        Ok(s) if s.contains('.') => s.trim_end_matches('0').trim_end_matches('.').to_owned(),
+        Ok(s) => s,
+        Err(_) => "?".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::{
+        abi::{self, AbiEncode},
+        types::Address,
+    };
+
+    use super::*;
+
+    fn abi() -> Abi {
+        serde_json::from_str(
+            r#"[
+                {
+                    "type": "event",
+                    "name": "Transfer",
+                    "anonymous": false,
+                    "inputs": [
+                        { "name": "from", "type": "address", "indexed": true },
+                        { "name": "to", "type": "address", "indexed": true },
+                        { "name": "value", "type": "uint256", "indexed": false }
+                    ]
+                },
+                {
+                    "type": "event",
+                    "name": "Stored",
+                    "anonymous": true,
+                    "inputs": [{ "name": "value", "type": "uint256", "indexed": false }]
+                }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn log(topics: Vec<H256>, data: Vec<u8>) -> Log {
+        Log {
+            topics,
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    fn arg(name: &str, value: impl ToString) -> EventArg {
+        EventArg {
+            name: name.to_owned(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn decodes_known_events() {
+        let abi = abi();
+        let (from, to) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let transfer = abi.event("Transfer").unwrap().signature();
+        let event = decode_event(
+            &abi,
+            &log(
+                vec![transfer, from.into(), to.into()],
+                U256::from(1000).encode(),
+            ),
+        );
+        assert_eq!(
+            event,
+            Event {
+                name: Some("Transfer".to_owned()),
+                args: vec![
+                    arg("from", format!("{from:?}")),
+                    arg("to", format!("{to:?}")),
+                    arg("value", 1000),
+                ],
+            }
+        );
+        assert_eq!(
+            event.to_string(),
+            format!("Transfer(from={from:?}, to={to:?}, value=1000)")
+        );
+    }
+
+    #[test]
+    fn shows_unknown_and_anonymous_events_raw() {
+        let abi = abi();
+        let topic = H256::repeat_byte(0xab);
+        let event = decode_event(&abi, &log(vec![topic], vec![0x12, 0x34]));
+        assert_eq!(
+            event,
+            Event {
+                name: None,
+                args: vec![arg("topic0", format!("{topic:?}")), arg("data", "0x1234")],
+            }
+        );
+        assert!(event.to_string().starts_with("unknown(topic0=0xabab"));
+
+        // anonymous events have no signature topic to identify them by
+        let data = abi::encode(&[Token::Uint(7.into())]);
+        let event = decode_event(&abi, &log(vec![], data.clone()));
+        assert_eq!(event.name, None);
+        assert_eq!(
+            event.args,
+            [arg(
+                "data",
+                format!("0x{}", ethers::utils::hex::encode(data))
+            )]
+        );
+    }
+
+    #[test]
+    fn formats_amounts_without_trailing_zeros() {
+        let gwei = U256::exp10(9);
+        assert_eq!(format_amount(gwei * 30, "gwei"), "30");
+        assert_eq!(format_amount(gwei * 3 / 2, "gwei"), "1.5");
+        assert_eq!(format_amount(U256::one(), "gwei"), "0.000000001");
+        assert_eq!(format_amount(U256::zero(), "ether"), "0");
+        assert_eq!(format_amount(U256::MAX, "wei"), U256::MAX.to_string());
+    }
+
+    #[test]
+    fn flattens_events_into_one_csv_cell() {
+        let receipt = Receipt {
+            chain: "polygon".to_owned(),
+            contract: "ERC20Bridged".to_owned(),
+            function: "transfer".to_owned(),
+            tx: H256::repeat_byte(1),
+            block: Some(7),
+            status: TxStatus::Success,
+            gas_used: Some(21_000.into()),
+            effective_gas_price: Some(2.into()),
+            cost: Some(42_000.into()),
+            events: vec![
+                Event {
+                    name: Some("Transfer".to_owned()),
+                    args: vec![arg("value", 5)],
+                },
+                Event {
+                    name: None,
+                    args: vec![arg("data", "0x")],
+                },
+            ],
+        };
+        let row = CsvReceipt::from(&receipt);
+        assert_eq!(row.events, "Transfer(value=5); unknown(data=0x)");
+
+        let mut csv = csv::Writer::from_writer(vec![]);
+        csv.serialize(row).unwrap();
+        let csv = String::from_utf8(csv.into_inner().unwrap()).unwrap();
+        assert!(
+            csv.ends_with(",success,21000,2,42000,Transfer(value=5); unknown(data=0x)\n"),
+            "{csv}"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Waiting for the relayer to propagate cross-chain calls.
+//!
+//! After a transaction on one chain, its effect on another chain (a new
+//! balance, a stored value, an emitted event) shows up only once the relayer
+//! has delivered it.  [`Wait`] checks for that effect with exponential
+//! backoff until a deadline, resolving as soon as the effect is observed:
+//!
+//! ```ignore
+//! let erc20 = &erc20;
+//! let minted = Wait::new(format!("FBB balance of {to:?} to be {expected}"))
+//!     .since(sent_at)
+//!     .for_state(
+//!         || async move { erc20.balance_of(to).call().await },
+//!         |bal| *bal == expected,
+//!     )
+//!     .await?;
+//! println!("Minted after {:?}", minted.latency);
+//! ```
+
+use std::{
+    fmt::Debug,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use ethers::{
+    providers::Middleware,
+    types::{Filter, Log},
+};
+use eyre::Result;
+use thiserror::Error;
+
+/// Default time to wait for a relayed effect.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The relayed effect was not observed in time.
+#[derive(Debug, Error)]
+#[error("Timed out after {waited:?} waiting for {expected}; last observed: {last_observed}")]
+pub struct RelayTimeout {
+    /// What we were waiting for.
+    pub expected: String,
+    /// What we saw the last time we checked.
+    pub last_observed: String,
+    pub waited: Duration,
+}
+
+/// An observed relayed effect.
+#[derive(Debug, Clone)]
+pub struct Relayed<T> {
+    /// The observed state or event.
+    pub value: T,
+    /// Time from [`Wait::since`] until the effect was observed.
+    pub latency: Duration,
+}
+
+/// The result of checking for a relayed effect once.
+enum Observed<T> {
+    Done(T),
+    NotYet(String),
+}
+
+/// Waits for a relayed effect; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Wait {
+    expected: String,
+    since: Instant,
+    timeout: Duration,
+    interval: Duration,
+    max_interval: Duration,
+}
+
+impl Wait {
+    /// Wait for the effect described by `expected` (used in error messages).
+    pub fn new(expected: impl Into<String>) -> Self {
+        Self {
+            expected: expected.into(),
+            since: Instant::now(),
+            timeout: DEFAULT_TIMEOUT,
+            interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Give up (with a [`RelayTimeout`]) after `timeout`.  Defaults to [`DEFAULT_TIMEOUT`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Check after `initial`, doubling the interval after every check up to `max`.
+    /// Defaults to 100ms and 1s.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.interval = initial;
+        self.max_interval = max.max(initial);
+        self
+    }
+
+    /// Measure latency (and the timeout) from `since`, typically the time the
+    /// source-chain transaction was sent.  Defaults to the time [`Wait::new`] was called.
+    pub fn since(mut self, since: Instant) -> Self {
+        self.since = since;
+        self
+    }
+
+    /// Wait until `observe` returns a value for which `done` holds.
+    pub async fn for_state<T, E, F, Fut>(
+        &self,
+        mut observe: F,
+        done: impl Fn(&T) -> bool,
+    ) -> Result<Relayed<T>>
+    where
+        T: Debug,
+        E: Into<eyre::Report>,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.poll(|| {
+            let fut = observe();
+            let done = &done;
+            async move {
+                let val = fut.await.map_err(Into::into)?;
+                Ok(if done(&val) {
+                    Observed::Done(val)
+                } else {
+                    Observed::NotYet(format!("{val:?}"))
+                })
+            }
+        })
+        .await
+    }
+
+    /// Wait until a log matching `filter` shows up on the chain behind `provider`.
+    ///
+    /// Set the filter's starting block (e.g., to the block number before the
+    /// source-chain transaction was sent) to avoid matching older events.
+    pub async fn for_event<M>(&self, provider: &M, filter: &Filter) -> Result<Relayed<Log>>
+    where
+        M: Middleware,
+        M::Error: 'static,
+    {
+        self.poll(|| async move {
+            let mut logs = provider.get_logs(filter).await?;
+            Ok(if logs.is_empty() {
+                let block = provider.get_block_number().await?;
+                Observed::NotYet(format!("no matching events up to block {block}"))
+            } else {
+                Observed::Done(logs.swap_remove(0))
+            })
+        })
+        .await
+    }
+
+    async fn poll<T, F, Fut>(&self, mut observe: F) -> Result<Relayed<T>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Observed<T>>>,
+    {
+        let deadline = self.since + self.timeout;
+        let mut interval = self.interval;
+        loop {
+            let last_observed = match observe().await? {
+                Observed::Done(value) => {
+                    return Ok(Relayed {
+                        value,
+                        latency: self.since.elapsed(),
+                    })
+                }
+                Observed::NotYet(last) => last,
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RelayTimeout {
+                    expected: self.expected.clone(),
+                    last_observed,
+                    waited: self.since.elapsed(),
+                }
+                .into());
+            }
+            tokio::time::sleep(interval.min(deadline - now)).await;
+            interval = (interval * 2).min(self.max_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counter() -> impl FnMut() -> std::future::Ready<Result<u32>> {
+        let mut n = 0;
+        move || {
+            n += 1;
+            std::future::ready(Ok(n))
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_on_the_first_matching_value() {
+        let wait = Wait::new("3").backoff(Duration::from_millis(1), Duration::from_millis(1));
+        let relayed = wait.for_state(counter(), |n| *n >= 3).await.unwrap();
+        assert_eq!(relayed.value, 3);
+
+        let relayed = wait.for_state(counter(), |_| true).await.unwrap();
+        assert_eq!(relayed.value, 1);
+    }
+
+    #[tokio::test]
+    async fn caps_the_backoff() {
+        // doubling from 1ms without a cap would check only 8 times in 200ms
+        let mut checks = 0;
+        let err = Wait::new("never")
+            .timeout(Duration::from_millis(200))
+            .backoff(Duration::from_millis(1), Duration::from_millis(4))
+            .for_state(
+                || {
+                    checks += 1;
+                    std::future::ready(Ok::<_, eyre::Report>(()))
+                },
+                |_| false,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<RelayTimeout>().is_some(), "{err}");
+        assert!(checks > 20, "only {checks} checks");
+    }
+
+    #[tokio::test]
+    async fn times_out_with_the_expected_and_last_observed_states() {
+        let err = Wait::new("value to be 0")
+            .timeout(Duration::from_millis(20))
+            .backoff(Duration::from_millis(1), Duration::from_millis(2))
+            .for_state(counter(), |n| *n == 0)
+            .await
+            .unwrap_err();
+        let timeout = err.downcast_ref::<RelayTimeout>().unwrap();
+        assert_eq!(timeout.expected, "value to be 0");
+        assert!(timeout.waited >= Duration::from_millis(20));
+        let last: u32 = timeout.last_observed.parse().unwrap();
+        assert!(last > 1, "{last}");
+        let msg = err.to_string();
+        assert!(msg.contains("waiting for value to be 0"), "{msg}");
+        assert!(msg.contains(&format!("last observed: {last}")), "{msg}");
+    }
+}
@@ -0,0 +1,380 @@
+//! Where the keys that sign transactions come from.
+//!
+//! By default, transactions are signed by the local accounts Cubist derives
+//! from the mnemonics in the config file.  A project's [`SIGNERS_FILE`] can
+//! instead select, per chain, an encrypted JSON keystore or a single private
+//! key taken from an environment variable:
+//!
+//! ```json
+//! {
+//!   "ethereum": { "keystore": { "path": "keys/ethereum.json", "password_file": "/run/secrets/eth" } },
+//!   "polygon": { "private_key": { "env": "POLYGON_PRIVATE_KEY" } }
+//! }
+//! ```
+//!
+//! Keystore passwords are read from `password_file`, if given, or prompted
+//! for.  The file only refers to secrets, so it can be committed; relative
+//! paths are relative to the file itself.
+
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use ethers::{
+    contract::builders::ContractCall,
+    middleware::SignerMiddleware,
+    providers::Middleware,
+    signers::{LocalWallet, Signer},
+    types::{Address, TransactionReceipt},
+};
+use eyre::{bail, ensure, eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::address::Resolver;
+
+/// Name of the signers file, kept next to `cubist-config.json`.
+pub const SIGNERS_FILE: &str = "signers.json";
+
+/// Where the key signing transactions on a chain comes from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SignerSource {
+    /// The local accounts Cubist derives from the chain's mnemonic.
+    #[default]
+    Mnemonic,
+    /// An encrypted JSON keystore (e.g., one created with `geth account new`).
+    Keystore {
+        path: PathBuf,
+        /// File holding the keystore's password; prompted for if not given.
+        password_file: Option<PathBuf>,
+    },
+    /// A hex private key taken from an environment variable.
+    PrivateKey { env: String },
+}
+
+impl fmt::Display for SignerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignerSource::Mnemonic => write!(f, "mnemonic"),
+            SignerSource::Keystore { path, .. } => write!(f, "keystore {}", path.display()),
+            SignerSource::PrivateKey { env } => write!(f, "private key from ${env}"),
+        }
+    }
+}
+
+/// The signer source of each chain, loaded from a [`SIGNERS_FILE`].
+#[derive(Debug, Default)]
+pub struct Signers {
+    sources: BTreeMap<String, SignerSource>,
+}
+
+impl Signers {
+    /// Load the signers file from `path`; with a missing file, every chain uses
+    /// its mnemonic.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let ctx = || format!("Reading signers file {}", path.display());
+        let mut sources: BTreeMap<String, SignerSource> =
+            serde_json::from_str(&fs::read_to_string(path).with_context(ctx)?).with_context(ctx)?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for source in sources.values_mut() {
+            if let SignerSource::Keystore {
+                path,
+                password_file,
+            } = source
+            {
+                *path = dir.join(&*path);
+                if let Some(file) = password_file {
+                    *file = dir.join(&*file);
+                }
+            }
+        }
+        Ok(Self { sources })
+    }
+
+    /// The signer source of `chain`.
+    pub fn source(&self, chain: &str) -> &SignerSource {
+        const MNEMONIC: &SignerSource = &SignerSource::Mnemonic;
+        self.sources.get(chain).unwrap_or(MNEMONIC)
+    }
+
+    /// The address of the wallet signing transactions on `chain`, unless that
+    /// chain uses its mnemonic.  Unlike [`Signers::wallet`], this never prompts:
+    /// a keystore's address is taken from the (unencrypted) `address` field.
+    pub fn address(&self, chain: &str) -> Result<Option<Address>> {
+        match self.source(chain) {
+            SignerSource::Mnemonic => Ok(None),
+            SignerSource::Keystore { path, .. } => {
+                let ctx = || format!("Reading keystore {}", path.display());
+                let json: Value =
+                    serde_json::from_str(&fs::read_to_string(path).with_context(ctx)?)
+                        .with_context(ctx)?;
+                let address = json["address"].as_str().ok_or_else(|| {
+                    eyre!("Keystore {} does not record its address", path.display())
+                })?;
+                Ok(Some(address.parse().with_context(ctx)?))
+            }
+            SignerSource::PrivateKey { .. } => Ok(self.wallet(chain)?.map(|w| w.address())),
+        }
+    }
+
+    /// The wallet signing transactions on `chain`, unless that chain uses its
+    /// mnemonic; this may prompt for a keystore password.
+    pub fn wallet(&self, chain: &str) -> Result<Option<LocalWallet>> {
+        let source = self.source(chain);
+        let ctx = || format!("Loading the {source} signing on {chain}");
+        let wallet = match source {
+            SignerSource::Mnemonic => return Ok(None),
+            SignerSource::Keystore {
+                path,
+                password_file,
+            } => {
+                let password = match password_file {
+                    Some(file) => read_password_file(file),
+                    None => {
+                        prompt_password(&format!("Password for {} ({chain}): ", path.display()))
+                    }
+                }
+                .with_context(ctx)?;
+                LocalWallet::decrypt_keystore(path, password).with_context(ctx)?
+            }
+            SignerSource::PrivateKey { env } => env::var(env)
+                .map_err(|_| eyre!("Environment variable {env} is not set"))
+                .and_then(|key| parse_private_key(&key))
+                .with_context(ctx)?,
+        };
+        Ok(Some(wallet))
+    }
+
+    /// The address of the account signing transactions on `chain`: the wallet
+    /// selected for that chain, if any, or else the local account selected with
+    /// '--from' (`from`), or `default`.  Like [`Signers::address`], this never
+    /// prompts.
+    pub fn signer_address(
+        &self,
+        chain: &str,
+        from: Option<&str>,
+        resolver: &Resolver,
+        default: Address,
+    ) -> Result<Address> {
+        match self.address(chain)? {
+            Some(addr) => {
+                check_from(chain, addr, from, resolver)?;
+                Ok(addr)
+            }
+            None => from.map_or(Ok(default), |from| resolver.resolve_signer(chain, from)),
+        }
+    }
+
+    /// The account signing transactions on `chain` (see
+    /// [`Signers::signer_address`]), with its wallet unlocked; this may prompt
+    /// for a keystore password.
+    pub fn signer(
+        &self,
+        chain: &str,
+        from: Option<&str>,
+        resolver: &Resolver,
+        default: Address,
+    ) -> Result<ChainSigner> {
+        match self.wallet(chain)? {
+            Some(wallet) => {
+                check_from(chain, wallet.address(), from, resolver)?;
+                Ok(ChainSigner::wallet(wallet))
+            }
+            None => Ok(ChainSigner::account(from.map_or(Ok(default), |from| {
+                resolver.resolve_signer(chain, from)
+            })?)),
+        }
+    }
+}
+
+/// Check that '--from' (`from`), if given, selects `addr`, the wallet signing on
+/// `chain`; bare indices refer to the local accounts on `chain`.
+fn check_from(chain: &str, addr: Address, from: Option<&str>, resolver: &Resolver) -> Result<()> {
+    let Some(from) = from else {
+        return Ok(());
+    };
+    let from = match from.trim().parse::<usize>() {
+        Ok(i) => resolver.account(chain, i)?,
+        Err(_) => resolver.resolve(from)?,
+    };
+    ensure!(
+        from == addr,
+        "Transactions on {chain} are signed by {addr:?} (see {SIGNERS_FILE}), not {from:?}"
+    );
+    Ok(())
+}
+
+/// The account signing transactions on a chain: one of the local accounts
+/// Cubist derives from the chain's mnemonic, or a [`Signers`] wallet.
+#[derive(Debug, Clone)]
+pub struct ChainSigner {
+    address: Address,
+    wallet: Option<LocalWallet>,
+}
+
+impl ChainSigner {
+    /// A local account, whose transactions Cubist signs.
+    pub fn account(address: Address) -> Self {
+        Self {
+            address,
+            wallet: None,
+        }
+    }
+
+    /// A wallet, which signs transactions itself.
+    pub fn wallet(wallet: LocalWallet) -> Self {
+        Self {
+            address: wallet.address(),
+            wallet: Some(wallet),
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Sign and send `call` (whose sender must be this signer) with `client`,
+    /// and wait for it to be mined.
+    pub async fn send<C, M, D>(
+        &self,
+        client: &C,
+        call: &ContractCall<M, D>,
+    ) -> Result<TransactionReceipt>
+    where
+        C: Middleware,
+        C::Provider: Clone + 'static,
+        M: Middleware + 'static,
+        D: ethers::abi::Detokenize,
+    {
+        let receipt = match &self.wallet {
+            None => call.send().await?.await?,
+            Some(wallet) => {
+                let provider = client.provider().clone();
+                let chain_id = provider.get_chainid().await?.as_u64();
+                SignerMiddleware::new(provider, wallet.clone().with_chain_id(chain_id))
+                    .send_transaction(call.tx.clone(), call.block)
+                    .await?
+                    .await?
+            }
+        };
+        receipt.ok_or_else(|| eyre!("Transaction calling {} was dropped", call.function.name))
+    }
+}
+
+/// Parse a hex private key, with or without a `0x` prefix.
+pub fn parse_private_key(key: &str) -> Result<LocalWallet> {
+    let key = key.trim();
+    Ok(key.strip_prefix("0x").unwrap_or(key).parse()?)
+}
+
+fn read_password_file(path: &Path) -> Result<String> {
+    let password =
+        fs::read_to_string(path).context(format!("Reading password file {}", path.display()))?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Read a password from the terminal without echoing it.
+fn prompt_password(prompt: &str) -> Result<String> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        bail!("Cannot prompt for a password without a terminal; set 'password_file' in {SIGNERS_FILE}");
+    }
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+    stty("-echo");
+    let mut password = String::new();
+    let read = stdin.lock().read_line(&mut password);
+    stty("echo");
+    eprintln!();
+    read?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+fn stty(arg: &str) {
+    // best effort: without 'stty', the password is echoed
+    let _ = Command::new("stty")
+        .arg(arg)
+        .stdin(Stdio::inherit())
+        .status();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a well-known test key (the first Hardhat account)
+    const KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    fn resolver() -> Resolver {
+        let mut r = Resolver::new(None);
+        r.add_accounts(
+            "ethereum",
+            vec![Address::repeat_byte(1), Address::repeat_byte(2)],
+        );
+        r.add_accounts("polygon", vec![Address::repeat_byte(3)]);
+        r
+    }
+
+    fn signers(env: &str) -> Signers {
+        let source = SignerSource::PrivateKey { env: env.into() };
+        Signers {
+            sources: BTreeMap::from([("polygon".to_owned(), source)]),
+        }
+    }
+
+    #[test]
+    fn selects_local_accounts_without_wallets() {
+        let (r, default) = (resolver(), Address::repeat_byte(9));
+        let signers = Signers::default();
+        let addr = |from| signers.signer_address("ethereum", from, &r, default);
+        assert_eq!(addr(None).unwrap(), default);
+        assert_eq!(addr(Some("1")).unwrap(), Address::repeat_byte(2));
+        assert_eq!(addr(Some("polygon:0")).unwrap_err().to_string(),
+            format!("{:?} cannot sign on 'ethereum'; expected one of its local accounts: 0 ({:?}), 1 ({:?})",
+                Address::repeat_byte(3), Address::repeat_byte(1), Address::repeat_byte(2)));
+        let signer = signers.signer("ethereum", Some("0"), &r, default).unwrap();
+        assert_eq!(signer.address(), Address::repeat_byte(1));
+    }
+
+    #[test]
+    fn selects_wallets_over_local_accounts() {
+        let env = "TEMPLATE_SUPPORT_TEST_SIGNER_KEY";
+        env::set_var(env, KEY);
+        let (r, default) = (resolver(), Address::repeat_byte(9));
+        let signers = signers(env);
+        let wallet = parse_private_key(KEY).unwrap().address();
+        assert_eq!(
+            signers
+                .signer_address("polygon", None, &r, default)
+                .unwrap(),
+            wallet
+        );
+        let from = format!("{wallet:?}");
+        let signer = signers.signer("polygon", Some(&from), &r, default).unwrap();
+        assert_eq!(signer.address(), wallet);
+        // '--from' must agree with the wallet
+        let err = signers
+            .signer_address("polygon", Some("0"), &r, default)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains(&format!("signed by {wallet:?}")),
+            "{err}"
+        );
+        // other chains still use their mnemonic
+        assert_eq!(
+            signers
+                .signer_address("ethereum", Some("1"), &r, default)
+                .unwrap(),
+            Address::repeat_byte(2)
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Styled terminal output.
+//!
+//! The `s_*` macros are used throughout the CLIs to highlight actions
+//! (e.g., "Deploying"), values (addresses, amounts), contract names and
+//! table headers.  Styles are only applied when stdout supports colors, so
+//! piping a command's output into a file or another program yields plain
+//! text.
+
+use std::fmt::{self, Display};
+
+pub use owo_colors::{OwoColorize, Stream, Style};
+
+/// Style `$x` with `$style`, but only if stdout supports colors.
+#[macro_export]
+macro_rules! s_styled {
+    ($x: expr, $style: expr) => {{
+        use $crate::style::OwoColorize as _;
+        $x.if_supports_color($crate::style::Stream::Stdout, |x| x.style($style))
+    }};
+}
+
+/// Style an action (e.g., "Calling", "Deploying").
+#[macro_export]
+macro_rules! s_action {
+    ($x: expr) => {
+        $crate::s_styled!($x, $crate::style::Style::new().bold().green())
+    };
+}
+
+/// Style a value (e.g., an address or an amount).
+#[macro_export]
+macro_rules! s_value {
+    ($x: expr) => {
+        $crate::s_styled!($x, $crate::style::Style::new().yellow())
+    };
+}
+
+/// Style a contract name.
+#[macro_export]
+macro_rules! s_contract {
+    ($x: expr) => {
+        $crate::s_styled!($x, $crate::style::Style::new().blue())
+    };
+}
+
+/// Style a table header.
+#[macro_export]
+macro_rules! s_header {
+    ($x: expr) => {
+        $crate::s_styled!($x, $crate::style::Style::new().bold())
+    };
+}
+
+/// Style a value that changed since it was last shown.
+#[macro_export]
+macro_rules! s_changed {
+    ($x: expr) => {
+        $crate::s_styled!($x, $crate::style::Style::new().bold().black().on_yellow())
+    };
+}
+
+/// Style an error or a failed check.
+#[macro_export]
+macro_rules! s_error {
+    ($x: expr) => {
+        $crate::s_styled!($x, $crate::style::Style::new().bold().red())
+    };
+}
+
+/// A value that is styled with [`s_changed!`] if it changed since it was
+/// last shown, and with [`s_value!`] otherwise.
+#[derive(Debug, Clone)]
+pub struct Tracked<T> {
+    pub value: T,
+    pub changed: bool,
+}
+
+impl<T> Tracked<T> {
+    pub fn new(value: T, changed: bool) -> Self {
+        Self { value, changed }
+    }
+}
+
+impl<T: Display> Display for Tracked<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // forward the formatter so that padding still applies
+        if self.changed {
+            Display::fmt(&s_changed!(self.value), f)
+        } else {
+            Display::fmt(&s_value!(self.value), f)
+        }
+    }
+}
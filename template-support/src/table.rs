@@ -0,0 +1,124 @@
+//! Box-drawing tables.
+
+use std::fmt::{self, Display};
+
+use eyre::{ensure, Result};
+
+const SEP_TOP: (&str, &str, &str) = ("┌─", "─┬─", "─┐");
+const SEP_MID: (&str, &str, &str) = ("├─", "─┼─", "─┤");
+const SEP_DEF: (&str, &str, &str) = ("│ ", " │ ", " │");
+const SEP_BOT: (&str, &str, &str) = ("└─", "─┴─", "─┘");
+
+/// Horizontal alignment of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// A table column: fixed width (longer values are truncated) and alignment.
+#[derive(Debug, Clone, Copy)]
+pub struct Column {
+    pub width: usize,
+    pub align: Align,
+}
+
+impl Column {
+    pub const fn left(width: usize) -> Self {
+        Self {
+            width,
+            align: Align::Left,
+        }
+    }
+
+    pub const fn right(width: usize) -> Self {
+        Self {
+            width,
+            align: Align::Right,
+        }
+    }
+
+    fn pad(&self, cell: &dyn Display) -> String {
+        let w = self.width;
+        match self.align {
+            Align::Left => format!("{cell:<w$.w$}"),
+            Align::Right => format!("{cell:>w$.w$}"),
+        }
+    }
+}
+
+/// A table with a header row, rendered with box-drawing characters.
+///
+/// Cells are padded (and truncated) when they are added, so styled values
+/// (see [`crate::style`]) line up the same way plain ones do.
+#[derive(Debug)]
+pub struct Table {
+    columns: Vec<Column>,
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Create a table with the given columns and header cells.
+    ///
+    /// Fails if there are not as many header cells as columns.
+    pub fn new(columns: &[Column], header: &[&dyn Display]) -> Result<Self> {
+        let mut table = Self {
+            columns: columns.to_vec(),
+            header: vec![],
+            rows: vec![],
+        };
+        table.header = table.pad_cells(header)?;
+        Ok(table)
+    }
+
+    /// Append a row.
+    ///
+    /// Fails if there are not as many cells as columns.
+    pub fn row(&mut self, cells: &[&dyn Display]) -> Result<&mut Self> {
+        let row = self.pad_cells(cells)?;
+        self.rows.push(row);
+        Ok(self)
+    }
+
+    /// Print the table to stdout.
+    pub fn print(&self) {
+        print!("{self}");
+    }
+
+    fn pad_cells(&self, cells: &[&dyn Display]) -> Result<Vec<String>> {
+        ensure!(
+            self.columns.len() == cells.len(),
+            "Wrong number of cells: expected {}, got {}",
+            self.columns.len(),
+            cells.len()
+        );
+        Ok(self
+            .columns
+            .iter()
+            .zip(cells)
+            .map(|(col, cell)| col.pad(*cell))
+            .collect())
+    }
+
+    fn fmt_border(&self, f: &mut fmt::Formatter<'_>, sep: (&str, &str, &str)) -> fmt::Result {
+        let dashes: Vec<String> = self.columns.iter().map(|c| "─".repeat(c.width)).collect();
+        fmt_line(f, &dashes, sep)
+    }
+}
+
+fn fmt_line(f: &mut fmt::Formatter<'_>, cells: &[String], sep: (&str, &str, &str)) -> fmt::Result {
+    writeln!(f, "{}{}{}", sep.0, cells.join(sep.1), sep.2)
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_border(f, SEP_TOP)?;
+        fmt_line(f, &self.header, SEP_DEF)?;
+        self.fmt_border(f, SEP_MID)?;
+        for row in &self.rows {
+            fmt_line(f, row, SEP_DEF)?;
+        }
+        self.fmt_border(f, SEP_BOT)
+    }
+}
@@ -0,0 +1,137 @@
+//! Integration-test harness, the Rust counterpart of the JavaScript SDK's `TestDK`.
+//!
+//! A [`TestEnv`] deploys into a fresh temporary directory (like `TestDK`'s
+//! `tmp_deploy_dir: true`) and starts the Cubist services for the duration of
+//! a test; the services are always stopped when the [`TestEnv`] is dropped,
+//! even if the test panics.
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn deploys_and_stores() -> eyre::Result<()> {
+//!     let _env = TestEnv::start(env!("CARGO_MANIFEST_DIR"))?;
+//!     let receiver = StorageReceiver::deploy(U256::from(55)).await?;
+//!     // ...
+//!     Ok(())
+//! }
+//! ```
+//!
+//! The temporary deployment directory is selected by writing a copy of the
+//! project's `cubist-config.json` (with all paths made absolute) into the
+//! temporary directory and pointing the `CUBIST_CONFIG` environment variable
+//! at it.  Since that variable is process-wide, environments in the same test
+//! binary run one at a time.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, MutexGuard},
+};
+
+use eyre::{bail, Context, Result};
+use serde_json::Value;
+use tempfile::TempDir;
+
+use crate::config::{copy_config, CONFIG_ENV, CONFIG_FILE};
+
+/// Held by the active [`TestEnv`], so that environments don't overlap.
+static ACTIVE: Mutex<()> = Mutex::new(());
+
+/// A temporary deployment of a template, with Cubist services running.
+pub struct TestEnv {
+    project_dir: PathBuf,
+    tmp_dir: TempDir,
+    config: PathBuf,
+    running: bool,
+    _active: MutexGuard<'static, ()>,
+}
+
+impl TestEnv {
+    /// Prepare (but don't start) a test environment for the template in
+    /// `project_dir`, which is typically `env!("CARGO_MANIFEST_DIR")`.
+    pub fn new(project_dir: impl AsRef<Path>) -> Result<Self> {
+        // a previous test panicking while holding the lock is no reason to fail this one
+        let active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+
+        let project_dir = project_dir.as_ref().canonicalize()?;
+        let tmp_dir = tempfile::Builder::new().prefix("cubist-test").tempdir()?;
+        let config = tmp_dir.path().join(CONFIG_FILE);
+        let deploy_dir = tmp_dir.path().join("deploy");
+        write_tmp_config(&project_dir, &config, &deploy_dir)?;
+        env::set_var(CONFIG_ENV, &config);
+
+        Ok(Self {
+            project_dir,
+            tmp_dir,
+            config,
+            running: false,
+            _active: active,
+        })
+    }
+
+    /// Prepare a test environment and start the Cubist services.
+    pub fn start(project_dir: impl AsRef<Path>) -> Result<Self> {
+        let mut env = Self::new(project_dir)?;
+        env.start_service()?;
+        Ok(env)
+    }
+
+    /// The config file used by this environment.
+    pub fn config(&self) -> &Path {
+        &self.config
+    }
+
+    /// The temporary deployment directory.
+    pub fn deploy_dir(&self) -> PathBuf {
+        self.tmp_dir.path().join("deploy")
+    }
+
+    /// Start the Cubist services (chains and relayer).
+    pub fn start_service(&mut self) -> Result<()> {
+        // clean up after anybody who didn't
+        self.cubist("stop")?;
+        self.cubist("start")?;
+        self.running = true;
+        Ok(())
+    }
+
+    /// Stop the Cubist services.  Also done automatically on drop.
+    pub fn stop_service(&mut self) -> Result<()> {
+        self.running = false;
+        self.cubist("stop")
+    }
+
+    fn cubist(&self, cmd: &str) -> Result<()> {
+        let status = Command::new("cubist")
+            .arg(cmd)
+            .current_dir(&self.project_dir)
+            .env(CONFIG_ENV, &self.config)
+            .status()
+            .context("Running 'cubist'; is the Cubist CLI installed?")?;
+        if !status.success() {
+            bail!("'cubist {cmd}' failed: {status}");
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        if self.running {
+            if let Err(e) = self.stop_service() {
+                eprintln!("Failed to stop Cubist services: {e:?}");
+            }
+        }
+    }
+}
+
+/// Copy the project's config to `config`, making paths absolute and
+/// pointing the deployment directory at `deploy_dir`.
+fn write_tmp_config(project_dir: &Path, config: &Path, deploy_dir: &Path) -> Result<()> {
+    copy_config(&project_dir.join(CONFIG_FILE), config, |obj| {
+        obj.insert(
+            "deploy_dir".into(),
+            Value::from(deploy_dir.to_string_lossy().into_owned()),
+        );
+    })
+}
@@ -0,0 +1,42 @@
+//! Watching chains for new blocks.
+
+use std::time::Duration;
+
+use ethers::{
+    providers::Middleware,
+    types::{H256, U256},
+};
+use eyre::Result;
+use futures::{stream::select_all, Stream};
+
+/// A stream that yields (the hash of) every new block on any of the chains
+/// behind `providers`.  Each chain is polled every `interval`.
+pub async fn new_blocks<'a, M>(
+    providers: impl IntoIterator<Item = &'a M>,
+    interval: Duration,
+) -> Result<impl Stream<Item = H256> + Unpin + 'a>
+where
+    M: Middleware + 'a,
+    M::Error: 'static,
+{
+    let mut watchers = vec![];
+    for provider in providers {
+        watchers.push(provider.watch_blocks().await?.interval(interval));
+    }
+    Ok(select_all(watchers))
+}
+
+/// The change from `prev` to `cur`, e.g., "+5" or "-5".  `None` if the amount
+/// did not change (or if either amount is unknown).
+pub fn delta(prev: Option<U256>, cur: Option<U256>) -> Option<String> {
+    match (prev, cur) {
+        (Some(prev), Some(cur)) if cur > prev => Some(format!("+{}", cur - prev)),
+        (Some(prev), Some(cur)) if cur < prev => Some(format!("-{}", prev - cur)),
+        _ => None,
+    }
+}
+
+/// Clear the terminal and move the cursor to the top-left corner.
+pub fn clear_screen() {
+    print!("\x1B[2J\x1B[H");
+}